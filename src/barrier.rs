@@ -0,0 +1,71 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{LucidError, Result};
+
+/// A barrier shared between several processes. The number of processes that arrived so far
+/// is stored as a decimal number in a file which is only accessed while holding a lock on it.
+pub struct Barrier {
+    path: PathBuf,
+    count: u64,
+}
+
+fn read_counter(file: &mut File) -> Result<u64> {
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .map_err(|_| LucidError::BarrierError)?;
+    let content = content.trim();
+    if content.is_empty() {
+        return Ok(0);
+    }
+    content.parse().map_err(|_| LucidError::BarrierError)
+}
+
+impl Barrier {
+    /// Registers the arrival of this process at the barrier and returns the barrier along
+    /// with the number of processes that have arrived so far (including this one).
+    pub fn arrive(path: &Path, count: u64) -> Result<(Barrier, u64)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|_| LucidError::BarrierError)?;
+        file.lock().map_err(|_| LucidError::BarrierError)?;
+
+        let arrived = read_counter(&mut file)? + 1;
+        file.seek(SeekFrom::Start(0))
+            .and_then(|_| file.set_len(0))
+            .and_then(|_| writeln!(file, "{}", arrived))
+            .map_err(|_| LucidError::BarrierError)?;
+
+        let barrier = Barrier {
+            path: path.to_path_buf(),
+            count,
+        };
+        Ok((barrier, arrived))
+    }
+
+    /// Returns true once the expected number of processes has arrived.
+    pub fn is_complete(&self) -> Result<bool> {
+        let mut file = File::open(&self.path).map_err(|_| LucidError::BarrierError)?;
+        file.lock_shared().map_err(|_| LucidError::BarrierError)?;
+        Ok(read_counter(&mut file)? >= self.count)
+    }
+}
+
+#[test]
+fn test_barrier() {
+    let path = crate::TempPath::new("barrier");
+
+    let (first, arrived) = Barrier::arrive(&path, 2).unwrap();
+    assert_eq!(1, arrived);
+    assert_eq!(Ok(false), first.is_complete());
+
+    let (second, arrived) = Barrier::arrive(&path, 2).unwrap();
+    assert_eq!(2, arrived);
+    assert_eq!(Ok(true), first.is_complete());
+    assert_eq!(Ok(true), second.is_complete());
+}
@@ -0,0 +1,76 @@
+//! ANSI colors for messages, see `--color`.
+
+use std::env;
+use std::io::{self, IsTerminal};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    /// Color output to a terminal, unless `NO_COLOR` is set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output to a stream, given whether that stream is a terminal.
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                is_terminal && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+
+    /// Renders the error that lucid exits with, in red if stderr is colored.
+    pub fn format_error(self, msg: &str) -> String {
+        let text = format!("Error: {}", msg);
+        if self.enabled(io::stderr().is_terminal()) {
+            paint(ERROR, &text)
+        } else {
+            text
+        }
+    }
+}
+
+/// Colors for prefixes. Red is left out, since it is reserved for errors.
+const PREFIX_COLORS: [&str; 10] = ["36", "32", "33", "34", "35", "96", "92", "93", "94", "95"];
+
+pub const ERROR: &str = "31";
+pub const SIGNAL: &str = "1;33";
+pub const DEBUG: &str = "2";
+
+/// The color of a prefix, which is always the same for the same prefix (so that the output
+/// of several instances with different prefixes can be told apart).
+pub fn prefix_color(prefix: &str) -> &'static str {
+    // FNV-1a, which is stable across Rust versions (unlike `DefaultHasher`)
+    let hash = prefix.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+    PREFIX_COLORS[hash as usize % PREFIX_COLORS.len()]
+}
+
+/// Wraps `text` in the escape sequences for the given SGR parameters, e.g. `1;33`.
+pub fn paint(color: &str, text: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", color, text)
+}
+
+#[test]
+fn test_colors() {
+    assert_eq!("\x1b[1;33mSIGTERM\x1b[0m", paint(SIGNAL, "SIGTERM"));
+    assert_eq!(prefix_color("worker-1"), prefix_color("worker-1"));
+    assert_ne!(prefix_color("worker-1"), prefix_color("worker-2"));
+    assert!(["lucid", "a", "b", "c", ""]
+        .iter()
+        .all(|prefix| prefix_color(prefix) != ERROR));
+
+    assert!(ColorChoice::Always.enabled(false));
+    assert!(!ColorChoice::Never.enabled(true));
+    assert!(!ColorChoice::Auto.enabled(false));
+    assert_eq!("Error: failed", ColorChoice::Never.format_error("failed"));
+    assert_eq!(
+        "\x1b[31mError: failed\x1b[0m",
+        ColorChoice::Always.format_error("failed")
+    );
+}
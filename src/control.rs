@@ -0,0 +1,185 @@
+//! A line-based protocol to drive a running lucid from a test harness, see `--control-socket`.
+//!
+//! Each line is a command (`STATUS`, `WAKE`, `EXIT <code>` or `SET-VERBOSITY <level>`) that is
+//! answered with a single line, either the status, `OK` or `ERROR <description>`.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+use crate::settings::parse_verbosity;
+use crate::{ExitCode, Result, VerbosityLevel};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(not(unix), allow(dead_code))]
+pub enum Command {
+    /// Reply with the status line that SIGUSR1 prints.
+    Status,
+    /// End the sleep as if it had elapsed.
+    Wake,
+    /// End the sleep and exit with the given code.
+    Exit(ExitCode),
+    SetVerbosity(VerbosityLevel),
+}
+
+impl Command {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    fn parse(line: &str) -> std::result::Result<Command, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default().to_ascii_uppercase();
+        let argument = words.next();
+        if words.next().is_some() {
+            return Err("too many arguments".into());
+        }
+        match (command.as_str(), argument) {
+            ("STATUS", None) => Ok(Command::Status),
+            ("WAKE", None) => Ok(Command::Wake),
+            ("EXIT", Some(code)) => code
+                .parse()
+                .map(Command::Exit)
+                .map_err(|_| format!("invalid exit code '{}'", code)),
+            ("SET-VERBOSITY", Some(level)) => parse_verbosity(level)
+                .map(Command::SetVerbosity)
+                .ok_or_else(|| format!("invalid verbosity '{}'", level)),
+            ("STATUS" | "WAKE", Some(_)) => Err("too many arguments".into()),
+            ("EXIT" | "SET-VERBOSITY", None) => Err("missing argument".into()),
+            _ => Err(format!("unknown command '{}'", command)),
+        }
+    }
+}
+
+/// A command from a client, which waits for the reply.
+pub struct Request {
+    pub command: Command,
+    client: Box<dyn Write + Send>,
+    replied: mpsc::Sender<()>,
+}
+
+impl Request {
+    /// Sends the reply right away, such that it arrives even if lucid exits next.
+    pub fn reply(mut self, response: &str) {
+        writeln!(self.client, "{}", response).ok();
+        self.replied.send(()).ok();
+    }
+}
+
+/// Listens on a Unix socket. Commands are collected by a background thread, which wakes up
+/// the main loop (see `signals::wake`). The socket file is removed when this is dropped.
+pub struct ControlSocket {
+    path: PathBuf,
+    requests: mpsc::Receiver<Request>,
+}
+
+impl ControlSocket {
+    /// Returns (without blocking) the requests that arrived since the last call.
+    pub fn take_requests(&self) -> Vec<Request> {
+        self.requests.try_iter().collect()
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+#[cfg(unix)]
+impl ControlSocket {
+    pub fn bind(path: PathBuf) -> Result<ControlSocket> {
+        use std::fs;
+        use std::os::unix::fs::FileTypeExt;
+        use std::os::unix::net::UnixListener;
+        use std::thread;
+
+        use crate::LucidError;
+
+        // A socket left behind by a previous run is replaced, any other file is not touched
+        if fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            fs::remove_file(&path).ok();
+        }
+        let listener = UnixListener::bind(&path).map_err(|_| LucidError::ControlSocketError)?;
+
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                thread::spawn(move || serve(stream, sender));
+            }
+        });
+
+        Ok(ControlSocket { path, requests })
+    }
+}
+
+#[cfg(not(unix))]
+impl ControlSocket {
+    pub fn bind(_: PathBuf) -> Result<ControlSocket> {
+        Err(crate::LucidError::Unsupported("--control-socket"))
+    }
+}
+
+/// Answers the commands of one client until it disconnects.
+#[cfg(unix)]
+fn serve(stream: std::os::unix::net::UnixStream, sender: mpsc::Sender<Request>) {
+    use std::io::{BufRead, BufReader};
+
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        let result = match Command::parse(&line) {
+            Ok(command) => {
+                let client = match writer.try_clone() {
+                    Ok(client) => Box::new(client),
+                    Err(_) => return,
+                };
+                // Wait for the reply, such that replies are in the order of the commands
+                let (replied, reply) = mpsc::channel();
+                let request = Request {
+                    command,
+                    client,
+                    replied,
+                };
+                if sender.send(request).is_err() {
+                    return;
+                }
+                crate::signals::wake();
+                match reply.recv() {
+                    Ok(()) => Ok(()),
+                    Err(_) => writeln!(writer, "ERROR lucid is exiting"),
+                }
+            }
+            Err(err) => writeln!(writer, "ERROR {}", err),
+        };
+        if result.is_err() {
+            return;
+        }
+    }
+}
+
+#[test]
+fn test_parse_command() {
+    assert_eq!(Ok(Command::Status), Command::parse("STATUS"));
+    assert_eq!(Ok(Command::Wake), Command::parse(" wake "));
+    assert_eq!(Ok(Command::Exit(3)), Command::parse("EXIT 3"));
+    assert_eq!(
+        Ok(Command::SetVerbosity(VerbosityLevel::Quiet)),
+        Command::parse("SET-VERBOSITY quiet")
+    );
+    assert_eq!(
+        Err("invalid exit code 'x'".into()),
+        Command::parse("EXIT x")
+    );
+    assert_eq!(Err("missing argument".into()), Command::parse("EXIT"));
+    assert_eq!(Err("too many arguments".into()), Command::parse("WAKE now"));
+    assert_eq!(
+        Err("unknown command 'SLEEP'".into()),
+        Command::parse("sleep")
+    );
+}
@@ -0,0 +1,100 @@
+use std::process;
+
+use crate::memory::MemoryHog;
+use crate::ExitCode;
+
+/// How to terminate abnormally, see `--crash-mode`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrashMode {
+    /// Die from SIGSEGV, like after an invalid memory access.
+    Segfault,
+    /// Die from SIGABRT.
+    Abort,
+    /// Panic, which exits with code 101.
+    Panic,
+    /// Allocate memory until an allocation fails or the process is killed.
+    OutOfMemory,
+    /// Die from SIGQUIT with core dumps enabled, such that a core file is written.
+    Core,
+}
+
+impl CrashMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            CrashMode::Segfault => "segfault",
+            CrashMode::Abort => "abort",
+            CrashMode::Panic => "panic",
+            CrashMode::OutOfMemory => "oom",
+            CrashMode::Core => "core",
+        }
+    }
+
+    /// The exit code that a Unix shell reports for the crash (128 + the signal number, or 101
+    /// for a panic). Running out of memory usually ends with an abort.
+    pub fn exit_code(self) -> ExitCode {
+        match self {
+            CrashMode::Segfault => 128 + 11,
+            CrashMode::Abort | CrashMode::OutOfMemory => 128 + 6,
+            CrashMode::Panic => 101,
+            CrashMode::Core => 128 + 3,
+        }
+    }
+}
+
+/// Terminates the process as described by `mode`, without any cleanup.
+pub fn crash(mode: CrashMode) -> ! {
+    match mode {
+        CrashMode::Segfault => {
+            // SAFETY: restoring the default disposition and raising a signal has no
+            // preconditions. The Rust runtime's own SIGSEGV handler would ignore a raised signal.
+            unsafe {
+                libc::signal(libc::SIGSEGV, libc::SIG_DFL);
+                libc::raise(libc::SIGSEGV);
+            }
+            process::abort()
+        }
+        CrashMode::Abort => process::abort(),
+        CrashMode::Core => {
+            dump_core();
+            process::abort()
+        }
+        CrashMode::Panic => panic!("simulated crash (--crash-mode panic)"),
+        CrashMode::OutOfMemory => {
+            const CHUNK: u64 = 64 * 1024 * 1024;
+
+            let mut memory = MemoryHog::default();
+            loop {
+                memory.grow_to(memory.allocated() + CHUNK);
+            }
+        }
+    }
+}
+
+/// Raises the soft limit for core files to the hard limit and, on Linux, makes the process
+/// dumpable again (switching users clears that). Then dies from SIGQUIT, which dumps core by
+/// default. Failures are ignored, since the process is about to crash anyway.
+#[cfg(unix)]
+fn dump_core() {
+    // SAFETY: `limit` is a valid `rlimit` struct, and `prctl` with `PR_SET_DUMPABLE` only
+    // changes a flag of the process.
+    unsafe {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        if libc::getrlimit(libc::RLIMIT_CORE, &mut limit) == 0 {
+            limit.rlim_cur = limit.rlim_max;
+            libc::setrlimit(libc::RLIMIT_CORE, &limit);
+        }
+        #[cfg(target_os = "linux")]
+        libc::prctl(libc::PR_SET_DUMPABLE, 1);
+
+        // The Rust runtime does not handle SIGQUIT, but some other code might have
+        libc::signal(libc::SIGQUIT, libc::SIG_DFL);
+        libc::raise(libc::SIGQUIT);
+    }
+}
+
+/// There are no core dumps here, the abort is the closest thing.
+#[cfg(not(unix))]
+fn dump_core() {}
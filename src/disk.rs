@@ -0,0 +1,133 @@
+//! Sustained sequential disk I/O, see `--write-rate` and `--read-rate`.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{thread, time};
+
+use crate::signals;
+
+/// The I/O of each interval is done at once, followed by an `fsync` if requested.
+const INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+/// Largest buffer for a single `read` or `write` call.
+const CHUNK: usize = 1024 * 1024;
+
+/// A thread that writes to or reads from a file at a fixed rate, until this is dropped. If
+/// the I/O fails, the thread stops and wakes up the main loop to report the error.
+pub struct DiskLoad {
+    stop: Arc<AtomicBool>,
+    error: Arc<Mutex<Option<io::Error>>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl DiskLoad {
+    /// Writes `rate` bytes per second to `path`, which is truncated first.
+    pub fn write(path: &Path, rate: u64, fsync: bool) -> io::Result<DiskLoad> {
+        let mut file = File::create(path)?;
+        let buffer = vec![0xAA; CHUNK];
+        Ok(DiskLoad::start(rate, move |mut bytes| {
+            while bytes > 0 {
+                let n = bytes.min(CHUNK);
+                file.write_all(&buffer[..n])?;
+                bytes -= n;
+            }
+            if fsync {
+                file.sync_data()?;
+            }
+            Ok(())
+        }))
+    }
+
+    /// Reads `rate` bytes per second from `path`, starting over at its end. Reads are
+    /// likely served from the page cache once the whole file has been read.
+    pub fn read(path: &Path, rate: u64) -> io::Result<DiskLoad> {
+        let mut file = OpenOptions::new().read(true).open(path)?;
+        let mut buffer = vec![0; CHUNK];
+        Ok(DiskLoad::start(rate, move |mut bytes| {
+            while bytes > 0 {
+                match file.read(&mut buffer[..bytes.min(CHUNK)])? {
+                    0 if file.stream_position()? == 0 => {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "empty file"))
+                    }
+                    0 => {
+                        file.seek(SeekFrom::Start(0))?;
+                    }
+                    n => bytes -= n,
+                }
+            }
+            Ok(())
+        }))
+    }
+
+    fn start(
+        rate: u64,
+        mut transfer: impl FnMut(usize) -> io::Result<()> + Send + 'static,
+    ) -> DiskLoad {
+        let stop = Arc::new(AtomicBool::new(false));
+        let error = Arc::new(Mutex::new(None));
+        let (stopped, failed) = (stop.clone(), error.clone());
+        let thread = thread::spawn(move || {
+            let start = time::Instant::now();
+            let mut done = 0u64;
+            let mut next = start;
+            while !stopped.load(Ordering::Relaxed) {
+                next += INTERVAL;
+                // Catch up with the rate, e.g. after a slow fsync
+                let due = (rate as f64 * (next - start).as_secs_f64()) as u64;
+                if let Err(err) = transfer((due - done) as usize) {
+                    *failed.lock().unwrap_or_else(|err| err.into_inner()) = Some(err);
+                    signals::wake();
+                    return;
+                }
+                done = due;
+                thread::sleep(next.saturating_duration_since(time::Instant::now()));
+            }
+        });
+        DiskLoad {
+            stop,
+            error,
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns the error that stopped the I/O, once.
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+    }
+}
+
+impl Drop for DiskLoad {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+#[test]
+fn test_disk_load() {
+    let path = crate::TempPath::new("disk");
+
+    let writer = DiskLoad::write(&path, 1_000_000, true).unwrap();
+    thread::sleep(time::Duration::from_millis(250));
+    drop(writer);
+    let written = std::fs::metadata(&path).unwrap().len();
+    assert!((200_000..=400_000).contains(&written), "{}", written);
+
+    let reader = DiskLoad::read(&path, 10_000_000).unwrap();
+    thread::sleep(time::Duration::from_millis(150));
+    assert!(reader.take_error().is_none());
+    drop(reader);
+
+    std::fs::write(&path, "").unwrap();
+    let reader = DiskLoad::read(&path, 1000).unwrap();
+    thread::sleep(time::Duration::from_millis(150));
+    assert!(reader.take_error().is_some());
+}
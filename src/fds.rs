@@ -0,0 +1,82 @@
+//! Holding a known number of open file descriptors, see `--open-fds`.
+
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+
+/// What the descriptors of `--open-fds` refer to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FdTarget {
+    /// The file is opened for reading, once per descriptor.
+    Path(PathBuf),
+    /// Both ends of pipes (Unix only).
+    Pipe,
+}
+
+impl FdTarget {
+    pub fn parse(value: &str) -> FdTarget {
+        match value {
+            "pipe" => FdTarget::Pipe,
+            path => FdTarget::Path(path.into()),
+        }
+    }
+
+    /// Opens up to `count` more descriptors and adds them to `fds` (a pipe counts twice).
+    pub fn open(&self, fds: &mut Vec<File>, count: usize) -> io::Result<()> {
+        match self {
+            FdTarget::Path(path) => {
+                fds.push(File::open(path)?);
+                Ok(())
+            }
+            FdTarget::Pipe => {
+                let (read_end, write_end) = pipe()?;
+                fds.push(read_end);
+                if count > 1 {
+                    fds.push(write_end);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn pipe() -> io::Result<(File, File)> {
+    use std::os::unix::io::FromRawFd;
+
+    let (read_end, write_end) = nix::unistd::pipe()?;
+    // SAFETY: both descriptors were just created and are not owned by anything else.
+    unsafe { Ok((File::from_raw_fd(read_end), File::from_raw_fd(write_end))) }
+}
+
+#[cfg(not(unix))]
+fn pipe() -> io::Result<(File, File)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "pipes are not supported on this platform",
+    ))
+}
+
+/// The descriptor number, where there is such a thing.
+#[cfg(unix)]
+pub fn number(file: &File) -> Option<i32> {
+    use std::os::unix::io::AsRawFd;
+
+    Some(file.as_raw_fd())
+}
+
+#[cfg(not(unix))]
+pub fn number(_: &File) -> Option<i32> {
+    None
+}
+
+#[cfg(unix)]
+#[test]
+fn test_open_fds() {
+    let mut fds = vec![];
+    FdTarget::parse("/dev/null").open(&mut fds, 3).unwrap();
+    FdTarget::parse("pipe").open(&mut fds, 2).unwrap();
+    FdTarget::parse("pipe").open(&mut fds, 1).unwrap();
+    assert_eq!(4, fds.len());
+    assert!(FdTarget::parse("/nonexistent").open(&mut fds, 1).is_err());
+}
@@ -0,0 +1,3601 @@
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::process;
+use std::{thread, time};
+
+mod barrier;
+mod color;
+mod control;
+mod crash;
+mod disk;
+mod fds;
+mod listen;
+mod load;
+mod lock;
+mod measure;
+mod memory;
+mod metrics;
+mod notify;
+mod parent;
+mod pause;
+mod pidfile;
+mod platform;
+mod progress;
+mod random;
+mod rlimit;
+mod sched;
+pub mod script;
+pub mod settings;
+pub mod signals;
+mod sleeper;
+mod spam;
+mod state;
+mod stdin;
+mod summary;
+mod syslog;
+pub mod template;
+mod terminal;
+pub mod timestamp;
+mod touch;
+mod watch;
+mod webhook;
+
+use barrier::Barrier;
+pub use color::ColorChoice;
+use control::{Command, ControlSocket};
+pub use crash::CrashMode;
+use disk::DiskLoad;
+pub use fds::FdTarget;
+use load::{BusyLoad, IdleThreads, SpawnError};
+use lock::FileLock;
+pub use lock::LockMode;
+use measure::Latencies;
+pub use memory::parse_size;
+use memory::MemoryHog;
+use metrics::Metrics;
+use notify::Notifier;
+use parent::ParentWatch;
+use pause::PauseTracker;
+use pidfile::Pidfile;
+use platform::Credentials;
+pub use platform::DaemonOptions;
+use random::Rng;
+pub use rlimit::Rlimit;
+pub use sched::parse_cpu_list;
+use script::Script;
+use settings::{Settings, Source};
+use signals::{Pid, Signal};
+pub use sleeper::Sleeper;
+use spam::{Spammer, Stream};
+pub use stdin::StdinMode;
+use stdin::{Input, StdinReader};
+use summary::RunSummary;
+pub use syslog::LogTarget;
+use syslog::{Priority, SystemLogger};
+use template::{MessageTemplate, PrefixTemplate};
+use terminal::KeyListener;
+use timestamp::{Boundary, TimestampFormat};
+use touch::TouchFile;
+pub use watch::FileCondition;
+use watch::FileWatch;
+
+#[derive(Debug, PartialEq)]
+pub enum LucidError {
+    DurationParseError,
+    DurationNegative,
+    DurationUnitMissing,
+    DurationUnitOrder,
+    DurationUnitUnknown(String),
+    SizeParseError,
+    RlimitParseError,
+    RlimitError,
+    ScriptError(String),
+    TimestampParseError,
+    NegativeTimeSpan,
+    InvalidRandomRange,
+    DeadlineInPast,
+    InvalidArgument(&'static str),
+    FailedToDaemonize,
+    FailedToRunPreHook,
+    FailedToRunCommand,
+    FailedToSpawnChildren,
+    BarrierError,
+    AccuracyNotMet,
+    MetricsFileError,
+    TerminalError,
+    InvalidTimestampFormat,
+    InvalidMessageFormat,
+    InvalidPrefix,
+    ExitCodeFileError,
+    ConfigFileError(PathBuf, usize),
+    InvalidEnvironmentVariable(&'static str),
+    InvalidSignal,
+    WebhookNotSupported,
+    NoSuchProcess(Pid),
+    NoSuchUser(String),
+    NoSuchGroup(String),
+    DropPrivilegesError,
+    NiceError,
+    CpuAffinityError,
+    PidfileError,
+    TouchFileError,
+    StdinError,
+    LockError,
+    OpenFdsError,
+    ThreadsError,
+    DiskIoError,
+    LogFileError,
+    SystemLogError,
+    NotifyError,
+    ListenError,
+    ControlSocketError,
+    Unsupported(&'static str),
+}
+
+impl LucidError {
+    pub fn message(&self) -> String {
+        match self {
+            LucidError::DurationParseError => "Could not parse 'duration' argument".into(),
+            LucidError::DurationNegative => "Duration can not be negative".into(),
+            LucidError::DurationUnitMissing => {
+                "Durations need a unit like 's' or 'm' (see '--strict-duration')".into()
+            }
+            LucidError::DurationUnitOrder => {
+                "Duration units must be given in the order d, h, m, s, ms, us, ns and at most once each"
+                    .into()
+            }
+            LucidError::DurationUnitUnknown(unit) => format!(
+                "Unknown duration unit '{}' (expected one of d, h, m, s, ms, us, ns)",
+                unit
+            ),
+            LucidError::ScriptError(step) => format!("Invalid script step '{}'", step),
+            LucidError::SizeParseError => "Could not parse size argument (e.g. '512M')".into(),
+            LucidError::RlimitParseError => {
+                "Could not parse '--rlimit' argument (e.g. 'NOFILE=64,AS=256M')".into()
+            }
+            LucidError::RlimitError => "Could not set all '--rlimit' limits".into(),
+            LucidError::NiceError => "Could not set the '--nice' value".into(),
+            LucidError::CpuAffinityError => "Could not set the '--cpu-affinity'".into(),
+            LucidError::TimestampParseError => "Could not parse timestamp argument".into(),
+            LucidError::NegativeTimeSpan => {
+                "The second timestamp lies before the first one (see '--clamp-negative')".into()
+            }
+            LucidError::InvalidRandomRange => {
+                "The lower bound of '--random' is larger than the upper bound".into()
+            }
+            LucidError::DeadlineInPast => "The '--until' deadline lies in the past".into(),
+            LucidError::InvalidArgument(name) => format!("Could not parse '{}' argument", name),
+            LucidError::FailedToDaemonize => "Failed to daemonize itself".into(),
+            LucidError::FailedToRunPreHook => "Failed to run the '--pre-hook' command".into(),
+            LucidError::FailedToRunCommand => "Failed to run the '--then' command".into(),
+            LucidError::FailedToSpawnChildren => "Failed to spawn the child processes".into(),
+            LucidError::ListenError => "Could not listen on the '--listen' address".into(),
+            LucidError::ControlSocketError => "Could not create the '--control-socket'".into(),
+            LucidError::BarrierError => "Could not access the barrier file".into(),
+            LucidError::AccuracyNotMet => {
+                "Could not sleep within the required accuracy (see '--max-retries')".into()
+            }
+            LucidError::MetricsFileError => "Could not write the metrics file".into(),
+            LucidError::TerminalError => "Could not configure the terminal".into(),
+            LucidError::InvalidTimestampFormat => "Invalid '--timestamp-format' argument".into(),
+            LucidError::InvalidMessageFormat => "Invalid '--message-format' argument".into(),
+            LucidError::InvalidPrefix => {
+                "Invalid '--prefix' argument (the placeholders are {pid}, {ppid}, {index} and \
+                 {time})"
+                    .into()
+            }
+            LucidError::ExitCodeFileError => "Could not read an exit code from the file".into(),
+            LucidError::ConfigFileError(path, 0) => {
+                format!("Could not read config file '{}'", path.display())
+            }
+            LucidError::ConfigFileError(path, line) => {
+                format!("Invalid line {} in config file '{}'", line, path.display())
+            }
+            LucidError::InvalidEnvironmentVariable(name) => {
+                format!("Invalid value for environment variable {}", name)
+            }
+            LucidError::InvalidSignal => "Invalid signal specification".into(),
+            LucidError::LogFileError => "Could not write to the log file".into(),
+            LucidError::SystemLogError => {
+                "Could not connect to the '--log-target' system logger".into()
+            }
+            LucidError::NotifyError => "Invalid socket address in $NOTIFY_SOCKET".into(),
+            LucidError::Unsupported(option) => {
+                format!("'{}' is not supported on this platform", option)
+            }
+            LucidError::PidfileError => "Could not write or remove the PID file".into(),
+            LucidError::StdinError => "Could not close stdin".into(),
+            LucidError::LockError => "Could not lock the '--lock' file".into(),
+            LucidError::DiskIoError => "Could not open the '--write-file' or '--read-file'".into(),
+            LucidError::OpenFdsError => "Could not open all '--open-fds' descriptors".into(),
+            LucidError::ThreadsError => "Could not spawn all '--threads'".into(),
+            LucidError::TouchFileError => "Could not update or remove the '--touch' file".into(),
+            LucidError::NoSuchProcess(pid) => format!("There is no process with PID {}", pid),
+            LucidError::NoSuchUser(user) => format!("There is no user '{}'", user),
+            LucidError::NoSuchGroup(group) => format!("There is no group '{}'", group),
+            LucidError::DropPrivilegesError => {
+                "Could not switch to the '--user' or '--group'".into()
+            }
+            LucidError::WebhookNotSupported => {
+                "'--heartbeat-url' requires lucid to be built with the 'webhook' feature".into()
+            }
+        }
+    }
+}
+
+/// Determines how much information should be printed.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum VerbosityLevel {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+pub type ExitCode = i32;
+
+/// The reason for leaving the main loop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WakeReason {
+    Elapsed,
+    Interrupted,
+    BarrierReached,
+    FileCondition,
+    KeyPressed,
+    Signal(Signal),
+    /// A `WAKE` command on the control socket.
+    Control,
+    /// An `EXIT` command on the control socket.
+    ControlExit,
+    /// The parent process exited, see `--die-with-parent`.
+    ParentDied,
+    /// The main process of `--init` exited.
+    MainProcessExited,
+    TimedOut,
+    /// `--crash-after` or `--dump-core-on`, only used for the summary right before crashing.
+    Crashed,
+}
+
+impl WakeReason {
+    fn name(&self) -> &'static str {
+        match self {
+            WakeReason::Elapsed => "elapsed",
+            WakeReason::Interrupted => "interrupted",
+            WakeReason::BarrierReached => "barrier",
+            WakeReason::FileCondition => "file",
+            WakeReason::KeyPressed => "key",
+            WakeReason::Signal(_) => "signal",
+            WakeReason::Control => "control",
+            WakeReason::ControlExit => "control_exit",
+            WakeReason::ParentDied => "parent",
+            WakeReason::MainProcessExited => "child",
+            WakeReason::TimedOut => "timeout",
+            WakeReason::Crashed => "crash",
+        }
+    }
+}
+
+/// What a message reports, for machine-readable output (see `--format json`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    SleepStart,
+    Heartbeat,
+    Signal,
+    Wake,
+}
+
+impl Event {
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::SleepStart => "sleep_start",
+            Event::Heartbeat => "heartbeat",
+            Event::Signal => "signal",
+            Event::Wake => "wake",
+        }
+    }
+}
+
+/// Options that control how messages are rendered.
+#[derive(Default)]
+pub struct OutputStyle {
+    pub timestamp_format: Option<TimestampFormat>,
+    /// Use the time since the start as the timestamp (instead of `timestamp_format`).
+    pub elapsed_timestamps: bool,
+    pub message_template: MessageTemplate,
+    pub whole_seconds: bool,
+    pub json: bool,
+    /// Color the prefix by its name and highlight signals (not used for JSON).
+    pub color: bool,
+}
+
+pub struct OutputHandler<'a> {
+    sink: Box<dyn Write + 'a>,
+    /// Messages that are kept until the final sink is known, see `hold`.
+    held: Option<Vec<u8>>,
+    write_failed: bool,
+    buffer_size: Option<usize>,
+    prefix: PrefixTemplate,
+    /// The value of `{index}` in the prefix, `0` outside of `--fork` instances.
+    fork_index: String,
+    verbosity_level: VerbosityLevel,
+    /// The level to return to after `toggle_verbose`.
+    initial_verbosity_level: VerbosityLevel,
+    style: OutputStyle,
+    /// Format of the `ts` field in JSON mode.
+    json_timestamp: TimestampFormat,
+    last_flush: time::Instant,
+    created: time::Instant,
+    /// Receive every message as well, see `--log-target`.
+    loggers: Vec<SystemLogger>,
+}
+
+impl<'a> OutputHandler<'a> {
+    /// How often buffered output is flushed (at most), see `--output-buffer-size`.
+    const FLUSH_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+    /// Creates a handler that writes to `sink`, e.g. a locked stdout. With a `buffer_size`,
+    /// the output is buffered (see `--output-buffer-size`).
+    pub fn new(
+        sink: Box<dyn Write + 'a>,
+        prefix: PrefixTemplate,
+        verbosity_level: VerbosityLevel,
+        style: OutputStyle,
+        buffer_size: Option<usize>,
+    ) -> Self {
+        OutputHandler {
+            sink: Self::buffer(sink, buffer_size),
+            held: None,
+            write_failed: false,
+            buffer_size,
+            prefix,
+            fork_index: env::var(FORK_INDEX_VARIABLE).unwrap_or_else(|_| "0".into()),
+            verbosity_level,
+            initial_verbosity_level: verbosity_level,
+            style,
+            json_timestamp: TimestampFormat::parse(timestamp::RFC3339_MILLIS)
+                .expect("valid timestamp format"),
+            last_flush: time::Instant::now(),
+            created: time::Instant::now(),
+            loggers: vec![],
+        }
+    }
+
+    fn buffer(sink: Box<dyn Write + 'a>, buffer_size: Option<usize>) -> Box<dyn Write + 'a> {
+        match buffer_size {
+            Some(capacity) => Box::new(io::BufWriter::with_capacity(capacity, sink)),
+            None => sink,
+        }
+    }
+
+    /// Keeps all messages in memory until `redirect` is called.
+    pub fn hold(&mut self) {
+        self.held.get_or_insert_with(Vec::new);
+    }
+
+    /// Sends all further messages to the system logger as well.
+    pub fn add_logger(&mut self, logger: SystemLogger) {
+        self.loggers.push(logger);
+    }
+
+    /// Writes all further messages (and the ones kept by `hold`) to `sink` instead.
+    pub fn redirect(&mut self, sink: Box<dyn Write + 'a>) {
+        self.flush();
+        self.sink = Self::buffer(sink, self.buffer_size);
+        if let Some(held) = self.held.take() {
+            self.write(&held);
+        }
+    }
+
+    /// Changes the verbosity for all further messages.
+    pub fn set_verbosity(&mut self, verbosity_level: VerbosityLevel) {
+        self.verbosity_level = verbosity_level;
+    }
+
+    /// Switches to verbose output, or back to the initial level (normal output if that was
+    /// verbose as well). Returns true if the output is verbose now.
+    pub fn toggle_verbose(&mut self) -> bool {
+        self.verbosity_level = match self.verbosity_level {
+            VerbosityLevel::Verbose if self.initial_verbosity_level != VerbosityLevel::Verbose => {
+                self.initial_verbosity_level
+            }
+            VerbosityLevel::Verbose => VerbosityLevel::Normal,
+            _ => VerbosityLevel::Verbose,
+        };
+        self.verbosity_level == VerbosityLevel::Verbose
+    }
+
+    /// Returns true if writing a message failed at least once.
+    pub fn write_failed(&self) -> bool {
+        self.write_failed
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        match self.held {
+            Some(ref mut held) => held.extend_from_slice(bytes),
+            None => {
+                if self.sink.write_all(bytes).is_err() {
+                    self.write_failed = true;
+                }
+            }
+        }
+    }
+
+    pub fn print(&mut self, msg: &str) {
+        match self.verbosity_level {
+            VerbosityLevel::Verbose | VerbosityLevel::Normal => {
+                self.print_with_prefix("info", msg, None)
+            }
+            _ => {}
+        }
+    }
+
+    /// Like `print`, but in JSON mode, the message is tagged with the event and, if
+    /// given, a duration (`duration_ms`).
+    pub fn print_event(&mut self, event: Event, duration: Option<time::Duration>, msg: &str) {
+        if self.verbosity_level != VerbosityLevel::Quiet {
+            self.print_with_prefix("info", msg, Some((event, duration)))
+        }
+    }
+
+    /// Like `print_always`, but tags the message like `print_event`.
+    pub fn print_event_always(
+        &mut self,
+        event: Event,
+        duration: Option<time::Duration>,
+        msg: &str,
+    ) {
+        self.print_with_prefix("info", msg, Some((event, duration)))
+    }
+
+    /// Prints a line as it is, without prefix or timestamp, regardless of the verbosity level.
+    pub fn print_raw(&mut self, line: &str) {
+        for logger in &self.loggers {
+            logger.send(Priority::Info, line);
+        }
+        self.write(format!("{}\n", line).as_bytes());
+    }
+
+    /// Prints a message regardless of the verbosity level.
+    pub fn print_always(&mut self, msg: &str) {
+        self.print_with_prefix("info", msg, None)
+    }
+
+    pub fn print_verbose(&mut self, msg: &str) {
+        if self.verbosity_level == VerbosityLevel::Verbose {
+            self.print_with_prefix("debug", msg, None)
+        }
+    }
+
+    /// The prefix with its placeholders expanded for a message at `now`.
+    pub fn prefix(&self, now: time::SystemTime) -> String {
+        self.prefix
+            .render(process::id(), platform::parent_id(), &self.fork_index, now)
+    }
+
+    fn print_with_prefix(
+        &mut self,
+        level: &str,
+        msg: &str,
+        event: Option<(Event, Option<time::Duration>)>,
+    ) {
+        let priority = match (level, event) {
+            (_, Some((Event::Signal, _))) => Priority::Notice,
+            ("debug", _) => Priority::Debug,
+            _ => Priority::Info,
+        };
+        for logger in &self.loggers {
+            logger.send(priority, msg);
+        }
+
+        let now = time::SystemTime::now();
+        let prefix = self.prefix(now);
+        let timestamp = if self.style.elapsed_timestamps {
+            Some(self.format_duration(&self.created.elapsed()))
+        } else {
+            self.style
+                .timestamp_format
+                .as_ref()
+                .map(|format| format.render(now))
+        };
+
+        let mut line = String::new();
+        if self.style.json {
+            line.push_str(&format!(
+                "{{\"prefix\":{},\"level\":\"{}\",\"msg\":{},\"elapsed_ms\":{}",
+                json_string(&prefix),
+                level,
+                json_string(msg),
+                self.created.elapsed().as_millis()
+            ));
+            line.push_str(&format!(
+                ",\"ts\":{}",
+                json_string(&self.json_timestamp.render(now))
+            ));
+            if let Some(timestamp) = timestamp {
+                line.push_str(&format!(",\"timestamp\":{}", json_string(&timestamp)));
+            }
+            if let Some((event, duration)) = event {
+                line.push_str(&format!(",\"event\":\"{}\"", event.name()));
+                if let Some(duration) = duration {
+                    line.push_str(&format!(",\"duration_ms\":{}", duration.as_millis()));
+                }
+            }
+            line.push_str("}\n");
+        } else {
+            if let Some(timestamp) = timestamp {
+                line.push_str(&format!("[{}] ", timestamp));
+            }
+            let elapsed = self.format_duration(&self.created.elapsed());
+            let (prefix, msg) = if self.style.color {
+                let msg = match (level, event) {
+                    (_, Some((Event::Signal, _))) => color::paint(color::SIGNAL, msg),
+                    ("debug", _) => color::paint(color::DEBUG, msg),
+                    _ => msg.to_string(),
+                };
+                (color::paint(color::prefix_color(&prefix), &prefix), msg)
+            } else {
+                (prefix, msg.to_string())
+            };
+            line.push_str(&self.style.message_template.render(
+                &prefix,
+                &msg,
+                process::id(),
+                &elapsed,
+            ));
+            line.push('\n');
+        }
+
+        self.write(line.as_bytes());
+
+        if self.buffer_size.is_some() && self.last_flush.elapsed() >= Self::FLUSH_INTERVAL {
+            self.flush();
+        }
+    }
+
+    /// Formats a duration for display, honoring `--no-subsecond`.
+    pub fn format_duration(&self, duration: &time::Duration) -> String {
+        if self.style.whole_seconds {
+            format!("{}s", duration.as_secs_f64().round())
+        } else {
+            duration_as_str(duration)
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if self.sink.flush().is_err() {
+            self.write_failed = true;
+        }
+        self.last_flush = time::Instant::now();
+    }
+}
+
+pub type Result<T> = std::result::Result<T, LucidError>;
+
+/// Encodes a string as a (quoted) JSON string literal.
+fn json_string(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len() + 2);
+    encoded.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => encoded.push_str("\\\""),
+            '\\' => encoded.push_str("\\\\"),
+            '\n' => encoded.push_str("\\n"),
+            '\r' => encoded.push_str("\\r"),
+            '\t' => encoded.push_str("\\t"),
+            c if (c as u32) < 0x20 => encoded.push_str(&format!("\\u{:04x}", c as u32)),
+            c => encoded.push(c),
+        }
+    }
+    encoded.push('"');
+    encoded
+}
+
+/// Formats a duration in seconds with millisecond precision, e.g. `1.500s`. Durations below a
+/// millisecond get as many digits as needed to show three significant ones, e.g. `0.000500s`.
+pub fn duration_as_str(duration: &time::Duration) -> String {
+    let (secs, nanos) = (duration.as_secs(), duration.subsec_nanos());
+    if secs > 0 || nanos == 0 || nanos >= 1_000_000 {
+        format!("{}.{:03}s", secs, nanos / 1_000_000)
+    } else if nanos >= 1_000 {
+        format!("0.{:06}s", nanos / 1_000)
+    } else {
+        format!("0.{:09}s", nanos)
+    }
+}
+
+pub fn duration_from_float(duration_sec: f64) -> Result<time::Duration> {
+    if duration_sec < 0.0 {
+        return Err(LucidError::DurationNegative);
+    }
+
+    let secs = duration_sec.floor() as u64;
+    let nanos = ((duration_sec - secs as f64) * 1e9).round() as u64;
+
+    time::Duration::from_secs(secs)
+        .checked_add(time::Duration::from_nanos(nanos))
+        .ok_or(LucidError::DurationParseError)
+}
+
+/// Formats how far along a sleep of `total` is after `elapsed`, e.g. " (32%)". Empty when
+/// sleeping forever. Never reports more than 100%.
+fn progress_suffix(elapsed: &time::Duration, total: Option<&time::Duration>) -> String {
+    match total {
+        None => String::new(),
+        Some(total) if total.is_zero() => " (100%)".into(),
+        Some(total) => {
+            let percent = (elapsed.as_secs_f64() / total.as_secs_f64() * 100.0).min(100.0);
+            format!(" ({}%)", percent.floor())
+        }
+    }
+}
+
+/// The point in time `duration` from now, or `None` if that is too far in the future to be
+/// represented (which is as good as never).
+pub(crate) fn deadline_after(duration: time::Duration) -> Option<time::Instant> {
+    time::Instant::now().checked_add(duration)
+}
+
+fn epoch_nanos() -> u128 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+fn run_script_actions(output: &mut OutputHandler, actions: &[(time::Duration, script::Action)]) {
+    for (_, action) in actions {
+        match action {
+            script::Action::Print(text) => output.print_raw(text),
+            script::Action::Stderr(text) => {
+                output.flush();
+                eprintln!("{}", text);
+            }
+        }
+    }
+}
+
+/// Sends SIGTERM to the given process group and waits for up to one second until all of its
+/// members have exited. As `--init`, exited processes are reaped in the meantime (and removed
+/// from `children`), since their zombies would keep the group around.
+fn forward_termination(
+    output: &mut OutputHandler,
+    group: Pid,
+    mut children: Option<&mut Vec<process::Child>>,
+) {
+    const TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+    if signals::terminate_group(group).is_err() {
+        output.print_verbose(&format!(
+            "No processes left in process group {} to forward the signal to",
+            group
+        ));
+        return;
+    }
+    output.print(&format!("Forwarded SIGTERM to process group {}", group));
+
+    let start = time::Instant::now();
+    while {
+        if let Some(children) = children.as_deref_mut() {
+            for exited in platform::reap_any() {
+                children.retain(|child| child.id() != exited.pid);
+                output.print_verbose(&format!(
+                    "Reaped process {} ({})",
+                    exited.pid, exited.status
+                ));
+            }
+        }
+        signals::group_exists(group)
+    } {
+        if start.elapsed() >= TIMEOUT {
+            output.print(&format!(
+                "Process group {} is still running after {}",
+                group,
+                output.format_duration(&TIMEOUT)
+            ));
+            return;
+        }
+        thread::sleep(time::Duration::from_millis(10));
+    }
+}
+
+/// Prints the `--summary` like other messages. Still printed with `--quiet`, but to stderr
+/// (unless there is a log file), such that stdout stays empty.
+fn print_summary(
+    output: &mut OutputHandler,
+    config: &Config,
+    verbosity_level: VerbosityLevel,
+    summary: &RunSummary,
+) {
+    if verbosity_level == VerbosityLevel::Quiet && !config.stderr && config.log_file.is_none() {
+        output.flush();
+        writeln!(io::stderr(), "{}", summary.to_json()).ok();
+    } else {
+        output.print_raw(&summary.to_json());
+        output.flush();
+    }
+}
+
+/// Re-sends a signal that lucid received to a process group, see `--forward-signals`.
+fn forward_to_group(output: &mut OutputHandler, group: Pid, signal: Signal) {
+    match signals::signal_group(group, signal) {
+        Ok(()) => output.print_verbose(&format!("Forwarded {} to process group {}", signal, group)),
+        Err(err) => output.print_verbose(&format!(
+            "Could not forward {} to process group {}: {}",
+            signal, group, err
+        )),
+    }
+}
+
+/// Sends a termination signal on to each of the `children`, see `--init`.
+fn forward_to_children(
+    output: &mut OutputHandler,
+    children: &[process::Child],
+    termination: signals::Termination,
+) {
+    let Some(signal) = termination.signal else {
+        return;
+    };
+    let forwarded = children
+        .iter()
+        .filter(|child| signals::kill(Pid::from_raw(child.id() as i32), Some(signal)).is_ok())
+        .count();
+    output.print_event(
+        Event::Signal,
+        None,
+        &format!(
+            "Forwarded {} to {} child process{}",
+            signal,
+            forwarded,
+            if forwarded == 1 { "" } else { "es" }
+        ),
+    );
+}
+
+/// How often to retry acquiring a `--lock` that is held by someone else.
+const LOCK_RETRY: time::Duration = time::Duration::from_millis(100);
+
+/// Switches to the `--user` and `--group`, if that did not happen yet.
+fn drop_privileges(output: &mut OutputHandler, credentials: Option<Credentials>) -> Result<()> {
+    if let Some(credentials) = credentials {
+        if let Err(err) = credentials.drop_privileges() {
+            output.print(&format!(
+                "Could not switch to {}: {}",
+                credentials.describe(),
+                err
+            ));
+            return Err(LucidError::DropPrivilegesError);
+        }
+        output.print_verbose(&format!("Switched to {}", credentials.describe()));
+    }
+    Ok(())
+}
+
+/// Handles termination signals that arrived before the sleep started, during `what`. Returns
+/// the exit code if they interrupt lucid.
+fn interrupt_before_sleep(
+    config: &Config,
+    output: &mut OutputHandler,
+    received: &[signals::Termination],
+    what: &str,
+    exit_code: ExitCode,
+) -> Option<ExitCode> {
+    let last = received.last()?;
+    if config.no_interrupt {
+        for termination in received {
+            output.print(&format!("Ignoring {}.", termination));
+        }
+        return None;
+    }
+    output.print(&format!("Caught {} - interrupting {}.", last, what));
+    if config.signal_exit_codes {
+        return Some(last.exit_code());
+    }
+    Some(config.interrupt_exit_code.unwrap_or(exit_code))
+}
+
+/// How often the time remaining until a wall-clock deadline is recomputed.
+const WALL_CLOCK_CHECK: time::Duration = time::Duration::from_secs(1);
+
+/// Shortens the `nap` (if any) to at most `until`.
+fn earliest(nap: Option<time::Duration>, until: time::Duration) -> Option<time::Duration> {
+    Some(nap.map_or(until, |nap| nap.min(until)))
+}
+
+/// The status that is reported on SIGUSR1 and via the control socket.
+fn status_line(
+    output: &OutputHandler,
+    since_start: time::Duration,
+    sleeping_duration: Option<time::Duration>,
+    termination_signals: usize,
+    allocated: u64,
+) -> String {
+    let remaining = match sleeping_duration {
+        None => "sleeping forever".into(),
+        Some(d) => format!(
+            "{} remaining",
+            output.format_duration(&d.saturating_sub(since_start))
+        ),
+    };
+    let allocated = match allocated {
+        0 => String::new(),
+        bytes => format!(", {} allocated", memory::size_as_str(bytes)),
+    };
+    format!(
+        "Status: PID {}, asleep for {}, {} termination signal{}{}, {}",
+        process::id(),
+        output.format_duration(&since_start),
+        termination_signals,
+        if termination_signals == 1 { "" } else { "s" },
+        allocated,
+        remaining
+    )
+}
+
+fn send_notification(output: &mut OutputHandler, notifier: &Notifier, state: &str) {
+    match notifier.notify(state) {
+        Ok(()) => output.print_verbose(&format!("Sent {} to the service manager", state)),
+        Err(err) => output.print(&format!(
+            "Could not send {} to the service manager: {}",
+            state, err
+        )),
+    }
+}
+
+/// Waits for all child processes to exit. If `terminate` is set, they are asked to exit first
+/// (via SIGTERM where available, otherwise they are killed).
+fn reap_children(output: &mut OutputHandler, children: Vec<process::Child>, terminate: bool) {
+    for mut child in children {
+        let pid = child.id();
+        if terminate && signals::terminate(Pid::from_raw(pid as i32)).is_err() {
+            child.kill().ok();
+        }
+        match child.wait() {
+            Ok(status) => {
+                output.print_verbose(&format!("Reaped child process {} ({})", pid, status))
+            }
+            Err(_) => output.print(&format!("Could not reap child process {}", pid)),
+        }
+    }
+}
+
+/// The environment variable that tells the instances of `--fork` their index.
+pub const FORK_INDEX_VARIABLE: &str = "LUCID_FORK_INDEX";
+
+/// Runs `count` instances of lucid with `args` (see `--fork`) and waits until all of them
+/// have exited. Returns the highest exit code of the instances. With `forward_signals`, the
+/// instances run in their own process group, which gets all signals that lucid receives.
+fn run_instances(
+    output: &mut OutputHandler,
+    count: usize,
+    args: &[String],
+    forward_signals: bool,
+) -> Result<ExitCode> {
+    const POLL_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+    signals::install_termination_handlers()?;
+    if forward_signals {
+        signals::install_status_handler()?;
+        signals::install_verbosity_handler()?;
+    }
+    let executable = env::current_exe().map_err(|_| LucidError::FailedToSpawnChildren)?;
+    let mut running = vec![];
+    let mut group = None;
+    for index in 1..=count {
+        let mut command = process::Command::new(&executable);
+        command
+            .args(args)
+            .env(FORK_INDEX_VARIABLE, index.to_string());
+        if forward_signals {
+            platform::use_process_group(&mut command, group.unwrap_or(0))?;
+        }
+        let child = command
+            .spawn()
+            .map_err(|_| LucidError::FailedToSpawnChildren)?;
+        output.print_verbose(&format!(
+            "Started instance {} with PID {}",
+            index,
+            child.id()
+        ));
+        if forward_signals {
+            group.get_or_insert(child.id() as i32);
+        }
+        running.push((index, child));
+    }
+    let user_signals = [
+        (signals::take_status_requests as fn() -> usize, "SIGUSR1"),
+        (signals::take_verbosity_toggles, "SIGUSR2"),
+    ];
+    output.print(&format!(
+        "Started {} instance{}",
+        count,
+        if count == 1 { "" } else { "s" }
+    ));
+
+    let mut failed = vec![];
+    let mut exit_code = 0;
+    while !running.is_empty() {
+        if let Some(group) = group {
+            for (take, name) in user_signals {
+                for signal in (0..take()).filter_map(|_| signals::parse_signal(name)) {
+                    forward_to_group(output, Pid::from_raw(group), signal);
+                }
+            }
+        }
+        for termination in signals::take_terminations() {
+            if let (Some(group), Some(signal)) = (group, termination.signal) {
+                forward_to_group(output, Pid::from_raw(group), signal);
+                continue;
+            }
+            // Signals from the terminal reach the whole process group, including the
+            // instances, so only those sent to lucid alone are forwarded
+            let Some(signal) = termination.signal.filter(|_| termination.sender.is_some()) else {
+                continue;
+            };
+            for (_, child) in &running {
+                signals::kill(Pid::from_raw(child.id() as i32), Some(signal)).ok();
+            }
+            output.print_event(
+                Event::Signal,
+                None,
+                &format!(
+                    "Forwarded {} to {} instance{}",
+                    termination,
+                    running.len(),
+                    if running.len() == 1 { "" } else { "s" }
+                ),
+            );
+        }
+
+        let mut index = 0;
+        while index < running.len() {
+            let status = match running[index].1.try_wait() {
+                Ok(Some(status)) => status,
+                Ok(None) => {
+                    index += 1;
+                    continue;
+                }
+                Err(_) => return Err(LucidError::FailedToSpawnChildren),
+            };
+            let (instance, _) = running.remove(index);
+            let code = platform::exit_code_of(status);
+            output.print_verbose(&format!("Instance {} exited ({})", instance, status));
+            if code != 0 {
+                failed.push(format!("#{}", instance));
+            }
+            exit_code = exit_code.max(code);
+        }
+        if !running.is_empty() {
+            signals::wait(Some(POLL_INTERVAL));
+        }
+    }
+
+    if failed.is_empty() {
+        output.print(&format!(
+            "All {} instance{} exited successfully.",
+            count,
+            if count == 1 { "" } else { "s" }
+        ));
+    } else {
+        output.print(&format!(
+            "{} of {} instances failed ({}), the highest exit code is {}.",
+            failed.len(),
+            count,
+            failed.join(", "),
+            exit_code
+        ));
+    }
+    Ok(exit_code)
+}
+
+/// Parses a duration like `1.5`, `500ms` or `1h30m15s`. The supported units are `d`, `h`,
+/// `m`, `s`, `ms`, `us` and `ns`, which have to be given in this order and at most once each.
+/// A bare number is interpreted as (fractional) seconds.
+pub fn parse_duration(input: &str) -> Result<time::Duration> {
+    const UNITS: &[(&str, f64)] = &[
+        ("d", 24.0 * 60.0 * 60.0),
+        ("h", 60.0 * 60.0),
+        ("m", 60.0),
+        ("s", 1.0),
+        ("ms", 1e-3),
+        ("us", 1e-6),
+        ("ns", 1e-9),
+    ];
+
+    let input = input.trim();
+    if let Ok(seconds) = input.parse::<f64>() {
+        return duration_from_float(seconds);
+    }
+    if let Some(positive) = input.strip_prefix('-') {
+        parse_duration(positive)?;
+        return Err(LucidError::DurationNegative);
+    }
+
+    if input.is_empty() {
+        return Err(LucidError::DurationParseError);
+    }
+
+    let mut rest = input;
+    let mut next_unit = 0;
+    // Summed up as durations, so that small units are not lost next to large ones
+    let mut total = time::Duration::ZERO;
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or(LucidError::DurationParseError)?;
+        let (number, tail) = rest.split_at(number_end);
+        let unit_end = tail
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_end);
+
+        let value = number
+            .parse::<f64>()
+            .map_err(|_| LucidError::DurationParseError)?;
+        let index = match UNITS.iter().position(|&(name, _)| name == unit) {
+            Some(index) => index,
+            None if unit.is_empty() => return Err(LucidError::DurationParseError),
+            None => return Err(LucidError::DurationUnitUnknown(unit.into())),
+        };
+        if index < next_unit {
+            return Err(LucidError::DurationUnitOrder);
+        }
+
+        total = total
+            .checked_add(duration_from_float(value * UNITS[index].1)?)
+            .ok_or(LucidError::DurationParseError)?;
+        next_unit = index + 1;
+        rest = tail;
+    }
+
+    Ok(total)
+}
+
+/// Estimates the smallest duration the platform can sleep for, by timing a few tiny sleeps.
+fn measure_sleep_granularity() -> time::Duration {
+    const SAMPLES: usize = 5;
+
+    (0..SAMPLES)
+        .map(|_| {
+            let start = time::Instant::now();
+            thread::sleep(time::Duration::from_nanos(1));
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+/// Prints the `LUCID_*` environment variables and whether they were used or overridden.
+fn dump_environment(output: &mut OutputHandler, settings: &Settings) {
+    for &(key, name) in settings::ENVIRONMENT_VARIABLES {
+        match env::var_os(name) {
+            None => output.print(&format!("{} is not set", name)),
+            Some(value) => {
+                let status = if settings.source(key) == Some(&Source::CommandLine) {
+                    "overridden by the command line"
+                } else {
+                    "used"
+                };
+                output.print(&format!(
+                    "{} = \"{}\" ({})",
+                    name,
+                    value.to_string_lossy(),
+                    status
+                ));
+            }
+        }
+    }
+
+    let mut unrecognized: Vec<_> = env::vars_os()
+        .filter(|(name, _)| {
+            let name = name.to_string_lossy();
+            name.starts_with("LUCID_")
+                && !settings::ENVIRONMENT_VARIABLES
+                    .iter()
+                    .any(|&(_, known)| known == name)
+        })
+        .collect();
+    unrecognized.sort();
+    for (name, value) in unrecognized {
+        output.print(&format!(
+            "{} = \"{}\" (not recognized)",
+            name.to_string_lossy(),
+            value.to_string_lossy()
+        ));
+    }
+}
+
+/// How long to sleep.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sleep {
+    /// Sleep until interrupted.
+    Forever,
+    For(time::Duration),
+    /// Sleep until the start of the next minute, hour or day in local time.
+    UntilEndOf(Boundary),
+    /// Sleep until the given point in time.
+    Until(time::SystemTime),
+    /// Sleep for the time span between two points in time (see `Config::clamp_negative`).
+    Between(time::SystemTime, time::SystemTime),
+    /// Sleep for a random duration between the two bounds (see `Config::seed`).
+    Random(time::Duration, time::Duration),
+    /// Follow the timeline of the script.
+    Script(Script),
+}
+
+/// A random offset of up to this much, in either direction, for the sleep duration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Jitter {
+    Fixed(time::Duration),
+    /// A fraction of the sleep duration, e.g. `0.1` for 10%.
+    Relative(f64),
+}
+
+impl Jitter {
+    /// The largest offset for a sleep of the given duration.
+    pub fn max_offset(self, duration: time::Duration) -> time::Duration {
+        match self {
+            Jitter::Fixed(offset) => offset,
+            Jitter::Relative(fraction) => duration.mul_f64(fraction),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Human,
+    Csv,
+    Json,
+}
+
+/// Everything that controls a run of lucid, see `run`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub sleep: Sleep,
+    pub clamp_negative: bool,
+    /// Instead of idling, keep this many threads busy at the given load (0.0 to 1.0).
+    pub busy: Option<(usize, f64)>,
+    /// Number of idle threads to spawn, unless `busy` is set.
+    pub threads: usize,
+    /// Prefix for the names of the busy or idle threads.
+    pub thread_name: Option<String>,
+    /// Bytes to allocate (and touch) at startup.
+    pub memory: Option<u64>,
+    /// Bytes per second to allocate additionally while sleeping.
+    pub leak_rate: Option<u64>,
+    /// File and bytes per second to write while sleeping.
+    pub disk_write: Option<(PathBuf, u64)>,
+    /// File and bytes per second to read while sleeping.
+    pub disk_read: Option<(PathBuf, u64)>,
+    /// Flush the `disk_write` data to the disk after the writes of every interval (100ms).
+    pub fsync: bool,
+    /// Lines per second to write to stdout and stderr while sleeping.
+    pub spam_stdout: Option<f64>,
+    pub spam_stderr: Option<f64>,
+    /// Characters per line written by `spam_stdout` and `spam_stderr` (without the newline).
+    pub line_length: usize,
+    pub stdin_mode: StdinMode,
+    /// File to lock before sleeping. The lock is held until lucid exits.
+    pub lock: Option<PathBuf>,
+    pub lock_mode: LockMode,
+    pub lock_shared: bool,
+    /// Resource limits to apply at startup.
+    pub rlimits: Vec<Rlimit>,
+    /// Nice value to set at startup.
+    pub nice: Option<i32>,
+    /// CPUs to restrict lucid (and everything it spawns) to.
+    pub cpu_affinity: Option<Vec<usize>>,
+    /// Number of file descriptors to hold open while sleeping (see `fd_target`).
+    pub open_fds: usize,
+    pub fd_target: FdTarget,
+    /// Upper bound for the sleep duration, which also applies when sleeping forever.
+    pub max_duration: Option<time::Duration>,
+    /// Defer termination signals that arrive during this initial part of the sleep.
+    pub grace: Option<time::Duration>,
+    /// Only the given number of termination signals interrupts the sleep, earlier ones are
+    /// ignored.
+    pub die_after_signals: Option<usize>,
+    /// Terminate abnormally in the given way after this long.
+    pub crash: Option<(time::Duration, CrashMode)>,
+    /// Keep running for this long after being interrupted, to simulate a slow shutdown.
+    pub shutdown_delay: Option<time::Duration>,
+    /// Number of child processes to spawn and the arguments to run them with (the current
+    /// executable is used). The children are terminated when lucid is interrupted and reaped
+    /// before it exits.
+    pub children: Option<(usize, Vec<String>)>,
+    /// Instead of sleeping, run this many instances with the given arguments (the current
+    /// executable is used) and exit with the highest exit code among them.
+    pub fork: Option<(usize, Vec<String>)>,
+    /// Number of child processes that exit right away, but are only reaped before lucid exits.
+    pub zombies: usize,
+    /// Act as a minimal init: reap every child process (also reparented ones) right away and
+    /// forward termination signals to the children. The `then` command is started right away
+    /// as the main process, and lucid exits with its status once it exits.
+    pub init: bool,
+    /// Notify the service manager via the sd_notify protocol (if `$NOTIFY_SOCKET` is set).
+    pub notify: bool,
+    /// TCP address to accept connections on once ready (see `ready_after`).
+    pub listen: Option<String>,
+    /// Answer connections to `listen` with this body in an HTTP response.
+    pub http_response: Option<String>,
+    /// Only send `READY=1` and start listening after this long.
+    pub ready_after: Option<time::Duration>,
+    /// Number of sleeps in a row (0 and 1 both mean a single sleep).
+    pub repeat: u32,
+    /// Repeat the sleep until interrupted, overriding `repeat`.
+    pub repeat_forever: bool,
+    pub exit_code: ExitCode,
+    pub exit_code_file: Option<PathBuf>,
+    /// Exit with a random code between the two bounds (inclusive) instead of `exit_code`, see
+    /// `seed`.
+    pub random_exit_code: Option<(ExitCode, ExitCode)>,
+    /// Fail with this probability (with the exit code, or 1 if that is zero), and exit with
+    /// zero otherwise.
+    pub fail_probability: Option<f64>,
+    pub interrupt_exit_code: Option<ExitCode>,
+    /// Exit with 128 + the signal number when interrupted by a termination signal.
+    pub signal_exit_codes: bool,
+    pub timeout_code: ExitCode,
+    pub daemon: bool,
+    pub daemon_options: DaemonOptions,
+    /// Continue in a forked child while the original process exits, so the child is reparented.
+    pub orphan: bool,
+    /// Exit when the parent process exits.
+    pub die_with_parent: bool,
+    pub pidfile: Option<PathBuf>,
+    /// User (name or UID) to switch to once everything is set up.
+    pub user: Option<String>,
+    /// Group (name or GID) to switch to, by default the primary group of `user`.
+    pub group: Option<String>,
+    /// File to touch every `touch_interval` while sleeping (see `touch_final`).
+    pub touch: Option<PathBuf>,
+    pub touch_interval: time::Duration,
+    /// Written to the `touch` file when exiting, instead of removing it.
+    pub touch_final: Option<String>,
+    /// Unix socket for commands like `STATUS` or `WAKE` (see the `control` module).
+    pub control_socket: Option<PathBuf>,
+    pub no_interrupt: bool,
+    pub prefix: String,
+    pub verbosity: VerbosityLevel,
+    pub stderr: bool,
+    pub pre_hook: Option<String>,
+    pub forward_signals: bool,
+    /// Command (and arguments) to execute after waking up.
+    pub then: Option<Vec<String>>,
+    /// Send the signals that were ignored during the sleep to the `then` command.
+    pub forward_ignored: bool,
+    pub random_start_delay: Option<time::Duration>,
+    /// Randomly lengthen or shorten the sleep (see `seed`).
+    pub jitter: Option<Jitter>,
+    pub seed: Option<u64>,
+    pub report_granularity: bool,
+    /// How often to check for wake-up conditions (100ms if not set). Must not exceed the
+    /// sleep duration.
+    pub poll_interval: Option<time::Duration>,
+    /// Barrier file and the number of processes to wait for.
+    pub barrier: Option<(PathBuf, u64)>,
+    /// Path and the condition to wait for.
+    pub wait_for: Option<(PathBuf, FileCondition)>,
+    pub wait_signals: Vec<(Signal, Option<ExitCode>)>,
+    /// Signals that end the sleep (with the given exit code, if any) without being awaited.
+    pub exit_on_signals: Vec<(Signal, Option<ExitCode>)>,
+    /// Signal that makes lucid dump core, see `CrashMode::Core`.
+    pub dump_core_on: Option<Signal>,
+    /// Signals that are logged, but otherwise ignored.
+    pub ignore_signals: Vec<Signal>,
+    pub on_wake_signal: Option<(Pid, Signal)>,
+    pub on_interrupt_signal: Option<(Pid, Signal)>,
+    /// Maximum overshoot in percent of the sleep duration.
+    pub required_accuracy: Option<f64>,
+    /// Measure the overshoot of every sleep and how late heartbeats are, and print statistics
+    /// before exiting.
+    pub measure: bool,
+    pub max_retries: u32,
+    pub any_key: bool,
+    pub start_marker: bool,
+    pub end_marker: bool,
+    pub marker_fd: Option<i32>,
+    pub heartbeat: Option<time::Duration>,
+    /// Show a live countdown on stderr if it is a TTY, and print the remaining time every
+    /// second otherwise.
+    pub progress: bool,
+    /// Rewrite a single line on stdout with the remaining time, if it is a TTY, the sleep is
+    /// finite and normal human-readable output goes to stdout. Unlike `progress`, there is no
+    /// fallback otherwise.
+    pub countdown: bool,
+    /// Extend the sleep by this duration on every SIGHUP (instead of being interrupted).
+    pub extend: Option<time::Duration>,
+    /// Restart the sleep on every SIGHUP (instead of being interrupted), re-reading the
+    /// `script_file` if there is one.
+    pub reload_on_hup: bool,
+    /// Where the script of `Sleep::Script` was read from.
+    pub script_file: Option<PathBuf>,
+    /// Do not count the time during which the process was stopped (SIGSTOP/SIGCONT).
+    pub exclude_paused: bool,
+    /// Report when the process was stopped (SIGSTOP/SIGTSTP) and continued again.
+    pub report_stops: bool,
+    pub heartbeat_url: Option<String>,
+    pub heartbeat_interval: time::Duration,
+    pub metrics_file: Option<PathBuf>,
+    pub format: OutputFormat,
+    pub color: ColorChoice,
+    /// Where messages go, e.g. only to syslog.
+    pub log_targets: Vec<LogTarget>,
+    pub csv_header: bool,
+    /// Print a JSON summary of the run right before exiting.
+    pub summary: bool,
+    pub timestamp_format: Option<TimestampFormat>,
+    /// Prefix messages with the time since the start instead of the current time.
+    pub elapsed_timestamps: bool,
+    pub message_template: MessageTemplate,
+    pub output_buffer_size: Option<usize>,
+    /// Write all messages to this file instead (opened after daemonizing).
+    pub log_file: Option<PathBuf>,
+    /// Append to `log_file` instead of truncating it.
+    pub log_append: bool,
+    pub whole_seconds: bool,
+    /// Report the `LUCID_*` environment variables and whether the given settings used them.
+    pub verbose_env: Option<Settings>,
+    /// Print the inherited environment, file descriptors, limits etc. at startup.
+    pub dump_state: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            sleep: Sleep::Forever,
+            clamp_negative: false,
+            busy: None,
+            threads: 0,
+            thread_name: None,
+            memory: None,
+            leak_rate: None,
+            disk_write: None,
+            disk_read: None,
+            fsync: false,
+            spam_stdout: None,
+            spam_stderr: None,
+            line_length: 80,
+            stdin_mode: StdinMode::Ignore,
+            lock: None,
+            lock_mode: LockMode::Flock,
+            lock_shared: false,
+            rlimits: vec![],
+            nice: None,
+            cpu_affinity: None,
+            open_fds: 0,
+            fd_target: FdTarget::Path("/dev/null".into()),
+            max_duration: None,
+            grace: None,
+            die_after_signals: None,
+            shutdown_delay: None,
+            crash: None,
+            children: None,
+            fork: None,
+            zombies: 0,
+            init: false,
+            notify: false,
+            listen: None,
+            http_response: None,
+            ready_after: None,
+            repeat: 0,
+            repeat_forever: false,
+            exit_code: 0,
+            exit_code_file: None,
+            random_exit_code: None,
+            fail_probability: None,
+            interrupt_exit_code: None,
+            signal_exit_codes: false,
+            timeout_code: 124,
+            daemon: false,
+            daemon_options: DaemonOptions::default(),
+            orphan: false,
+            die_with_parent: false,
+            pidfile: None,
+            user: None,
+            group: None,
+            touch: None,
+            touch_interval: time::Duration::from_secs(1),
+            touch_final: None,
+            control_socket: None,
+            no_interrupt: false,
+            prefix: "lucid".into(),
+            verbosity: VerbosityLevel::Normal,
+            stderr: false,
+            pre_hook: None,
+            forward_signals: false,
+            then: None,
+            forward_ignored: false,
+            random_start_delay: None,
+            jitter: None,
+            seed: None,
+            report_granularity: false,
+            poll_interval: None,
+            barrier: None,
+            wait_for: None,
+            wait_signals: vec![],
+            dump_core_on: None,
+            exit_on_signals: vec![],
+            ignore_signals: vec![],
+            on_wake_signal: None,
+            on_interrupt_signal: None,
+            required_accuracy: None,
+            measure: false,
+            max_retries: 3,
+            any_key: false,
+            start_marker: false,
+            end_marker: false,
+            marker_fd: None,
+            heartbeat: None,
+            progress: false,
+            countdown: false,
+            extend: None,
+            reload_on_hup: false,
+            script_file: None,
+            exclude_paused: false,
+            report_stops: false,
+            heartbeat_url: None,
+            heartbeat_interval: time::Duration::from_secs(30),
+            metrics_file: None,
+            format: OutputFormat::Human,
+            color: ColorChoice::Auto,
+            log_targets: vec![LogTarget::Stdout],
+            csv_header: false,
+            summary: false,
+            timestamp_format: None,
+            elapsed_timestamps: false,
+            message_template: MessageTemplate::default(),
+            output_buffer_size: None,
+            log_file: None,
+            log_append: false,
+            whole_seconds: false,
+            verbose_env: None,
+            dump_state: false,
+        }
+    }
+}
+
+/// Sleeps as described by `config` and returns the exit code for the process. This installs
+/// process-wide signal handlers. On Windows, that only works once per process.
+pub fn run(config: Config) -> Result<ExitCode> {
+    let mut session = match Session::set_up(&config)? {
+        ControlFlow::Continue(session) => session,
+        ControlFlow::Break(exit_code) => return Ok(exit_code),
+    };
+    let (wake_reason, start_time) = session.sleep()?;
+    session.tear_down(wake_reason, start_time.elapsed())
+}
+
+fn format_cycle(config: &Config, repetition: u32) -> String {
+    match config.repeat_forever {
+        true => repetition.to_string(),
+        false => format!("{}/{}", repetition, config.repeat),
+    }
+}
+
+/// Everything that is set up before the main loop, kept until it is torn down again.
+struct Session<'a> {
+    config: &'a Config,
+    output: OutputHandler<'a>,
+    verbosity_level: VerbosityLevel,
+    csv_output: bool,
+    exit_code: ExitCode,
+    /// Only dropped once ready if there is a `--listen` socket to bind.
+    credentials: Option<Credentials>,
+    sleeping_duration: Option<time::Duration>,
+    wall_deadline: Option<time::SystemTime>,
+    cycle_time: time::Duration,
+    pidfile: Option<Pidfile>,
+    touch_file: Option<TouchFile>,
+    control: Option<ControlSocket>,
+    control_exit_code: Option<ExitCode>,
+    notifier: Option<Notifier>,
+    termination_signals: usize,
+    lock: Option<FileLock>,
+    /// The process group of the pre-hook, see `--forward-signals`.
+    tracked_group: Option<Pid>,
+    marker_sink: Box<dyn Write>,
+    countdown: Option<progress::Countdown>,
+    console_heartbeat: Option<time::Duration>,
+    barrier: Option<Barrier>,
+    file_watch: Option<FileWatch>,
+    key_listener: Option<KeyListener>,
+    memory: MemoryHog,
+    leak_start: time::Instant,
+    busy_load: Option<BusyLoad>,
+    idle_threads: Option<IdleThreads>,
+    children: Vec<process::Child>,
+    child_group: Option<i32>,
+    main_process: Option<u32>,
+    main_exit_code: Option<ExitCode>,
+    user_signals: (Option<Signal>, Option<Signal>),
+    zombies: Vec<i32>,
+    script: Option<Script>,
+    grace_end: Option<Option<time::Instant>>,
+    deferred_termination: Option<signals::Termination>,
+    interrupted_by: Option<signals::Termination>,
+    crash_at: Option<(time::Instant, CrashMode)>,
+    ready_at: Option<time::Instant>,
+    watchdog_interval: Option<time::Duration>,
+    next_watchdog: Option<time::Instant>,
+    next_touch: Option<time::Instant>,
+    polling: bool,
+    ignored_signals: Vec<Signal>,
+    spammers: Vec<Spammer>,
+    stdin_reader: Option<StdinReader>,
+    disk_loads: Vec<DiskLoad>,
+    open_fds: Vec<fs::File>,
+    parent_watch: Option<ParentWatch>,
+    wake_signals: Vec<(Signal, Option<ExitCode>)>,
+    accuracy_not_met: bool,
+    overshoots: Latencies,
+    heartbeat_latencies: Latencies,
+    cycles: u64,
+}
+
+/// The state of a single cycle of the main loop, which starts over for every repetition.
+struct Cycle {
+    start_time: time::Instant,
+    next_heartbeat: time::Duration,
+    next_console_heartbeat: Option<time::Duration>,
+    pauses: PauseTracker,
+    next_action: usize,
+}
+
+impl Cycle {
+    fn start(config: &Config, console_heartbeat: Option<time::Duration>) -> Cycle {
+        let start_time = time::Instant::now();
+        Cycle {
+            start_time,
+            next_heartbeat: config.heartbeat_interval,
+            next_console_heartbeat: console_heartbeat,
+            pauses: PauseTracker::new(start_time, config.exclude_paused),
+            next_action: 0,
+        }
+    }
+}
+
+impl<'a> Session<'a> {
+    /// Applies the process settings and sets up everything the main loop needs. Breaks with the
+    /// exit code if lucid is already done before going to sleep.
+    fn set_up(config: &'a Config) -> Result<ControlFlow<ExitCode, Session<'a>>> {
+        // In CSV mode, the summary line is the only output
+        let csv_output = config.format == OutputFormat::Csv;
+        let verbosity_level = if csv_output {
+            VerbosityLevel::Quiet
+        } else {
+            config.verbosity
+        };
+        let mut exit_code = config.exit_code;
+
+        // Not locked for the whole run, since `--spam-stdout` and `--spam-stderr` write from other
+        // threads
+        let (stream, is_terminal): (Box<dyn Write>, _) =
+            if !config.log_targets.contains(&LogTarget::Stdout) {
+                (Box::new(io::sink()), false)
+            } else if config.stderr {
+                (Box::new(io::stderr()), io::stderr().is_terminal())
+            } else {
+                (Box::new(io::stdout()), io::stdout().is_terminal())
+            };
+        let mut output = OutputHandler::new(
+            stream,
+            PrefixTemplate::parse(&config.prefix)?,
+            verbosity_level,
+            OutputStyle {
+                timestamp_format: config.timestamp_format.clone(),
+                elapsed_timestamps: config.elapsed_timestamps,
+                message_template: config.message_template.clone(),
+                whole_seconds: config.whole_seconds,
+                json: config.format == OutputFormat::Json,
+                // Messages for a log file are kept until it is opened, so they are never colored
+                color: config.log_file.is_none() && config.color.enabled(is_terminal),
+            },
+            config.output_buffer_size,
+        );
+
+        for &target in config
+            .log_targets
+            .iter()
+            .filter(|&&t| t != LogTarget::Stdout)
+        {
+            let tag = output.prefix(time::SystemTime::now());
+            output.add_logger(SystemLogger::open(target, &tag)?);
+        }
+
+        if let Some((count, ref args)) = config.fork {
+            return run_instances(&mut output, count, args, config.forward_signals)
+                .map(ControlFlow::Break);
+        }
+
+        // The log file is only opened after daemonizing, messages up to then are kept
+        if config.log_file.is_some() {
+            output.hold();
+        }
+
+        if let Some(ref settings) = config.verbose_env {
+            dump_environment(&mut output, settings);
+        }
+
+        for limit in &config.rlimits {
+            if !cfg!(unix) {
+                return Err(LucidError::Unsupported("--rlimit"));
+            }
+            let name = limit.resource.name();
+            match limit.apply() {
+                Ok(hard) => output.print_verbose(&format!(
+                    "Set {} to {} (hard limit: {})",
+                    name,
+                    limit.resource.format(limit.soft),
+                    limit.resource.format(hard)
+                )),
+                Err(err) => {
+                    output.print(&format!(
+                        "Could not set {} to {}: {}",
+                        name,
+                        limit.resource.format(limit.soft),
+                        err
+                    ));
+                    return Err(LucidError::RlimitError);
+                }
+            }
+        }
+
+        if let Some(nice) = config.nice {
+            if !cfg!(unix) {
+                return Err(LucidError::Unsupported("--nice"));
+            }
+            if let Err(err) = sched::set_nice(nice) {
+                output.print(&format!(
+                    "Could not set the nice value to {}: {}",
+                    nice, err
+                ));
+                return Err(LucidError::NiceError);
+            }
+            output.print_verbose(&format!("Set the nice value to {}", nice));
+        }
+
+        if let Some(ref cpus) = config.cpu_affinity {
+            if !cfg!(target_os = "linux") {
+                return Err(LucidError::Unsupported("--cpu-affinity"));
+            }
+            match sched::set_cpu_affinity(cpus) {
+                Ok(applied) => output.print_verbose(&format!(
+                    "Running on CPU{} {}",
+                    if applied.len() == 1 { "" } else { "s" },
+                    sched::format_cpu_list(&applied)
+                )),
+                Err(err) => {
+                    output.print(&format!(
+                        "Could not restrict lucid to CPUs {}: {}",
+                        sched::format_cpu_list(cpus),
+                        err
+                    ));
+                    return Err(LucidError::CpuAffinityError);
+                }
+            }
+        }
+
+        // Resolved early, such that unknown names are reported before anything else happens
+        let mut credentials = if config.user.is_some() || config.group.is_some() {
+            Some(Credentials::lookup(
+                config.user.as_deref(),
+                config.group.as_deref(),
+            )?)
+        } else {
+            None
+        };
+
+        let mut rng = match config.seed {
+            None => Rng::from_entropy(),
+            Some(seed) => Rng::new(seed),
+        };
+
+        let mut sleeping_duration = match config.sleep {
+            Sleep::Forever => None,
+            Sleep::For(duration) => Some(duration),
+            Sleep::UntilEndOf(boundary) => {
+                let now = time::SystemTime::now();
+                let target = timestamp::next_boundary(now, boundary)?;
+                output.print_verbose(&format!(
+                    "Sleeping until the end of the {} at {}",
+                    boundary.name(),
+                    TimestampFormat::parse(timestamp::RFC3339)?.render(target)
+                ));
+                Some(target.duration_since(now).unwrap_or_default())
+            }
+            Sleep::Until(deadline) => {
+                let remaining = deadline
+                    .duration_since(time::SystemTime::now())
+                    .map_err(|_| LucidError::DeadlineInPast)?;
+                output.print_verbose(&format!(
+                    "Sleeping until {}, which is {} from now",
+                    TimestampFormat::parse(timestamp::RFC3339)?.render(deadline),
+                    output.format_duration(&remaining)
+                ));
+                Some(remaining)
+            }
+            Sleep::Between(start, end) => {
+                let rfc3339 = TimestampFormat::parse(timestamp::RFC3339)?;
+                let (t1, t2) = (rfc3339.render(start), rfc3339.render(end));
+                let span = match end.duration_since(start) {
+                    Ok(span) => {
+                        output.print_verbose(&format!(
+                            "Time span between '{}' and '{}' is {}",
+                            t1,
+                            t2,
+                            output.format_duration(&span)
+                        ));
+                        span
+                    }
+                    Err(_) if config.clamp_negative => {
+                        output.print_verbose(&format!(
+                            "Time span between '{}' and '{}' is negative, clamping to zero",
+                            t1, t2
+                        ));
+                        time::Duration::from_secs(0)
+                    }
+                    Err(_) => return Err(LucidError::NegativeTimeSpan),
+                };
+                Some(span)
+            }
+            Sleep::Script(ref script) => Some(script.duration()),
+            Sleep::Random(min, max) => {
+                if min > max {
+                    return Err(LucidError::InvalidRandomRange);
+                }
+                let duration = min + (max - min).mul_f64(rng.next_f64());
+                output.print_verbose(&format!(
+                    "Randomly chose to sleep for {} (between {} and {})",
+                    output.format_duration(&duration),
+                    output.format_duration(&min),
+                    output.format_duration(&max)
+                ));
+                Some(duration)
+            }
+        };
+
+        // Deadlines on the wall clock are recomputed while sleeping, such that they are met even if
+        // the system was suspended or the clock was adjusted
+        let mut wall_deadline = match config.sleep {
+            Sleep::Until(deadline) => Some(deadline),
+            Sleep::UntilEndOf(_) => sleeping_duration.map(|d| time::SystemTime::now() + d),
+            _ => None,
+        };
+
+        if let (Some(jitter), Some(duration)) = (config.jitter, sleeping_duration) {
+            let max_offset = jitter.max_offset(duration);
+            let offset = max_offset.mul_f64(rng.next_f64());
+            let jittered = if rng.next_f64() < 0.5 {
+                duration.saturating_sub(offset)
+            } else {
+                duration + offset
+            };
+            output.print_verbose(&format!(
+                "Jitter of up to {} changed the sleep from {} to {}",
+                output.format_duration(&max_offset),
+                output.format_duration(&duration),
+                output.format_duration(&jittered)
+            ));
+            sleeping_duration = Some(jittered);
+            wall_deadline = wall_deadline.map(|deadline| deadline + jittered - duration);
+        }
+
+        if let Some((min, max)) = config.random_exit_code {
+            exit_code = min + (rng.next_u64() % ((max - min) as u64 + 1)) as ExitCode;
+            output.print_verbose(&format!(
+                "Randomly chose exit code {} (between {} and {})",
+                exit_code, min, max
+            ));
+        }
+
+        if let Some(probability) = config.fail_probability {
+            if rng.next_f64() < probability {
+                exit_code = exit_code.max(1);
+                output.print_verbose(&format!(
+                    "Randomly chose to fail with exit code {} (failure probability: {})",
+                    exit_code, probability
+                ));
+            } else {
+                exit_code = 0;
+                output.print_verbose(&format!(
+                    "Randomly chose to succeed (failure probability: {})",
+                    probability
+                ));
+            }
+        }
+
+        if let Some(max_duration) = config.max_duration {
+            if sleeping_duration.is_some_and(|d| d > max_duration) {
+                wall_deadline = None;
+            }
+            match sleeping_duration {
+                Some(duration) if duration <= max_duration => {}
+                Some(duration) => output.print_verbose(&format!(
+                    "Capping the sleep of {} at {} (see '--max-duration')",
+                    output.format_duration(&duration),
+                    output.format_duration(&max_duration)
+                )),
+                None => output.print_verbose(&format!(
+                    "Sleeping for at most {} (see '--max-duration')",
+                    output.format_duration(&max_duration)
+                )),
+            }
+            sleeping_duration =
+                Some(sleeping_duration.map_or(max_duration, |d| d.min(max_duration)));
+        }
+
+        let cycle_time = match config.poll_interval {
+            None => time::Duration::from_millis(100),
+            Some(interval)
+                if interval.is_zero() || sleeping_duration.is_some_and(|d| interval > d) =>
+            {
+                return Err(LucidError::InvalidArgument("--poll-interval"));
+            }
+            Some(interval) => interval,
+        };
+
+        let wait_signals = &config.wait_signals;
+
+        if config.daemon {
+            output.print_verbose("Daemonizing..");
+            output.flush();
+            platform::daemonize(&config.daemon_options)?;
+        }
+
+        if let Some(ref path) = config.log_file {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(config.log_append)
+                .truncate(!config.log_append)
+                .open(path)
+                .map_err(|_| LucidError::LogFileError)?;
+            output.redirect(Box::new(file));
+        }
+
+        if config.orphan {
+            const TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+            let original = process::id();
+            output.print_verbose(&format!("getppid() = {}", platform::parent_id()));
+            output.flush();
+            if let Some(pid) = platform::fork_orphan()? {
+                output.print(&format!("Leaving orphan process with PID {} behind", pid));
+                output.flush();
+                return Ok(ControlFlow::Break(0));
+            }
+
+            // Wait until the exit of the original process has been noticed
+            let start = time::Instant::now();
+            while platform::parent_id() == original && start.elapsed() < TIMEOUT {
+                thread::sleep(time::Duration::from_millis(10));
+            }
+            output.print_verbose(&format!(
+                "getppid() = {} (after being orphaned)",
+                platform::parent_id()
+            ));
+        }
+
+        let pidfile = config.pidfile.as_deref().map(Pidfile::create).transpose()?;
+        let touch_file = config
+            .touch
+            .as_deref()
+            .map(|path| TouchFile::create(path, config.touch_final.clone()))
+            .transpose()?;
+        let control = config
+            .control_socket
+            .clone()
+            .map(ControlSocket::bind)
+            .transpose()?;
+        let control_exit_code = None;
+
+        let notifier = if config.notify {
+            let notifier = Notifier::from_environment()?;
+            if notifier.is_none() {
+                output
+                    .print_verbose("$NOTIFY_SOCKET is not set - not notifying the service manager");
+            }
+            notifier
+        } else {
+            None
+        };
+
+        // Print status information
+        output.print_verbose(&format!(
+            "getcwd() = {}",
+            env::current_dir()
+                .map(|p| p.to_string_lossy().into_owned())
+                .map(|s| format!("\"{}\"", s))
+                .unwrap_or_else(|_| "<error: could not read current working directory>".into())
+        ));
+        output.print_verbose(&format!("getpid() = {}", process::id()));
+        // Before installing signal handlers, which would hide the inherited dispositions
+        if config.dump_state {
+            for line in state::describe() {
+                output.print(&line);
+            }
+        }
+        if config.dump_state || verbosity_level == VerbosityLevel::Verbose {
+            for line in state::describe_container() {
+                output.print(&line);
+            }
+        }
+
+        // Set up signal handlers. Every delivery is counted, so none get lost if several signals
+        // arrive within one cycle of the main loop.
+        signals::install_termination_handlers()?;
+        signals::install_status_handler()?;
+        // In CSV mode, toggling would mix messages into the summary line
+        if !csv_output {
+            signals::install_verbosity_handler()?;
+        }
+        if config.extend.is_some() {
+            signals::install_hangup_handler("--extend")?;
+        }
+        if config.reload_on_hup {
+            signals::install_hangup_handler("--reload-on-hup")?;
+        }
+        if config.exclude_paused {
+            signals::install_resume_handler("--exclude-paused")?;
+        }
+        if config.report_stops {
+            signals::install_resume_handler("--report-stops")?;
+            signals::install_stop_handler()?;
+        }
+        if config.init {
+            signals::install_child_handler()?;
+        }
+        let wake_signals: Vec<(Signal, Option<ExitCode>)> = wait_signals
+            .iter()
+            .chain(&config.exit_on_signals)
+            .copied()
+            .collect();
+        let wake_signal_list: Vec<Signal> =
+            wake_signals.iter().map(|&(signal, _)| signal).collect();
+        if config
+            .ignore_signals
+            .iter()
+            .any(|signal| wake_signal_list.contains(signal))
+        {
+            return Err(LucidError::InvalidArgument("--ignore"));
+        }
+        if let Some(signal) = config.dump_core_on {
+            if wake_signal_list.contains(&signal) || config.ignore_signals.contains(&signal) {
+                return Err(LucidError::InvalidArgument("--dump-core-on"));
+            }
+            signals::install_handlers(&[signal])?;
+        }
+        signals::install_handlers(&wake_signal_list)?;
+        signals::install_ignore_handlers(&config.ignore_signals)?;
+
+        let mut termination_signals = 0;
+        if let Some(window) = config.random_start_delay {
+            let delay = window.mul_f64(rng.next_f64());
+            output.print(&format!(
+                "Delaying start by {}",
+                output.format_duration(&delay)
+            ));
+
+            let delay_start = time::Instant::now();
+            while delay_start.elapsed() < delay {
+                let received = signals::take_terminations();
+                termination_signals += received.len();
+                if let Some(code) =
+                    interrupt_before_sleep(config, &mut output, &received, "start delay", exit_code)
+                {
+                    return Ok(ControlFlow::Break(code));
+                }
+                signals::wait(Some(delay.saturating_sub(delay_start.elapsed())));
+            }
+        }
+
+        let lock = match config.lock {
+            Some(ref path) => {
+                let lock = FileLock::open(path, config.lock_mode, config.lock_shared)?;
+                let mut waiting = false;
+                while !lock.try_acquire()? {
+                    if !waiting {
+                        output.print(&format!(
+                            "Waiting for the {} lock on '{}'",
+                            lock.describe(),
+                            path.display()
+                        ));
+                        waiting = true;
+                    }
+                    let received = signals::take_terminations();
+                    termination_signals += received.len();
+                    if let Some(code) = interrupt_before_sleep(
+                        config,
+                        &mut output,
+                        &received,
+                        "the wait for the lock",
+                        exit_code,
+                    ) {
+                        return Ok(ControlFlow::Break(code));
+                    }
+                    signals::wait(Some(LOCK_RETRY));
+                }
+                output.print(&format!(
+                    "Acquired the {} lock on '{}'",
+                    lock.describe(),
+                    path.display()
+                ));
+                Some(lock)
+            }
+            None => None,
+        };
+
+        let mut tracked_group = None;
+        if let Some(ref command) = config.pre_hook {
+            output.print_verbose(&format!("Running pre-hook '{}'", command));
+            let mut hook = platform::shell_command(command);
+            if config.forward_signals {
+                platform::use_process_group(&mut hook, 0)?;
+            }
+            output.flush();
+            let mut child = hook.spawn().map_err(|_| LucidError::FailedToRunPreHook)?;
+            if config.forward_signals {
+                tracked_group = Some(Pid::from_raw(child.id() as i32));
+            }
+            let status = child.wait().map_err(|_| LucidError::FailedToRunPreHook)?;
+            if !status.success() {
+                let code = status.code().unwrap_or(1);
+                output.print(&format!("Pre-hook failed with exit code {}", code));
+                return Ok(ControlFlow::Break(code));
+            }
+        }
+
+        if config.report_granularity {
+            output.print(&format!(
+                "Sleep granularity ≈ {:.3}ms",
+                measure_sleep_granularity().as_secs_f64() * 1e3
+            ));
+        }
+
+        let mut marker_sink: Box<dyn Write> = match config.marker_fd {
+            None => Box::new(io::stdout()),
+            Some(fd) => Box::new(platform::open_fd(fd)?),
+        };
+
+        let heartbeat_url = config.heartbeat_url.as_deref();
+        if heartbeat_url.is_some() && !webhook::SUPPORTED {
+            return Err(LucidError::WebhookNotSupported);
+        }
+        let heartbeat_interval = config.heartbeat_interval;
+        if heartbeat_interval.is_zero() {
+            return Err(LucidError::InvalidArgument("--heartbeat-interval"));
+        }
+
+        let console_heartbeat = config.heartbeat;
+        if console_heartbeat.is_some_and(|interval| interval.is_zero()) {
+            return Err(LucidError::InvalidArgument("--heartbeat"));
+        }
+        let countdown = if config.progress {
+            progress::Countdown::new()
+        } else if config.countdown
+            && sleeping_duration.is_some()
+            && verbosity_level == VerbosityLevel::Normal
+            && config.format == OutputFormat::Human
+            && !config.stderr
+            && config.log_file.is_none()
+            && config.log_targets.contains(&LogTarget::Stdout)
+        {
+            progress::Countdown::on_stdout()
+        } else {
+            None
+        };
+        // Without a terminal to draw on, the countdown falls back to plain heartbeat lines
+        let console_heartbeat = console_heartbeat.or_else(|| {
+            (config.progress && countdown.is_none()).then_some(time::Duration::from_secs(1))
+        });
+
+        for &(pid, _) in config
+            .on_wake_signal
+            .iter()
+            .chain(&config.on_interrupt_signal)
+        {
+            signals::kill(pid, None).map_err(|_| LucidError::NoSuchProcess(pid))?;
+        }
+
+        let barrier = match config.barrier {
+            None => None,
+            Some((ref path, count)) => {
+                if count == 0 {
+                    return Err(LucidError::InvalidArgument("--barrier-count"));
+                }
+                let (barrier, arrived) = Barrier::arrive(path, count)?;
+                output.print(&format!(
+                    "Arrived at barrier '{}' ({}/{})",
+                    path.display(),
+                    arrived,
+                    count
+                ));
+                Some(barrier)
+            }
+        };
+
+        let file_watch = config.wait_for.as_ref().map(|(path, condition)| {
+            let watch = FileWatch::new(path, *condition);
+            output.print_verbose(&format!("Waiting for {}", watch.describe()));
+            watch
+        });
+
+        match sleeping_duration {
+            None => {
+                output.print_event(Event::SleepStart, None, "Going to sleep forever");
+            }
+            Some(sleeping_duration) => {
+                output.print_event(
+                    Event::SleepStart,
+                    Some(sleeping_duration),
+                    &format!(
+                        "Going to sleep for {}",
+                        output.format_duration(&sleeping_duration)
+                    ),
+                );
+            }
+        }
+
+        let key_listener = if config.any_key {
+            KeyListener::new()?
+        } else {
+            None
+        };
+
+        if config.start_marker {
+            writeln!(marker_sink, "LUCID_START {}", epoch_nanos()).ok();
+        }
+
+        if config.repeat_forever || config.repeat > 1 {
+            output.print_verbose(&format!("Cycle {}", format_cycle(config, 1)));
+        }
+
+        let mut memory = MemoryHog::default();
+        if let Some(bytes) = config.memory {
+            memory.allocate(bytes);
+            output.print(&format!("Allocated {}", memory::size_as_str(bytes)));
+        }
+        let leak_start = time::Instant::now();
+
+        let thread_name = config.thread_name.as_deref();
+        let spawn_failed = |output: &mut OutputHandler, count, SpawnError(index, err)| {
+            output.print(&format!(
+                "Could not spawn thread {} of {}: {}",
+                index + 1,
+                count,
+                err
+            ));
+            LucidError::ThreadsError
+        };
+        let busy_load = match config.busy {
+            Some((threads, load)) => {
+                output.print(&format!(
+                    "Keeping {} thread{} busy at {}% load",
+                    threads,
+                    if threads == 1 { "" } else { "s" },
+                    (load * 100.0).round()
+                ));
+                let busy_load = BusyLoad::start(threads, load, thread_name)
+                    .map_err(|err| spawn_failed(&mut output, threads, err))?;
+                Some(busy_load)
+            }
+            None => None,
+        };
+        let idle_threads = match config.threads {
+            0 => None,
+            threads => {
+                let idle_threads = IdleThreads::start(threads, thread_name)
+                    .map_err(|err| spawn_failed(&mut output, threads, err))?;
+                output.print_verbose(&format!(
+                    "Spawned {} idle thread{}",
+                    threads,
+                    if threads == 1 { "" } else { "s" }
+                ));
+                Some(idle_threads)
+            }
+        };
+
+        let mut children = vec![];
+        // With `--forward-signals`, all children share a process group, led by the first child
+        let mut child_group = None;
+        if let Some((count, ref args)) = config.children {
+            let executable = env::current_exe().map_err(|_| LucidError::FailedToSpawnChildren)?;
+            for _ in 0..count {
+                let mut command = process::Command::new(&executable);
+                command.args(args);
+                if config.forward_signals {
+                    platform::use_process_group(&mut command, child_group.unwrap_or(0))?;
+                }
+                let child = command
+                    .spawn()
+                    .map_err(|_| LucidError::FailedToSpawnChildren)?;
+                output.print(&format!("Spawned child process with PID {}", child.id()));
+                if config.forward_signals {
+                    child_group.get_or_insert(child.id() as i32);
+                }
+                children.push(child);
+            }
+        }
+
+        // In init mode, the `then` command runs next to lucid instead of replacing it afterwards
+        let mut main_process = None;
+        let main_exit_code = None;
+        if config.init {
+            if process::id() != 1 && platform::become_subreaper() {
+                output.print_verbose("Adopting orphaned descendants as a child subreaper");
+            }
+            if let Some(ref argv) = config.then {
+                let mut command = process::Command::new(&argv[0]);
+                command.args(&argv[1..]);
+                if config.forward_signals {
+                    platform::use_process_group(&mut command, child_group.unwrap_or(0))?;
+                }
+                let child = command
+                    .spawn()
+                    .map_err(|_| LucidError::FailedToRunCommand)?;
+                if config.forward_signals {
+                    child_group.get_or_insert(child.id() as i32);
+                }
+                output.print(&format!(
+                    "Started main process '{}' with PID {}",
+                    argv.join(" "),
+                    child.id()
+                ));
+                main_process = Some(child.id());
+                children.push(child);
+            }
+        }
+
+        // SIGUSR1 and SIGUSR2, which are forwarded after being handled (there are none on Windows)
+        let user_signals = (
+            signals::parse_signal("SIGUSR1"),
+            signals::parse_signal("SIGUSR2"),
+        );
+
+        let mut zombies = vec![];
+        for _ in 0..config.zombies {
+            let pid = platform::spawn_zombie()?;
+            output.print(&format!("Created zombie process with PID {}", pid));
+            zombies.push(pid);
+        }
+
+        let script = match config.sleep {
+            Sleep::Script(ref script) => Some(script.clone()),
+            _ => None,
+        };
+
+        // Termination signals during the first `grace` of the sleep are deferred until it is over
+        // A grace period too long for a deadline never ends
+        let grace_end = config.grace.map(deadline_after);
+        let deferred_termination = None;
+        let interrupted_by = None;
+        // Never due if the crash is too far in the future
+        let crash_at = config
+            .crash
+            .and_then(|(after, mode)| Some((deadline_after(after)?, mode)));
+        // `None` if never ready, since `--ready-after` is too far in the future
+        let ready_at = (notifier.is_some() || config.listen.is_some())
+            .then(|| config.ready_after.unwrap_or_default())
+            .and_then(deadline_after);
+        let watchdog_interval = notifier.as_ref().and_then(Notifier::watchdog_interval);
+        let next_watchdog = watchdog_interval.and_then(deadline_after);
+        let next_touch = touch_file
+            .as_ref()
+            .and_then(|_| deadline_after(config.touch_interval));
+
+        // Conditions that can only be checked periodically, everything else is either due at a
+        // known time or arrives via a signal
+        let polling = config.poll_interval.is_some()
+            || barrier.is_some()
+            || file_watch.is_some()
+            || key_listener.is_some()
+            || config.exclude_paused
+            || config.report_stops
+            || config.leak_rate.is_some();
+
+        // The start delay, spawning children etc. already count towards a wall-clock deadline
+        if let Some(deadline) = wall_deadline {
+            sleeping_duration = Some(
+                deadline
+                    .duration_since(time::SystemTime::now())
+                    .unwrap_or_default(),
+            );
+        }
+
+        // Signals that did not interrupt the sleep, see `Config::forward_ignored`
+        let ignored_signals = vec![];
+
+        let spammers: Vec<_> = [
+            (Stream::Stdout, config.spam_stdout),
+            (Stream::Stderr, config.spam_stderr),
+        ]
+        .into_iter()
+        .filter_map(|(stream, rate)| rate.map(|rate| (stream, rate)))
+        .map(|(stream, rate)| {
+            output.print_verbose(&format!(
+                "Writing {} lines/s of {} characters to {}",
+                rate,
+                config.line_length,
+                stream.name()
+            ));
+            Spammer::start(stream, rate, config.line_length)
+        })
+        .collect();
+
+        let stdin_reader = StdinReader::start(config.stdin_mode)?;
+
+        let mut disk_loads = vec![];
+        if let Some((ref path, rate)) = config.disk_write {
+            output.print_verbose(&format!(
+                "Writing {}/s to '{}'",
+                memory::size_as_str(rate),
+                path.display()
+            ));
+            disk_loads.push(
+                DiskLoad::write(path, rate, config.fsync).map_err(|_| LucidError::DiskIoError)?,
+            );
+        }
+        if let Some((ref path, rate)) = config.disk_read {
+            output.print_verbose(&format!(
+                "Reading {}/s from '{}'",
+                memory::size_as_str(rate),
+                path.display()
+            ));
+            disk_loads.push(DiskLoad::read(path, rate).map_err(|_| LucidError::DiskIoError)?);
+        }
+
+        let mut open_fds = Vec::with_capacity(config.open_fds);
+        while open_fds.len() < config.open_fds {
+            let missing = config.open_fds - open_fds.len();
+            if let Err(err) = config.fd_target.open(&mut open_fds, missing) {
+                output.print(&format!(
+                    "Could not open descriptor {} of {}: {}",
+                    open_fds.len() + 1,
+                    config.open_fds,
+                    err
+                ));
+                return Err(LucidError::OpenFdsError);
+            }
+        }
+        let numbers = (
+            open_fds.first().and_then(fds::number),
+            open_fds.last().and_then(fds::number),
+        );
+        if let (Some(first), Some(last)) = numbers {
+            output.print_verbose(&format!(
+                "Opened {} file descriptors ({} to {})",
+                open_fds.len(),
+                first,
+                last
+            ));
+        } else if !open_fds.is_empty() {
+            output.print_verbose(&format!("Opened {} files", open_fds.len()));
+        }
+
+        // Everything that may need privileges is set up by now, except for a `--listen` socket that
+        // is only bound once ready
+        if ready_at.is_none() || config.listen.is_none() {
+            drop_privileges(&mut output, credentials.take())?;
+        }
+
+        // In verbose mode, changes of the parent are reported (where there is a parent PID)
+        let parent_watch = if config.die_with_parent
+            || (cfg!(unix) && verbosity_level == VerbosityLevel::Verbose)
+        {
+            let parent_watch = ParentWatch::start()?;
+            if config.die_with_parent {
+                output.print_verbose(&format!(
+                    "Exiting once the parent process {} exits",
+                    parent_watch.parent()
+                ));
+            }
+            Some(parent_watch)
+        } else {
+            None
+        };
+
+        Ok(ControlFlow::Continue(Session {
+            config,
+            output,
+            verbosity_level,
+            csv_output,
+            exit_code,
+            credentials,
+            sleeping_duration,
+            wall_deadline,
+            cycle_time,
+            pidfile,
+            touch_file,
+            control,
+            control_exit_code,
+            notifier,
+            termination_signals,
+            lock,
+            tracked_group,
+            marker_sink,
+            countdown,
+            console_heartbeat,
+            barrier,
+            file_watch,
+            key_listener,
+            memory,
+            leak_start,
+            busy_load,
+            idle_threads,
+            children,
+            child_group,
+            main_process,
+            main_exit_code,
+            user_signals,
+            zombies,
+            script,
+            grace_end,
+            deferred_termination,
+            interrupted_by,
+            crash_at,
+            ready_at,
+            watchdog_interval,
+            next_watchdog,
+            next_touch,
+            polling,
+            ignored_signals,
+            spammers,
+            stdin_reader,
+            disk_loads,
+            open_fds,
+            parent_watch,
+            wake_signals,
+            accuracy_not_met: false,
+            overshoots: Latencies::default(),
+            heartbeat_latencies: Latencies::default(),
+            cycles: 0,
+        }))
+    }
+
+    /// Runs the main loop, cycle after cycle, and returns why it ended and when the last cycle
+    /// started.
+    fn sleep(&mut self) -> Result<(WakeReason, time::Instant)> {
+        let config = self.config;
+        let mut retries = 0;
+        let mut repetition = 1;
+        loop {
+            let mut cycle = Cycle::start(config, self.console_heartbeat);
+            let wake_reason = self.run_cycle(&mut cycle)?;
+
+            if let (true, WakeReason::Elapsed, Some(sleeping_duration)) =
+                (config.measure, wake_reason, self.sleeping_duration)
+            {
+                let actual = cycle
+                    .start_time
+                    .elapsed()
+                    .saturating_sub(cycle.pauses.paused());
+                let overshoot = actual.saturating_sub(sleeping_duration);
+                self.output.print_verbose(&format!(
+                    "Requested {:.6}s, slept {:.6}s (overshoot: {}us)",
+                    sleeping_duration.as_secs_f64(),
+                    actual.as_secs_f64(),
+                    overshoot.as_micros()
+                ));
+                self.overshoots.add(overshoot);
+            }
+
+            if let (WakeReason::Elapsed, Some(sleeping_duration), Some(required_accuracy)) = (
+                wake_reason,
+                self.sleeping_duration,
+                config.required_accuracy,
+            ) {
+                let overshoot = cycle
+                    .start_time
+                    .elapsed()
+                    .saturating_sub(cycle.pauses.paused())
+                    .saturating_sub(sleeping_duration);
+                let overshoot_percent = if sleeping_duration.is_zero() {
+                    0.0
+                } else {
+                    overshoot.as_secs_f64() / sleeping_duration.as_secs_f64() * 100.0
+                };
+
+                if overshoot_percent > required_accuracy {
+                    if retries == config.max_retries {
+                        self.accuracy_not_met = true;
+                        return Ok((wake_reason, cycle.start_time));
+                    }
+                    retries += 1;
+                    self.output.print(&format!(
+                        "Overshoot of {:.3}ms ({:.2}%) exceeds the required accuracy, \
+                         retrying ({}/{})",
+                        overshoot.as_secs_f64() * 1e3,
+                        overshoot_percent,
+                        retries,
+                        config.max_retries
+                    ));
+                    continue;
+                }
+            }
+
+            if wake_reason == WakeReason::Elapsed
+                && (config.repeat_forever || repetition < config.repeat)
+            {
+                self.output.print(&format!(
+                    "Finished cycle {}",
+                    format_cycle(config, repetition)
+                ));
+                repetition += 1;
+                retries = 0;
+                // Further cycles last as long as the first one
+                self.wall_deadline = None;
+                self.output
+                    .print_verbose(&format!("Cycle {}", format_cycle(config, repetition)));
+                continue;
+            }
+
+            return Ok((wake_reason, cycle.start_time));
+        }
+    }
+
+    /// Sleeps until something wakes lucid up, which ends the current cycle.
+    fn run_cycle(&mut self, cycle: &mut Cycle) -> Result<WakeReason> {
+        let config = self.config;
+        let heartbeat_url = config.heartbeat_url.as_deref();
+        loop {
+            if config.report_stops && signals::take_stop_request() {
+                self.output
+                    .print_event(Event::Signal, None, "Received SIGTSTP - stopping.");
+                self.output.flush();
+                cycle.pauses.stopping(time::Instant::now());
+                signals::stop();
+            }
+            if config.exclude_paused || config.report_stops {
+                let resumed = signals::take_resumed();
+                if let Some(pause) = cycle.pauses.check(time::Instant::now(), resumed) {
+                    let pause_str = self.output.format_duration(&pause);
+                    if config.exclude_paused {
+                        self.output.print(&format!(
+                            "Resumed after being paused for {} - not counting it.",
+                            pause_str
+                        ));
+                    } else {
+                        // Without `--exclude-paused`, the time stopped is part of the sleep
+                        let slept = cycle.start_time.elapsed();
+                        let remaining = match self.sleeping_duration {
+                            Some(d) if slept < d => {
+                                format!("{} remaining", self.output.format_duration(&(d - slept)))
+                            }
+                            Some(_) => "the sleep is over".into(),
+                            None => "sleeping forever".into(),
+                        };
+                        self.output.print_event(
+                            Event::Signal,
+                            None,
+                            &format!(
+                                "Continued after being stopped for {} (counted towards the \
+                                 sleep, {}).",
+                                pause_str, remaining
+                            ),
+                        );
+                    }
+                }
+            }
+            let since_start = cycle
+                .start_time
+                .elapsed()
+                .saturating_sub(cycle.pauses.paused());
+            self.cycles += 1;
+
+            if let Some(rate) = config.leak_rate {
+                let leaked = (rate as f64 * self.leak_start.elapsed().as_secs_f64()) as u64;
+                self.memory.grow_to(config.memory.unwrap_or(0) + leaked);
+            }
+
+            if let Some(url) = heartbeat_url {
+                if since_start >= cycle.next_heartbeat {
+                    if config.measure {
+                        self.heartbeat_latencies
+                            .add(since_start - cycle.next_heartbeat);
+                    }
+                    let remaining = self
+                        .sleeping_duration
+                        .map(|d| d.saturating_sub(since_start));
+                    if let Err(err) = webhook::post_heartbeat(
+                        url,
+                        process::id(),
+                        &since_start,
+                        remaining.as_ref(),
+                    ) {
+                        self.output
+                            .print(&format!("Heartbeat to '{}' failed: {}", url, err));
+                    }
+                    cycle.next_heartbeat += config.heartbeat_interval;
+                }
+            }
+
+            if let (Some(next), Some(interval)) =
+                (cycle.next_console_heartbeat, self.console_heartbeat)
+            {
+                if since_start >= next {
+                    if config.measure {
+                        self.heartbeat_latencies.add(since_start - next);
+                    }
+                    match self.sleeping_duration {
+                        None => self.output.print_event_always(
+                            Event::Heartbeat,
+                            Some(since_start),
+                            &format!(
+                                "Still sleeping after {}",
+                                self.output.format_duration(&since_start)
+                            ),
+                        ),
+                        Some(d) if since_start < d => self.output.print_event_always(
+                            Event::Heartbeat,
+                            Some(since_start),
+                            &format!(
+                                "Still sleeping, {} remaining",
+                                self.output.format_duration(&(d - since_start))
+                            ),
+                        ),
+                        Some(_) => {}
+                    }
+                    cycle.next_console_heartbeat = Some(next + interval);
+                }
+            }
+
+            if config.reload_on_hup && signals::take_hangups() > 0 {
+                self.output
+                    .print("Received SIGHUP - reloading configuration.");
+                if let Some(ref path) = config.script_file {
+                    match fs::read_to_string(path)
+                        .map_err(|_| LucidError::InvalidArgument("--script-file"))
+                        .and_then(|content| Script::parse(&content))
+                    {
+                        Ok(reloaded) => {
+                            self.output.print_verbose(&format!(
+                                "Reloaded the script from '{}'",
+                                path.display()
+                            ));
+                            self.sleeping_duration = Some(match config.max_duration {
+                                Some(max) => reloaded.duration().min(max),
+                                None => reloaded.duration(),
+                            });
+                            self.script = Some(reloaded);
+                        }
+                        Err(err) => self.output.print(&format!(
+                            "Could not reload the script: {} - keeping the old one.",
+                            err.message()
+                        )),
+                    }
+                }
+                *cycle = Cycle::start(config, self.console_heartbeat);
+                continue;
+            }
+
+            if let Some(extension) = config.extend {
+                for _ in 0..signals::take_hangups() {
+                    match self.sleeping_duration {
+                        None => self
+                            .output
+                            .print_verbose("Received SIGHUP - already sleeping forever."),
+                        Some(ref mut total) => {
+                            *total += extension;
+                            self.wall_deadline =
+                                self.wall_deadline.map(|deadline| deadline + extension);
+                            self.output.print(&format!(
+                                "Received SIGHUP - extended the sleep to {} ({} remaining).",
+                                self.output.format_duration(total),
+                                self.output
+                                    .format_duration(&total.saturating_sub(since_start))
+                            ));
+                        }
+                    }
+                }
+            }
+
+            for _ in 0..signals::take_status_requests() {
+                self.output.print_always(&status_line(
+                    &self.output,
+                    since_start,
+                    self.sleeping_duration,
+                    self.termination_signals,
+                    self.memory.allocated(),
+                ));
+                if let (Some(group), Some(signal)) = (self.child_group, self.user_signals.0) {
+                    forward_to_group(&mut self.output, Pid::from_raw(group), signal);
+                }
+            }
+
+            for _ in 0..signals::take_verbosity_toggles() {
+                let verbose = self.output.toggle_verbose();
+                self.output.print_always(&format!(
+                    "Received SIGUSR2 - verbose output {}.",
+                    if verbose { "on" } else { "off" }
+                ));
+                if let (Some(group), Some(signal)) = (self.child_group, self.user_signals.1) {
+                    forward_to_group(&mut self.output, Pid::from_raw(group), signal);
+                }
+            }
+
+            for err in self.disk_loads.iter().filter_map(DiskLoad::take_error) {
+                self.output
+                    .print(&format!("Disk I/O failed, stopping it: {}", err));
+            }
+
+            if let Some(ref reader) = self.stdin_reader {
+                for input in reader.take_input() {
+                    match input {
+                        Input::Line(line) => self.output.print(&line),
+                        Input::Closed(bytes) => self.output.print_verbose(&format!(
+                            "Reached the end of stdin after {} bytes",
+                            bytes
+                        )),
+                    }
+                }
+            }
+
+            if let Some(ref control) = self.control {
+                let mut wake = false;
+                for request in control.take_requests() {
+                    match request.command {
+                        Command::Status => request.reply(&status_line(
+                            &self.output,
+                            since_start,
+                            self.sleeping_duration,
+                            self.termination_signals,
+                            self.memory.allocated(),
+                        )),
+                        Command::Wake => {
+                            request.reply("OK");
+                            wake = true;
+                        }
+                        Command::Exit(code) => {
+                            request.reply("OK");
+                            self.control_exit_code = Some(code);
+                            wake = true;
+                        }
+                        Command::SetVerbosity(verbosity) => {
+                            self.output.set_verbosity(verbosity);
+                            request.reply("OK");
+                        }
+                    }
+                }
+                if wake {
+                    self.output.print("Woken up via the control socket.");
+                    return Ok(match self.control_exit_code {
+                        Some(_) => WakeReason::ControlExit,
+                        None => WakeReason::Control,
+                    });
+                }
+            }
+
+            if let Some((old, new)) = self.parent_watch.as_mut().and_then(ParentWatch::check) {
+                self.output
+                    .print_verbose(&format!("getppid() changed from {} to {}", old, new));
+                if config.die_with_parent {
+                    self.output
+                        .print(&format!("Parent process {} exited - exiting.", old));
+                    return Ok(WakeReason::ParentDied);
+                }
+            }
+
+            if config.init {
+                for exited in platform::reap_any() {
+                    self.children.retain(|child| child.id() != exited.pid);
+                    if self.main_process == Some(exited.pid) {
+                        self.output.print(&format!(
+                            "Main process {} exited ({}).",
+                            exited.pid, exited.status
+                        ));
+                        self.main_exit_code = Some(exited.code);
+                    } else {
+                        self.output.print_verbose(&format!(
+                            "Reaped process {} ({})",
+                            exited.pid, exited.status
+                        ));
+                    }
+                }
+                if self.main_exit_code.is_some() {
+                    return Ok(WakeReason::MainProcessExited);
+                }
+            }
+
+            let mut caught = vec![];
+            for termination in signals::take_terminations() {
+                self.termination_signals += 1;
+                if let (Some(group), Some(signal)) = (self.child_group, termination.signal) {
+                    forward_to_group(&mut self.output, Pid::from_raw(group), signal);
+                } else if config.init {
+                    forward_to_children(&mut self.output, &self.children, termination);
+                }
+                // Only the exit of the main process ends the sleep of `--init`
+                if config.init && self.main_process.is_some() {
+                    continue;
+                }
+                match config.die_after_signals {
+                    Some(count) if self.termination_signals < count => self.output.print_event(
+                        Event::Signal,
+                        None,
+                        &format!(
+                            "Ignoring {} ({}/{}).",
+                            termination, self.termination_signals, count
+                        ),
+                    ),
+                    _ if config.no_interrupt => {
+                        self.output.print_event(
+                            Event::Signal,
+                            None,
+                            &format!("Ignoring {}.", termination),
+                        );
+                        self.ignored_signals.extend(termination.signal);
+                    }
+                    _ => caught.push(termination),
+                }
+            }
+            if let Some(termination) = caught.last().copied().or(self.deferred_termination) {
+                if self
+                    .grace_end
+                    .is_some_and(|end| end.is_none_or(|end| time::Instant::now() < end))
+                {
+                    for termination in &caught {
+                        self.output.print_event(
+                            Event::Signal,
+                            None,
+                            &format!(
+                                "Caught {} during the grace period - deferring it.",
+                                termination
+                            ),
+                        );
+                    }
+                    self.deferred_termination = Some(termination);
+                } else {
+                    self.output.print_event(
+                        Event::Signal,
+                        None,
+                        &format!("Caught {} - interrupting sleep.", termination),
+                    );
+                    self.interrupted_by = Some(termination);
+                    return Ok(WakeReason::Interrupted);
+                }
+            }
+
+            for signal in signals::take_ignored() {
+                self.output
+                    .print_event(Event::Signal, None, &format!("Ignoring {}.", signal));
+                self.ignored_signals.push(signal);
+            }
+
+            if let Some(signal) = signals::take_received() {
+                if config.dump_core_on == Some(signal) {
+                    self.output
+                        .print(&format!("Received {} - dumping core.", signal));
+                    self.output.flush();
+                    if config.summary {
+                        print_summary(
+                            &mut self.output,
+                            config,
+                            self.verbosity_level,
+                            &RunSummary::before_crash(
+                                self.sleeping_duration,
+                                cycle.start_time.elapsed(),
+                                self.cycles,
+                                self.termination_signals,
+                                CrashMode::Core,
+                            ),
+                        );
+                    }
+                    crash::crash(CrashMode::Core);
+                }
+                self.output.print_event(
+                    Event::Signal,
+                    None,
+                    &format!("Received {} - waking up.", signal),
+                );
+                return Ok(WakeReason::Signal(signal));
+            }
+
+            if let Some(ref key_listener) = self.key_listener {
+                if key_listener.key_pressed() {
+                    self.output.print("Key pressed - interrupting sleep.");
+                    return Ok(WakeReason::KeyPressed);
+                }
+            }
+
+            if let Some(ref barrier) = self.barrier {
+                if barrier.is_complete()? {
+                    self.output.print("All processes arrived at the barrier.");
+                    return Ok(WakeReason::BarrierReached);
+                }
+            }
+
+            if let Some(ref file_watch) = self.file_watch {
+                if file_watch.is_met() {
+                    self.output
+                        .print(&format!("Done waiting for {}.", file_watch.describe()));
+                    return Ok(WakeReason::FileCondition);
+                }
+            }
+
+            if self.ready_at.is_some_and(|at| time::Instant::now() >= at) {
+                if let Some(ref notifier) = self.notifier {
+                    send_notification(&mut self.output, notifier, "READY=1");
+                }
+                if let Some(ref address) = config.listen {
+                    let local_addr = listen::listen(address, config.http_response.clone())
+                        .map_err(|_| LucidError::ListenError)?;
+                    self.output
+                        .print_verbose(&format!("Listening on {}", local_addr));
+                    drop_privileges(&mut self.output, self.credentials.take())?;
+                    if let Some(ref parent_watch) = self.parent_watch {
+                        parent_watch.arm()?;
+                    }
+                }
+                self.ready_at = None;
+            }
+            if let Some(ref notifier) = self.notifier {
+                if let (Some(next), Some(interval)) = (self.next_watchdog, self.watchdog_interval) {
+                    if time::Instant::now() >= next {
+                        send_notification(&mut self.output, notifier, "WATCHDOG=1");
+                        self.next_watchdog = next.checked_add(interval);
+                    }
+                }
+            }
+
+            if let (Some(next), Some(ref file)) = (self.next_touch, &self.touch_file) {
+                if time::Instant::now() >= next {
+                    if let Err(err) = file.touch() {
+                        self.output
+                            .print(&format!("Could not touch the '--touch' file: {}", err));
+                    }
+                    self.next_touch = next.checked_add(config.touch_interval);
+                }
+            }
+
+            if let Some((at, mode)) = self.crash_at {
+                if time::Instant::now() >= at {
+                    self.output.print(&format!("Crashing ({})", mode.name()));
+                    self.output.flush();
+                    if config.summary {
+                        print_summary(
+                            &mut self.output,
+                            config,
+                            self.verbosity_level,
+                            &RunSummary::before_crash(
+                                self.sleeping_duration,
+                                cycle.start_time.elapsed(),
+                                self.cycles,
+                                self.termination_signals,
+                                mode,
+                            ),
+                        );
+                    }
+                    crash::crash(mode);
+                }
+            }
+
+            // Measure again right before sleeping, such that the time spent in this cycle so far
+            // (heartbeats, status output, ...) does not add up to a drift over many cycles
+            let slept = cycle
+                .start_time
+                .elapsed()
+                .saturating_sub(cycle.pauses.paused());
+            if let Some(ref script) = self.script {
+                let due = script.due(cycle.next_action, slept);
+                cycle.next_action += due.len();
+                run_script_actions(&mut self.output, due);
+            }
+            if let Some(deadline) = self.wall_deadline {
+                let remaining = deadline
+                    .duration_since(time::SystemTime::now())
+                    .unwrap_or_default();
+                let expected = self.sleeping_duration.map(|d| d.saturating_sub(slept));
+                if expected.is_some_and(|expected| expected.abs_diff(remaining) > WALL_CLOCK_CHECK)
+                {
+                    self.output.print_verbose(&format!(
+                        "The wall clock jumped, {} remaining",
+                        self.output.format_duration(&remaining)
+                    ));
+                }
+                self.sleeping_duration = Some(slept + remaining);
+            }
+            // Sleep until the next thing is due. Signals wake up `signals::wait` early.
+            let mut nap = self.polling.then_some(self.cycle_time);
+            if self.wall_deadline.is_some() {
+                nap = earliest(nap, WALL_CLOCK_CHECK);
+            }
+            if let Some(sleeping_duration) = self.sleeping_duration {
+                if slept >= sleeping_duration {
+                    return Ok(WakeReason::Elapsed);
+                }
+                nap = earliest(nap, sleeping_duration - slept);
+            }
+            let due_after_start = [
+                self.script
+                    .as_ref()
+                    .and_then(|script| script.next_due(cycle.next_action)),
+                heartbeat_url.map(|_| cycle.next_heartbeat),
+                cycle.next_console_heartbeat,
+            ];
+            for due in due_after_start.into_iter().flatten() {
+                nap = earliest(nap, due.saturating_sub(slept));
+            }
+            let due_at = [
+                self.grace_end
+                    .flatten()
+                    .filter(|_| self.deferred_termination.is_some()),
+                self.crash_at.map(|(at, _)| at),
+                self.ready_at,
+                self.next_watchdog,
+                self.next_touch,
+            ];
+            for at in due_at.into_iter().flatten() {
+                nap = earliest(nap, at.saturating_duration_since(time::Instant::now()));
+            }
+            if let Some(ref mut countdown) = self.countdown {
+                nap = earliest(nap, progress::REDRAW_INTERVAL);
+                match self.sleeping_duration {
+                    Some(total) => countdown.draw(
+                        // '--countdown' only shows the remaining time, without a bar
+                        config
+                            .progress
+                            .then(|| slept.as_secs_f64() / total.as_secs_f64()),
+                        &format!(
+                            "{} remaining",
+                            self.output.format_duration(&total.saturating_sub(slept))
+                        ),
+                    ),
+                    None => countdown.draw(
+                        None,
+                        &format!("Sleeping for {}", self.output.format_duration(&slept)),
+                    ),
+                }
+            }
+            if let Some(nap) = nap {
+                cycle.pauses.expect(nap);
+            }
+            signals::wait(nap);
+            // Anything printed from here on starts on a clean line
+            if let Some(ref mut countdown) = self.countdown {
+                countdown.clear();
+            }
+
+            // The wait above can block for a long time, so `since_start` is stale by now
+            let dreamt = cycle
+                .start_time
+                .elapsed()
+                .saturating_sub(cycle.pauses.paused());
+            self.output.print_verbose(&format!(
+                "Still dreaming after {}{}",
+                self.output.format_duration(&dreamt),
+                progress_suffix(&dreamt, self.sleeping_duration.as_ref())
+            ));
+        }
+    }
+
+    /// Cleans up after the main loop and determines the exit code.
+    fn tear_down(self, wake_reason: WakeReason, elapsed: time::Duration) -> Result<ExitCode> {
+        let Session {
+            config,
+            mut output,
+            verbosity_level,
+            csv_output,
+            mut exit_code,
+            sleeping_duration,
+            pidfile,
+            touch_file,
+            control_exit_code,
+            notifier,
+            mut termination_signals,
+            lock,
+            tracked_group,
+            mut marker_sink,
+            mut countdown,
+            barrier,
+            file_watch,
+            key_listener,
+            memory,
+            busy_load,
+            idle_threads,
+            mut children,
+            child_group,
+            main_exit_code,
+            zombies,
+            script,
+            interrupted_by,
+            mut ignored_signals,
+            spammers,
+            disk_loads,
+            open_fds,
+            wake_signals,
+            accuracy_not_met,
+            overshoots,
+            heartbeat_latencies,
+            cycles,
+            ..
+        } = self;
+
+        // Unlike the bar of '--progress', the countdown line is kept
+        if let (Some(mut countdown), Some(total), true) =
+            (countdown.take(), sleeping_duration, config.countdown)
+        {
+            countdown.finish(&format!(
+                "{} remaining",
+                output.format_duration(&total.saturating_sub(elapsed))
+            ));
+        }
+        drop(spammers);
+        drop(disk_loads);
+
+        // When waiting for a condition, the duration acts as a timeout
+        let awaited_condition = if barrier.is_some() {
+            Some("at the barrier".to_string())
+        } else if let Some(ref file_watch) = file_watch {
+            Some(format!("for {}", file_watch.describe()))
+        } else if !config.wait_signals.is_empty() {
+            Some("for a signal".to_string())
+        } else {
+            None
+        };
+        let waiting_for_condition = awaited_condition.is_some();
+        let wake_reason = if waiting_for_condition && wake_reason == WakeReason::Elapsed {
+            WakeReason::TimedOut
+        } else {
+            wake_reason
+        };
+
+        if let Some(ref notifier) = notifier {
+            send_notification(&mut output, notifier, "STOPPING=1");
+        }
+
+        if let (WakeReason::Interrupted, Some(delay)) = (wake_reason, config.shutdown_delay) {
+            output.print(&format!(
+                "Shutting down... (this takes {})",
+                output.format_duration(&delay)
+            ));
+            let shutdown_start = time::Instant::now();
+            while shutdown_start.elapsed() < delay {
+                let received = signals::take_terminations();
+                termination_signals += received.len();
+                for termination in received {
+                    output.print(&format!(
+                        "Caught {} while shutting down - ignoring it.",
+                        termination
+                    ));
+                }
+                signals::wait(Some(delay.saturating_sub(shutdown_start.elapsed())));
+            }
+        }
+
+        if config.end_marker {
+            writeln!(marker_sink, "LUCID_END {}", epoch_nanos()).ok();
+        }
+
+        drop(busy_load);
+        drop(idle_threads);
+        if config.leak_rate.is_some() {
+            output.print_verbose(&format!(
+                "Allocated {} in total",
+                memory::size_as_str(memory.allocated())
+            ));
+        }
+
+        // Restores the terminal settings (there is nothing to restore on non-Unix platforms)
+        #[cfg_attr(not(unix), allow(clippy::drop_non_drop))]
+        drop(key_listener);
+
+        // Without a parent, there is nobody left to clean up. In init mode, the children already
+        // got the signals that interrupted lucid, so they are only terminated for other reasons.
+        let terminate = if config.init {
+            wake_reason != WakeReason::Interrupted
+        } else {
+            matches!(
+                wake_reason,
+                WakeReason::Interrupted | WakeReason::ParentDied
+            )
+        };
+        if let Some(group) = tracked_group.filter(|_| terminate) {
+            forward_termination(&mut output, group, None);
+        }
+        // The children already got the signals that interrupted lucid, but shutting down for any
+        // other reason has to reach the grandchildren in their process group as well
+        if let Some(group) = child_group {
+            if terminate && wake_reason != WakeReason::Interrupted {
+                let adopted = Some(&mut children).filter(|_| config.init);
+                forward_termination(&mut output, Pid::from_raw(group), adopted);
+            }
+        }
+
+        reap_children(&mut output, children, terminate && child_group.is_none());
+        if !zombies.is_empty() {
+            let reaped = zombies
+                .into_iter()
+                .filter(|&pid| platform::reap(pid))
+                .count();
+            output.print_verbose(&format!(
+                "Reaped {} zombie process{}",
+                reaped,
+                if reaped == 1 { "" } else { "es" }
+            ));
+        }
+
+        // The first error is only returned once everything is cleaned up
+        let mut error = accuracy_not_met.then_some(LucidError::AccuracyNotMet);
+
+        if let Some(ref path) = config.exit_code_file {
+            match fs::read_to_string(path)
+                .ok()
+                .and_then(|content| content.trim().parse::<ExitCode>().ok())
+            {
+                Some(code) => {
+                    exit_code = code;
+                    output.print_verbose(&format!(
+                        "Read exit code {} from '{}'",
+                        exit_code,
+                        path.display()
+                    ));
+                }
+                None => {
+                    error.get_or_insert(LucidError::ExitCodeFileError);
+                }
+            }
+        }
+
+        if let (WakeReason::Elapsed, Some(code)) =
+            (wake_reason, script.as_ref().and_then(Script::exit_code))
+        {
+            exit_code = code;
+        }
+
+        if let (WakeReason::Interrupted, Some(code)) = (wake_reason, config.interrupt_exit_code) {
+            exit_code = code;
+        }
+
+        if let Some(termination) = interrupted_by.filter(|_| config.signal_exit_codes) {
+            exit_code = termination.exit_code();
+            output.print(&format!(
+                "Exiting with code {} because of {}",
+                exit_code, termination
+            ));
+        }
+
+        if let Some(condition) = awaited_condition.filter(|_| wake_reason == WakeReason::TimedOut) {
+            output.print(&format!("Timed out while waiting {}.", condition));
+            exit_code = config.timeout_code;
+        }
+
+        if let Some(code) = control_exit_code {
+            exit_code = code;
+        }
+
+        if let Some(code) = main_exit_code {
+            exit_code = code;
+        }
+
+        if let WakeReason::Signal(signal) = wake_reason {
+            if let Some(&(_, Some(code))) = wake_signals.iter().find(|&&(s, _)| s == signal) {
+                exit_code = code;
+            }
+        }
+
+        let notification = if wake_reason == WakeReason::Interrupted {
+            config.on_interrupt_signal
+        } else {
+            config.on_wake_signal
+        };
+        if let Some((pid, signal)) = notification {
+            match signals::kill(pid, Some(signal)) {
+                Ok(()) => output.print_verbose(&format!("Sent {} to process {}", signal, pid)),
+                Err(err) => output.print(&format!(
+                    "Could not send {} to process {}: {}",
+                    signal, pid, err
+                )),
+            }
+        }
+
+        output.print_verbose(&format!("Main loop ran for {} cycles", cycles));
+        if config.no_interrupt && termination_signals > 0 {
+            output.print(&format!(
+                "Ignored {} termination signal{}.",
+                termination_signals,
+                if termination_signals == 1 { "" } else { "s" }
+            ));
+        }
+        output.print_event(
+            Event::Wake,
+            Some(elapsed),
+            &format!("Woke up after {}", output.format_duration(&elapsed)),
+        );
+        if config.measure {
+            output.print_always(&format!(
+                "Sleep overshoot: {}",
+                overshoots
+                    .describe()
+                    .unwrap_or_else(|| "not measured, the sleep did not elapse".into())
+            ));
+            if let Some(description) = heartbeat_latencies.describe() {
+                output.print_always(&format!("Heartbeat latency: {}", description));
+            }
+        }
+
+        let summary = RunSummary {
+            requested: sleeping_duration,
+            actual: elapsed,
+            cycles,
+            reason: wake_reason,
+            termination_signals,
+            signals: signals::received_counts(),
+            exit_code,
+        };
+
+        if csv_output && error.is_none() {
+            // Like other messages, the line is lost if stdout is closed
+            let mut stdout = io::stdout().lock();
+            if config.csv_header {
+                writeln!(stdout, "{}", RunSummary::CSV_HEADER).ok();
+            }
+            writeln!(stdout, "{}", summary.to_csv()).ok();
+        }
+
+        if let Some(path) = config.metrics_file.as_ref().filter(|_| error.is_none()) {
+            if Metrics(&summary).write_to(path).is_err() {
+                error.get_or_insert(LucidError::MetricsFileError);
+            }
+        }
+
+        if let Some(pidfile) = pidfile {
+            match pidfile.remove() {
+                // The PID file was typically created in a directory like /run, before switching
+                Err(_) if config.user.is_some() || config.group.is_some() => {
+                    output.print_verbose("Could not remove the PID file after switching users")
+                }
+                Err(err) => {
+                    error.get_or_insert(err);
+                }
+                Ok(()) => {}
+            }
+        }
+
+        if let Some(Err(err)) = touch_file.map(TouchFile::finish) {
+            error.get_or_insert(err);
+        }
+
+        drop(open_fds);
+
+        if let Some(lock) = lock {
+            drop(lock);
+            output.print_verbose("Released the lock");
+        }
+
+        if config.summary && error.is_none() {
+            print_summary(&mut output, config, verbosity_level, &summary);
+        }
+
+        output.flush();
+        if config.log_file.is_some() && output.write_failed() {
+            return Err(LucidError::LogFileError);
+        }
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        if let Some(argv) = config.then.as_ref().filter(|_| !config.init) {
+            if !matches!(
+                wake_reason,
+                WakeReason::Interrupted | WakeReason::TimedOut | WakeReason::ParentDied
+            ) {
+                output.print_verbose(&format!("Executing '{}'", argv.join(" ")));
+                if !config.forward_ignored {
+                    ignored_signals.clear();
+                } else if !ignored_signals.is_empty() {
+                    output.print_verbose(&format!(
+                        "Forwarding {} ignored signal{} to '{}'",
+                        ignored_signals.len(),
+                        if ignored_signals.len() == 1 { "" } else { "s" },
+                        argv[0]
+                    ));
+                }
+                output.flush();
+
+                let mut command = process::Command::new(&argv[0]);
+                command.args(&argv[1..]);
+                match platform::exec_forwarding(&mut command, &ignored_signals) {
+                    Ok(code) => return Ok(code),
+                    Err(err) => {
+                        output.print_verbose(&format!("Could not execute '{}': {}", argv[0], err));
+                        return Err(LucidError::FailedToRunCommand);
+                    }
+                }
+            }
+        }
+
+        Ok(exit_code)
+    }
+}
+
+/// A path in the temporary directory for the unit tests, removed again on drop
+#[cfg(test)]
+pub(crate) struct TempPath(PathBuf);
+
+#[cfg(test)]
+impl TempPath {
+    pub(crate) fn new(name: &str) -> TempPath {
+        let path = env::temp_dir().join(format!("lucid-test-{}-{}", name, process::id()));
+        fs::remove_file(&path).ok();
+        TempPath(path)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempPath {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<std::path::Path> for TempPath {
+    fn as_ref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        fs::remove_file(&self.0).ok();
+    }
+}
+
+#[test]
+fn test_duration_from_float() {
+    assert_eq!(Ok(time::Duration::from_secs(14)), duration_from_float(14.0));
+    assert_eq!(
+        Ok(time::Duration::from_micros(14_000_100)),
+        duration_from_float(14.0001)
+    );
+
+    assert_eq!(Ok(time::Duration::from_secs(0)), duration_from_float(0.0));
+
+    assert_eq!(
+        Ok(time::Duration::from_millis(12345)),
+        duration_from_float(12.345)
+    );
+    assert_eq!(
+        Ok(time::Duration::from_micros(12_345_400)),
+        duration_from_float(12.3454)
+    );
+    assert_eq!(
+        Ok(time::Duration::from_nanos(500_000)),
+        duration_from_float(0.0005)
+    );
+    assert_eq!(
+        Ok(time::Duration::from_nanos(1)),
+        duration_from_float(0.000000001)
+    );
+
+    assert_eq!(
+        Ok(time::Duration::from_millis(1)),
+        duration_from_float(0.001)
+    );
+    assert_eq!(
+        Ok(time::Duration::from_millis(1100)),
+        duration_from_float(1.1)
+    );
+
+    assert_eq!(Err(LucidError::DurationNegative), duration_from_float(-1.2));
+}
+
+#[test]
+fn test_duration_as_str() {
+    assert_eq!("0.000s", duration_as_str(&time::Duration::ZERO));
+    assert_eq!(
+        "1.500s",
+        duration_as_str(&time::Duration::from_millis(1500))
+    );
+    assert_eq!(
+        "1.000s",
+        duration_as_str(&time::Duration::from_nanos(1_000_000_500))
+    );
+    assert_eq!(
+        "0.000500s",
+        duration_as_str(&time::Duration::from_micros(500))
+    );
+    assert_eq!(
+        "0.000000250s",
+        duration_as_str(&time::Duration::from_nanos(250))
+    );
+}
+
+#[test]
+fn test_progress_suffix() {
+    let secs = time::Duration::from_secs_f64;
+    assert_eq!(" (32%)", progress_suffix(&secs(3.2), Some(&secs(10.0))));
+    assert_eq!(" (0%)", progress_suffix(&secs(0.0), Some(&secs(10.0))));
+    assert_eq!(" (99%)", progress_suffix(&secs(9.999), Some(&secs(10.0))));
+    assert_eq!(" (100%)", progress_suffix(&secs(10.5), Some(&secs(10.0))));
+    assert_eq!(" (100%)", progress_suffix(&secs(0.0), Some(&secs(0.0))));
+    assert_eq!("", progress_suffix(&secs(3.2), None));
+}
+
+#[test]
+fn test_json_string() {
+    assert_eq!("\"Going to sleep\"", json_string("Going to sleep"));
+    assert_eq!(
+        "\"a \\\"quoted\\\" \\\\ path\\n\\u0001\"",
+        json_string("a \"quoted\" \\ path\n\u{1}")
+    );
+}
+
+#[test]
+fn test_run() {
+    let config = Config {
+        sleep: Sleep::For(time::Duration::from_millis(50)),
+        exit_code: 3,
+        verbosity: VerbosityLevel::Quiet,
+        ..Config::default()
+    };
+    assert_eq!(Ok(3), run(config));
+}
+
+#[test]
+fn test_verbosity_level() {
+    assert!(VerbosityLevel::Normal > VerbosityLevel::Quiet);
+    assert!(VerbosityLevel::Verbose > VerbosityLevel::Normal);
+    assert!(VerbosityLevel::Verbose > VerbosityLevel::Quiet);
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(Ok(time::Duration::from_millis(1500)), parse_duration("1.5"));
+    assert_eq!(
+        Ok(time::Duration::from_millis(500)),
+        parse_duration("500ms")
+    );
+    assert_eq!(Ok(time::Duration::from_secs(90)), parse_duration("1.5m"));
+    assert_eq!(Ok(time::Duration::from_secs(7200)), parse_duration("2h"));
+    assert_eq!(Ok(time::Duration::from_secs(86400)), parse_duration("1d"));
+    assert_eq!(
+        Err(LucidError::DurationUnitUnknown("x".into())),
+        parse_duration("5x")
+    );
+    assert_eq!(
+        Err(LucidError::DurationUnitUnknown("sec".into())),
+        parse_duration("1m30sec")
+    );
+    assert_eq!(Err(LucidError::DurationParseError), parse_duration("1h30"));
+    assert_eq!(Err(LucidError::DurationParseError), parse_duration("s"));
+    assert_eq!(Err(LucidError::DurationNegative), parse_duration("-1s"));
+
+    assert_eq!(
+        Ok(time::Duration::from_secs(5415)),
+        parse_duration("1h30m15s")
+    );
+    assert_eq!(Ok(time::Duration::from_secs(150)), parse_duration("2m30s"));
+    assert_eq!(
+        Ok(time::Duration::from_nanos(1_500)),
+        parse_duration("1us500ns")
+    );
+    assert_eq!(
+        Ok(time::Duration::from_nanos(86_400_000_000_001)),
+        parse_duration("1d1ns")
+    );
+    assert_eq!(
+        Ok(time::Duration::from_millis(86_490_250)),
+        parse_duration("1d1m30s250ms")
+    );
+    assert_eq!(Err(LucidError::DurationUnitOrder), parse_duration("30m1h"));
+    assert_eq!(Err(LucidError::DurationUnitOrder), parse_duration("1m1m"));
+    assert_eq!(
+        Err(LucidError::DurationUnitOrder),
+        parse_duration("1s500ms1s")
+    );
+    assert_eq!(
+        Err(LucidError::DurationUnitUnknown("hm".into())),
+        parse_duration("1hm")
+    );
+    assert_eq!(
+        Err(LucidError::DurationParseError),
+        parse_duration("1h 30m")
+    );
+    assert_eq!(Err(LucidError::DurationParseError), parse_duration(""));
+}
@@ -0,0 +1,68 @@
+//! A dummy TCP service for port-based readiness checks, see `--listen`.
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::{thread, time};
+
+/// How long to wait for a client to send its request.
+const READ_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+/// Binds to `address` and accepts connections in a background thread until lucid exits.
+/// Connections are closed right away, or answered with `http_response` as the body of a
+/// `200 OK` response. Returns the bound address (useful with port 0).
+pub fn listen(address: &str, http_response: Option<String>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let local_addr = listener.local_addr()?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Some(ref body) = http_response {
+                respond(stream, body).ok();
+            }
+        }
+    });
+
+    Ok(local_addr)
+}
+
+fn respond(mut stream: TcpStream, body: &str) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    // Read (and discard) the request up to the end of its header
+    let mut request = vec![];
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 64 * 1024 {
+        match stream.read(&mut buffer)? {
+            0 => break,
+            n => request.extend_from_slice(&buffer[..n]),
+        }
+    }
+
+    stream.write_all(http_response(body).as_bytes())
+}
+
+fn http_response(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+#[test]
+fn test_http_response() {
+    assert_eq!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+        http_response("ok")
+    );
+
+    let address = listen("127.0.0.1:0", Some("hello".into())).unwrap();
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\nhello"));
+}
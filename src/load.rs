@@ -0,0 +1,121 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time;
+
+/// Length of a duty cycle: each thread spins for `load` of it and sleeps for the rest.
+const PERIOD: time::Duration = time::Duration::from_millis(100);
+
+/// Threads that keep CPUs busy, see `--busy`. They are stopped when this is dropped.
+pub struct BusyLoad {
+    stop: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl BusyLoad {
+    /// Starts `threads` threads that each spin for the fraction `load` (0.0 to 1.0) of the time.
+    pub fn start(threads: usize, load: f64, name: Option<&str>) -> Result<BusyLoad, SpawnError> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let busy_time = PERIOD.mul_f64(load.clamp(0.0, 1.0));
+        let mut busy_load = BusyLoad {
+            stop: stop.clone(),
+            threads: vec![],
+        };
+        // On failure, dropping `busy_load` stops the threads that were spawned
+        spawn_threads(&mut busy_load.threads, threads, name, || {
+            let stop = stop.clone();
+            move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let cycle_start = time::Instant::now();
+                    while cycle_start.elapsed() < busy_time {
+                        std::hint::spin_loop();
+                    }
+                    thread::sleep(PERIOD.saturating_sub(cycle_start.elapsed()));
+                }
+            }
+        })?;
+        Ok(busy_load)
+    }
+}
+
+impl Drop for BusyLoad {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Threads that do nothing until they are stopped when this is dropped, see `--threads`.
+pub struct IdleThreads {
+    stop: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl IdleThreads {
+    pub fn start(threads: usize, name: Option<&str>) -> Result<IdleThreads, SpawnError> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut idle_threads = IdleThreads {
+            stop: stop.clone(),
+            threads: vec![],
+        };
+        spawn_threads(&mut idle_threads.threads, threads, name, || {
+            let stop = stop.clone();
+            move || {
+                // Parking may return spuriously
+                while !stop.load(Ordering::Relaxed) {
+                    thread::park();
+                }
+            }
+        })?;
+        Ok(idle_threads)
+    }
+}
+
+impl Drop for IdleThreads {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            thread.thread().unpark();
+            thread.join().ok();
+        }
+    }
+}
+
+/// Spawning the thread with this (zero-based) index failed.
+#[derive(Debug)]
+pub struct SpawnError(pub usize, pub io::Error);
+
+/// Spawns `count` threads into `threads`, named `NAME-1` to `NAME-<count>` if a name is given.
+fn spawn_threads<F: FnOnce() + Send + 'static>(
+    threads: &mut Vec<thread::JoinHandle<()>>,
+    count: usize,
+    name: Option<&str>,
+    mut body: impl FnMut() -> F,
+) -> Result<(), SpawnError> {
+    for index in 0..count {
+        let mut builder = thread::Builder::new();
+        if let Some(name) = name {
+            builder = builder.name(format!("{}-{}", name, index + 1));
+        }
+        match builder.spawn(body()) {
+            Ok(thread) => threads.push(thread),
+            Err(err) => return Err(SpawnError(index, err)),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_idle_threads() {
+    let threads = IdleThreads::start(3, Some("idle")).unwrap();
+    let names: Vec<_> = threads
+        .threads
+        .iter()
+        .map(|thread| thread.thread().name().unwrap().to_string())
+        .collect();
+    assert_eq!(vec!["idle-1", "idle-2", "idle-3"], names);
+    drop(threads);
+}
@@ -0,0 +1,122 @@
+//! Holding a lock on a file while sleeping, see `--lock`.
+
+use std::fs::File;
+use std::path::Path;
+
+use crate::{LucidError, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockMode {
+    /// A BSD lock (`flock`) on the whole file.
+    Flock,
+    /// A POSIX record lock (`fcntl`) on the whole file.
+    Fcntl,
+}
+
+impl LockMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            LockMode::Flock => "flock",
+            LockMode::Fcntl => "fcntl",
+        }
+    }
+}
+
+/// An open file (created if necessary) that is locked via `try_acquire`. The lock is released
+/// when this is dropped.
+pub struct FileLock {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    file: File,
+    mode: LockMode,
+    shared: bool,
+}
+
+impl FileLock {
+    pub fn open(path: &Path, mode: LockMode, shared: bool) -> Result<FileLock> {
+        if !cfg!(unix) {
+            return Err(LucidError::Unsupported("--lock"));
+        }
+        // fcntl needs read access for a shared and write access for an exclusive lock
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|_| LucidError::LockError)?;
+        Ok(FileLock { file, mode, shared })
+    }
+
+    /// Tries to lock the file without blocking. Returns `false` if someone else holds a
+    /// conflicting lock.
+    #[cfg(unix)]
+    pub fn try_acquire(&self) -> Result<bool> {
+        use std::io;
+        use std::os::unix::io::AsRawFd;
+
+        let fd = self.file.as_raw_fd();
+        // SAFETY: `fd` is a valid file descriptor for as long as `self.file` lives, and
+        // `flock` is a plain C struct for which all-zero bytes are a valid value.
+        let result = unsafe {
+            match self.mode {
+                LockMode::Flock => {
+                    let operation = if self.shared {
+                        libc::LOCK_SH
+                    } else {
+                        libc::LOCK_EX
+                    };
+                    libc::flock(fd, operation | libc::LOCK_NB)
+                }
+                LockMode::Fcntl => {
+                    let mut lock: libc::flock = std::mem::zeroed();
+                    lock.l_type = if self.shared {
+                        libc::F_RDLCK
+                    } else {
+                        libc::F_WRLCK
+                    } as _;
+                    lock.l_whence = libc::SEEK_SET as _;
+                    libc::fcntl(fd, libc::F_SETLK, &lock)
+                }
+            }
+        };
+        if result == 0 {
+            return Ok(true);
+        }
+        match io::Error::last_os_error().raw_os_error() {
+            Some(libc::EAGAIN | libc::EACCES | libc::EINTR) => Ok(false),
+            _ => Err(LucidError::LockError),
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn try_acquire(&self) -> Result<bool> {
+        Err(LucidError::Unsupported("--lock"))
+    }
+
+    /// Describes the lock, e.g. `shared flock`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {}",
+            if self.shared { "shared" } else { "exclusive" },
+            self.mode.name()
+        )
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_flock_conflicts() {
+    let path = crate::TempPath::new("lock");
+
+    // flock locks conflict between open file descriptions, even within one process
+    let first = FileLock::open(&path, LockMode::Flock, false).unwrap();
+    let second = FileLock::open(&path, LockMode::Flock, true).unwrap();
+    assert_eq!(Ok(true), first.try_acquire());
+    assert_eq!(Ok(false), second.try_acquire());
+    drop(first);
+    assert_eq!(Ok(true), second.try_acquire());
+    assert_eq!("shared flock", second.describe());
+
+    let third = FileLock::open(&path, LockMode::Flock, true).unwrap();
+    assert_eq!(Ok(true), third.try_acquire());
+}
@@ -1,112 +1,746 @@
-use std::io::{self, Write};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+use std::process;
 use std::{thread, time};
 
-use clap::{crate_name, crate_version, AppSettings, Arg, Command};
+use clap::{crate_name, crate_version, AppSettings, Arg, ArgMatches, Command};
+use clap_complete::Shell;
 
-use nix::unistd;
+use lucid::script::Script;
+use lucid::settings::Settings;
+use lucid::template::{self, MessageTemplate};
+use lucid::timestamp::{self, Boundary, TimestampFormat};
+use lucid::{
+    parse_cpu_list, parse_duration, parse_size, signals, ColorChoice, Config, CrashMode,
+    DaemonOptions, ExitCode, FdTarget, FileCondition, Jitter, LockMode, LogTarget, LucidError,
+    OutputFormat, Result, Rlimit, Sleep, StdinMode,
+};
 
-#[derive(Debug, PartialEq)]
-enum LucidError {
-    DurationParseError,
-    DurationNegative,
-    FailedToDaemonize,
+/// Like `parse_duration`, but rejects bare numbers if `strict` is set (`--strict-duration`).
+fn parse_duration_checked(input: &str, strict: bool) -> Result<time::Duration> {
+    if strict && input.trim().parse::<f64>().is_ok() {
+        return Err(LucidError::DurationUnitMissing);
+    }
+    parse_duration(input)
 }
 
-impl LucidError {
-    fn message(&self) -> &str {
-        match self {
-            LucidError::DurationParseError => "Could not parse 'duration' argument",
-            LucidError::DurationNegative => "Duration can not be negative",
-            LucidError::FailedToDaemonize => "Failed to daemonize itself",
-        }
+/// Parses a duration given as the value of the option `name`, see `parse_duration_checked`.
+fn parse_duration_option(value: &str, name: &'static str, strict: bool) -> Result<time::Duration> {
+    parse_duration_checked(value, strict).map_err(|err| match err {
+        LucidError::DurationParseError => LucidError::InvalidArgument(name),
+        err => err,
+    })
+}
+
+/// Parses a load like `75%` or `75` into a fraction in (0, 1].
+fn parse_load(value: &str) -> Result<f64> {
+    let percent = value.trim().trim_end_matches('%').parse::<f64>();
+    match percent {
+        Ok(percent) if percent > 0.0 && percent <= 100.0 => Ok(percent / 100.0),
+        _ => Err(LucidError::InvalidArgument("--load")),
     }
 }
 
-/// Determines how much information should be printed.
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-enum VerbosityLevel {
-    Quiet,
-    Normal,
-    Verbose,
+/// Parses a jitter like `10%` (of the sleep duration) or `2s`.
+fn parse_jitter(value: &str, strict: bool) -> Result<Jitter> {
+    match value.trim().strip_suffix('%') {
+        Some(percent) => match percent.trim().parse::<f64>() {
+            Ok(percent) if (0.0..=100.0).contains(&percent) => {
+                Ok(Jitter::Relative(percent / 100.0))
+            }
+            _ => Err(LucidError::InvalidArgument("--jitter")),
+        },
+        None => parse_duration_option(value, "--jitter", strict).map(Jitter::Fixed),
+    }
 }
 
-type ExitCode = i32;
+/// Parses a rate of lines per second like `100` or `100/s`.
+fn parse_line_rate(value: &str, name: &'static str) -> Result<f64> {
+    match value.strip_suffix("/s").unwrap_or(value).parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        _ => Err(LucidError::InvalidArgument(name)),
+    }
+}
 
-struct OutputHandler<'a> {
-    stdout: io::StdoutLock<'a>,
-    stderr: io::StderrLock<'a>,
-    prefix: &'a str,
-    verbosity_level: VerbosityLevel,
-    print_to_stderr: bool,
+/// Parses `random` (any code between 0 and 255) or `random:MIN-MAX`. `None` for a plain exit
+/// code.
+fn parse_random_exit_code(value: &str) -> Result<Option<(ExitCode, ExitCode)>> {
+    let Some(range) = value.strip_prefix("random") else {
+        return Ok(None);
+    };
+    if range.is_empty() {
+        return Ok(Some((0, 255)));
+    }
+    let invalid = || LucidError::InvalidArgument("--exit-code");
+    let (min, max) = range
+        .strip_prefix(':')
+        .and_then(|range| range.split_once('-'))
+        .ok_or_else(invalid)?;
+    let min = parse_exit_code(min, "--exit-code")?;
+    let max = parse_exit_code(max, "--exit-code")?;
+    if min < 0 || min > max {
+        return Err(invalid());
+    }
+    Ok(Some((min, max)))
 }
 
-impl<'a> OutputHandler<'a> {
-    fn new(
-        stdout: io::StdoutLock<'a>,
-        stderr: io::StderrLock<'a>,
-        prefix: &'a str,
-        verbosity_level: VerbosityLevel,
-        print_to_stderr: bool,
-    ) -> Self {
-        OutputHandler {
-            stdout,
-            stderr,
-            prefix,
-            verbosity_level,
-            print_to_stderr,
+/// Parses an exit code given as the value of the option `name`.
+fn parse_exit_code(value: &str, name: &'static str) -> Result<ExitCode> {
+    value
+        .parse::<ExitCode>()
+        .map_err(|_| LucidError::InvalidArgument(name))
+}
+
+/// Options that name a resource only one process can own, see `without_exclusive_options`.
+const EXCLUSIVE_OPTIONS: [&str; 4] = ["--pidfile", "--pid-file", "--listen", "--control-socket"];
+
+/// Removes the `EXCLUSIVE_OPTIONS` (and their values) from `args`, such that children do not
+/// overwrite the PID file of their parent or fail to listen on the same port. Arguments after
+/// `--` are kept as they are.
+fn without_exclusive_options(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut args = args.into_iter();
+    let mut kept = vec![];
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            kept.push(arg);
+            kept.extend(args);
+            break;
+        }
+        match arg.split_once('=') {
+            _ if EXCLUSIVE_OPTIONS.contains(&arg.as_str()) => {
+                args.next();
+            }
+            Some((name, _)) if EXCLUSIVE_OPTIONS.contains(&name) => {}
+            _ => kept.push(arg),
         }
     }
+    kept
+}
 
-    fn print(&mut self, msg: &str) {
-        match self.verbosity_level {
-            VerbosityLevel::Verbose | VerbosityLevel::Normal => self.print_with_prefix(msg),
-            _ => {}
-        }
+/// Appends the index of a `--fork` instance to `path`, like to the prefix.
+fn instance_path(path: &str, fork_index: Option<&str>) -> PathBuf {
+    match fork_index {
+        Some(index) => PathBuf::from(format!("{}-{}", path, index)),
+        None => PathBuf::from(path),
     }
+}
 
-    fn print_verbose(&mut self, msg: &str) {
-        if self.verbosity_level == VerbosityLevel::Verbose {
-            self.print_with_prefix(msg)
-        }
+/// Moves the port of `address` by the index of a `--fork` instance minus one, such that the
+/// first instance listens on the given port. Port 0 (any free port) is kept.
+fn instance_address(address: &str, fork_index: Option<&str>) -> Result<String> {
+    let Some(index) = fork_index else {
+        return Ok(address.to_string());
+    };
+    let invalid = || LucidError::InvalidArgument("--listen");
+    let (host, port) = address.rsplit_once(':').ok_or_else(invalid)?;
+    let port = port.parse::<u16>().map_err(|_| invalid())?;
+    if port == 0 {
+        return Ok(address.to_string());
     }
+    let index = index.parse::<u16>().map_err(|_| invalid())?;
+    let port = port
+        .checked_add(index.saturating_sub(1))
+        .ok_or_else(invalid)?;
+    Ok(format!("{}:{}", host, port))
+}
+
+/// Translates the command line arguments (and the merged `settings`) into a `Config`.
+fn config_from_matches(matches: &ArgMatches, settings: Settings) -> Result<Config> {
+    let strict_duration = matches.is_present("strict-duration");
+    let duration_option = |id: &str, name: &'static str| {
+        matches
+            .value_of(id)
+            .map(|value| parse_duration_option(value, name, strict_duration))
+            .transpose()
+    };
+    let signal_target = |id: &str, name: &'static str| {
+        matches
+            .value_of(id)
+            .map(|spec| signals::parse_signal_target(spec).ok_or(LucidError::InvalidArgument(name)))
+            .transpose()
+    };
+
+    let sleep = if let Some(script) = matches.value_of("script") {
+        Sleep::Script(Script::parse(script)?)
+    } else if let Some(path) = matches.value_of("script-file") {
+        let script =
+            fs::read_to_string(path).map_err(|_| LucidError::InvalidArgument("--script-file"))?;
+        Sleep::Script(Script::parse(&script)?)
+    } else if let Some(unit) = matches.value_of("until-end-of") {
+        Sleep::UntilEndOf(match unit {
+            "minute" => Boundary::Minute,
+            "hour" => Boundary::Hour,
+            _ => Boundary::Day,
+        })
+    } else if let Some(until) = matches.value_of("until") {
+        Sleep::Until(timestamp::parse_deadline(until, time::SystemTime::now())?)
+    } else if let Some(mut timestamps) = matches.values_of("between") {
+        let start = timestamp::parse_timestamp(timestamps.next().unwrap())?;
+        let end = timestamp::parse_timestamp(timestamps.next().unwrap())?;
+        Sleep::Between(start, end)
+    } else if let Some(mut bounds) = matches.values_of("random") {
+        let min = parse_duration_option(bounds.next().unwrap(), "--random", strict_duration)?;
+        let max = parse_duration_option(bounds.next().unwrap(), "--random", strict_duration)?;
+        Sleep::Random(min, max)
+    } else if matches.is_present("stdin") {
+        let mut line = String::new();
+        io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .map_err(|_| LucidError::DurationParseError)?;
+        match line.trim() {
+            "" => Sleep::Forever,
+            duration => Sleep::For(parse_duration_checked(duration, strict_duration)?),
+        }
+    } else {
+        match matches.value_of("duration") {
+            None => Sleep::Forever,
+            Some(duration) => Sleep::For(parse_duration_checked(duration, strict_duration)?),
+        }
+    };
+
+    let timestamp_format = matches
+        .value_of("timestamp-format")
+        .or_else(|| {
+            matches
+                .is_present("timestamps")
+                .then_some(timestamp::SORTABLE)
+        })
+        .or(match matches.value_of("timestamp") {
+            Some("rfc3339") => Some(timestamp::RFC3339_MILLIS),
+            Some("unix") => Some("%s.%L"),
+            _ => None,
+        })
+        .map(TimestampFormat::parse)
+        .transpose()?;
+
+    let output_buffer_size = match matches.value_of("output-buffer-size") {
+        None => None,
+        Some(size) => Some(
+            size.parse::<usize>()
+                .ok()
+                .filter(|&size| size > 0)
+                .ok_or(LucidError::InvalidArgument("--output-buffer-size"))?,
+        ),
+    };
+
+    let poll_interval = matches
+        .value_of("poll-interval")
+        .map(|interval| interval.parse::<u64>().map(time::Duration::from_millis))
+        .transpose()
+        .map_err(|_| LucidError::InvalidArgument("--poll-interval"))?;
+
+    let barrier = match matches.value_of("barrier") {
+        None => None,
+        Some(path) => {
+            let count = matches
+                .value_of("barrier-count")
+                .and_then(|n| n.parse::<u64>().ok())
+                .ok_or(LucidError::InvalidArgument("--barrier-count"))?;
+            Some((PathBuf::from(path), count))
+        }
+    };
+
+    let wait_for = [
+        ("wait-for", FileCondition::Exists),
+        ("wait-for-gone", FileCondition::Gone),
+        ("wait-for-change", FileCondition::Changed),
+    ]
+    .iter()
+    .find_map(|&(id, condition)| {
+        matches
+            .value_of(id)
+            .map(|path| (PathBuf::from(path), condition))
+    });
+
+    let required_accuracy = match matches.value_of("require-accuracy") {
+        None => None,
+        Some(percent) => Some(
+            percent
+                .trim_end_matches('%')
+                .parse::<f64>()
+                .ok()
+                .filter(|p| *p >= 0.0)
+                .ok_or(LucidError::InvalidArgument("--require-accuracy"))?,
+        ),
+    };
+
+    // Children run with the same arguments, but one level less. The first `--depth` wins, so
+    // it can simply be prepended. The PID file, port and control socket stay with the parent.
+    let children = match matches.value_of("children") {
+        None => None,
+        Some(count) => {
+            let count = count
+                .parse::<usize>()
+                .map_err(|_| LucidError::InvalidArgument("--children"))?;
+            let depth = matches
+                .values_of("depth")
+                .and_then(|mut depths| depths.next())
+                .map_or(Ok(1), |depth| depth.parse::<usize>())
+                .map_err(|_| LucidError::InvalidArgument("--depth"))?;
+            (count > 0 && depth > 0).then(|| {
+                let mut args = vec!["--depth".to_string(), (depth - 1).to_string()];
+                args.extend(without_exclusive_options(env::args().skip(1)));
+                (count, args)
+            })
+        }
+    };
+
+    // Instances of `--fork` run with the same arguments and get their index via the
+    // environment, which is inherited by their own children as well. Each instance gets its
+    // own PID file, port and control socket.
+    let fork_index = env::var(lucid::FORK_INDEX_VARIABLE).ok();
+    let fork = match matches.value_of("fork").filter(|_| fork_index.is_none()) {
+        None => None,
+        Some(count) => match count.parse::<usize>() {
+            Ok(count) if count > 0 => Some((count, env::args().skip(1).collect())),
+            _ => return Err(LucidError::InvalidArgument("--fork")),
+        },
+    };
+
+    let crash = match duration_option("crash-after", "--crash-after")? {
+        None => None,
+        Some(after) => {
+            let mode = match matches.value_of("crash-mode") {
+                Some("segfault") => CrashMode::Segfault,
+                Some("panic") => CrashMode::Panic,
+                Some("oom") => CrashMode::OutOfMemory,
+                Some("core") => CrashMode::Core,
+                _ => CrashMode::Abort,
+            };
+            Some((after, mode))
+        }
+    };
+
+    let format = match matches.value_of("format") {
+        Some("csv") => OutputFormat::Csv,
+        Some("json") => OutputFormat::Json,
+        _ => OutputFormat::Human,
+    };
 
-    fn print_with_prefix(&mut self, msg: &str) {
-        let mut handle: Box<dyn Write> = if self.print_to_stderr {
-            Box::new(&mut self.stderr)
+    Ok(Config {
+        sleep,
+        clamp_negative: matches.is_present("clamp-negative"),
+        busy: if matches.is_present("busy") {
+            let threads = match matches.value_of("cpus").or(matches.value_of("threads")) {
+                None => thread::available_parallelism().map_or(1, |n| n.get()),
+                Some(n) => n
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or(LucidError::InvalidArgument("--cpus"))?,
+            };
+            Some((threads, parse_load(matches.value_of("load").unwrap())?))
         } else {
-            Box::new(&mut self.stdout)
-        };
-        writeln!(handle, "[{}]: {}", self.prefix, msg).ok();
-    }
+            None
+        },
+        threads: match matches.value_of("threads") {
+            Some(_) if matches.is_present("busy") => 0,
+            Some(n) => n
+                .parse::<usize>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or(LucidError::InvalidArgument("--threads"))?,
+            None => 0,
+        },
+        thread_name: matches.value_of("thread-name").map(String::from),
+        memory: matches.value_of("memory").map(parse_size).transpose()?,
+        leak_rate: matches
+            .value_of("leak-rate")
+            .map(|rate| parse_size(rate.strip_suffix("/s").unwrap_or(rate)))
+            .transpose()?,
+        disk_write: match matches.value_of("write-rate") {
+            Some(rate) => Some((
+                PathBuf::from(matches.value_of("write-file").unwrap()),
+                parse_size(rate.strip_suffix("/s").unwrap_or(rate))?,
+            )),
+            None => None,
+        },
+        disk_read: match matches.value_of("read-rate") {
+            Some(rate) => Some((
+                PathBuf::from(matches.value_of("read-file").unwrap()),
+                parse_size(rate.strip_suffix("/s").unwrap_or(rate))?,
+            )),
+            None => None,
+        },
+        fsync: matches.is_present("fsync"),
+        spam_stdout: matches
+            .value_of("spam-stdout")
+            .map(|rate| parse_line_rate(rate, "--spam-stdout"))
+            .transpose()?,
+        spam_stderr: matches
+            .value_of("spam-stderr")
+            .map(|rate| parse_line_rate(rate, "--spam-stderr"))
+            .transpose()?,
+        stdin_mode: match matches.value_of("stdin-mode") {
+            None => StdinMode::Ignore,
+            Some(mode) => {
+                StdinMode::parse(mode).ok_or(LucidError::InvalidArgument("--stdin-mode"))?
+            }
+        },
+        rlimits: match matches.values_of("rlimit") {
+            Some(lists) => lists
+                .map(Rlimit::parse_list)
+                .collect::<Result<Vec<_>>>()?
+                .concat(),
+            None => vec![],
+        },
+        nice: matches
+            .value_of("nice")
+            .map(|n| n.parse::<i32>())
+            .transpose()
+            .map_err(|_| LucidError::InvalidArgument("--nice"))?,
+        cpu_affinity: matches
+            .value_of("cpu-affinity")
+            .map(parse_cpu_list)
+            .transpose()?,
+        open_fds: matches
+            .value_of("open-fds")
+            .map(|n| n.parse::<usize>())
+            .transpose()
+            .map_err(|_| LucidError::InvalidArgument("--open-fds"))?
+            .unwrap_or(0),
+        fd_target: FdTarget::parse(matches.value_of("fd-target").unwrap_or("/dev/null")),
+        lock: matches.value_of("lock").map(PathBuf::from),
+        lock_mode: match matches.value_of("lock-mode") {
+            Some("fcntl") => LockMode::Fcntl,
+            _ => LockMode::Flock,
+        },
+        lock_shared: matches.is_present("lock-shared"),
+        line_length: matches
+            .value_of("line-length")
+            .map(|n| n.parse::<usize>())
+            .transpose()
+            .map_err(|_| LucidError::InvalidArgument("--line-length"))?
+            .unwrap_or(80),
+        max_duration: duration_option("max-duration", "--max-duration")?,
+        grace: duration_option("grace", "--grace")?,
+        die_after_signals: matches
+            .value_of("die-after-signals")
+            .map(|n| n.parse::<usize>().ok().filter(|&n| n > 0))
+            .map(|n| n.ok_or(LucidError::InvalidArgument("--die-after-signals")))
+            .transpose()?,
+        shutdown_delay: duration_option("shutdown-delay", "--shutdown-delay")?,
+        crash,
+        children,
+        fork,
+        notify: matches.is_present("notify"),
+        listen: matches
+            .value_of("listen")
+            .map(|address| instance_address(address, fork_index.as_deref()))
+            .transpose()?,
+        http_response: matches.value_of("http-response").map(String::from),
+        ready_after: duration_option("ready-after", "--ready-after")?,
+        zombies: matches
+            .value_of("zombies")
+            .map(|n| n.parse::<usize>())
+            .transpose()
+            .map_err(|_| LucidError::InvalidArgument("--zombies"))?
+            .unwrap_or(0),
+        init: matches.is_present("init"),
+        repeat: matches
+            .value_of("repeat")
+            .map(|n| n.parse::<u32>())
+            .transpose()
+            .map_err(|_| LucidError::InvalidArgument("--repeat"))?
+            .unwrap_or(0),
+        repeat_forever: matches.is_present("forever"),
+        exit_code: settings.exit_code.value,
+        exit_code_file: matches.value_of("exit-code-file").map(PathBuf::from),
+        random_exit_code: parse_random_exit_code(matches.value_of("exit-code").unwrap())?,
+        fail_probability: matches
+            .value_of("fail-probability")
+            .map(|value| match value.parse::<f64>() {
+                Ok(probability) if (0.0..=1.0).contains(&probability) => Ok(probability),
+                _ => Err(LucidError::InvalidArgument("--fail-probability")),
+            })
+            .transpose()?,
+        interrupt_exit_code: matches
+            .value_of("interrupt-exit-code")
+            .map(|code| parse_exit_code(code, "--interrupt-exit-code"))
+            .transpose()?,
+        signal_exit_codes: matches.is_present("signal-exit-codes"),
+        timeout_code: parse_exit_code(matches.value_of("timeout-code").unwrap(), "--timeout-code")?,
+        daemon: matches.is_present("daemon"),
+        daemon_options: DaemonOptions {
+            chdir: matches.value_of("chdir").map(PathBuf::from),
+            umask: matches
+                .value_of("umask")
+                .map(|mask| {
+                    u32::from_str_radix(mask, 8)
+                        .ok()
+                        .filter(|&mask| mask <= 0o777)
+                })
+                .map(|mask| mask.ok_or(LucidError::InvalidArgument("--umask")))
+                .transpose()?,
+            stdin: matches.value_of("daemon-stdin").map(PathBuf::from),
+            stdout: matches.value_of("daemon-stdout").map(PathBuf::from),
+            stderr: matches.value_of("daemon-stderr").map(PathBuf::from),
+        },
+        orphan: matches.is_present("orphan"),
+        die_with_parent: matches.is_present("die-with-parent"),
+        pidfile: matches
+            .value_of("pidfile")
+            .map(|path| instance_path(path, fork_index.as_deref())),
+        user: matches.value_of("user").map(String::from),
+        group: matches.value_of("group").map(String::from),
+        touch: matches.value_of("touch").map(PathBuf::from),
+        touch_interval: duration_option("touch-interval", "--touch-interval")?
+            .unwrap_or(time::Duration::from_secs(1)),
+        touch_final: matches.value_of("touch-final").map(String::from),
+        control_socket: matches
+            .value_of("control-socket")
+            .map(|path| instance_path(path, fork_index.as_deref())),
+        no_interrupt: settings.no_interrupt.value,
+        prefix: match fork_index {
+            Some(index) if !settings.prefix.value.contains("{index}") => {
+                format!("{}-{}", settings.prefix.value, index)
+            }
+            _ => settings.prefix.value.clone(),
+        },
+        verbosity: settings.verbosity.value,
+        stderr: settings.stderr.value,
+        pre_hook: matches.value_of("pre-hook").map(String::from),
+        forward_signals: matches.is_present("forward-signals"),
+        then: matches
+            .values_of("command")
+            .map(|command| command.map(String::from).collect()),
+        forward_ignored: matches.is_present("forward-ignored"),
+        random_start_delay: duration_option("random-start-delay", "--random-start-delay")?,
+        jitter: matches
+            .value_of("jitter")
+            .map(|value| parse_jitter(value, strict_duration))
+            .transpose()?,
+        seed: matches
+            .value_of("seed")
+            .map(|seed| seed.parse::<u64>())
+            .transpose()
+            .map_err(|_| LucidError::InvalidArgument("--seed"))?,
+        report_granularity: matches.is_present("report-granularity"),
+        poll_interval,
+        barrier,
+        wait_for,
+        wait_signals: matches
+            .value_of("wait-signal")
+            .map(signals::parse_signal_list)
+            .transpose()?
+            .unwrap_or_default(),
+        exit_on_signals: matches
+            .value_of("exit-on")
+            .map(signals::parse_signal_list)
+            .transpose()?
+            .unwrap_or_default(),
+        dump_core_on: matches
+            .value_of("dump-core-on")
+            .map(|name| signals::parse_signal(name).ok_or(LucidError::InvalidSignal))
+            .transpose()?,
+        ignore_signals: matches
+            .value_of("ignore")
+            .map(signals::parse_signal_names)
+            .transpose()?
+            .unwrap_or_default(),
+        on_wake_signal: signal_target("on-wake-signal", "--on-wake-signal")?,
+        on_interrupt_signal: signal_target("on-interrupt-signal", "--on-interrupt-signal")?,
+        required_accuracy,
+        measure: matches.is_present("measure"),
+        max_retries: matches
+            .value_of("max-retries")
+            .and_then(|n| n.parse::<u32>().ok())
+            .ok_or(LucidError::InvalidArgument("--max-retries"))?,
+        any_key: matches.is_present("any-key"),
+        start_marker: matches.is_present("start-marker"),
+        end_marker: matches.is_present("end-marker"),
+        marker_fd: matches
+            .value_of("marker-fd")
+            .map(|fd| fd.parse::<i32>())
+            .transpose()
+            .map_err(|_| LucidError::InvalidArgument("--marker-fd"))?,
+        heartbeat: duration_option("heartbeat", "--heartbeat")?,
+        progress: matches.is_present("progress"),
+        countdown: matches.is_present("countdown"),
+        extend: duration_option("extend", "--extend")?,
+        reload_on_hup: matches.is_present("reload-on-hup"),
+        script_file: matches.value_of("script-file").map(PathBuf::from),
+        exclude_paused: matches.is_present("exclude-paused"),
+        report_stops: matches.is_present("report-stops"),
+        heartbeat_url: matches.value_of("heartbeat-url").map(String::from),
+        heartbeat_interval: duration_option("heartbeat-interval", "--heartbeat-interval")?
+            .unwrap_or_default(),
+        metrics_file: matches.value_of("metrics-file").map(PathBuf::from),
+        format,
+        color: color_choice(matches),
+        log_targets: matches
+            .value_of("log-target")
+            .map_or(Ok(vec![LogTarget::Stdout]), LogTarget::parse_list)?,
+        csv_header: matches.is_present("csv-header"),
+        summary: matches.is_present("summary"),
+        timestamp_format,
+        elapsed_timestamps: matches.value_of("timestamp") == Some("elapsed"),
+        message_template: MessageTemplate::parse(
+            matches
+                .value_of("message-format")
+                .unwrap_or(template::DEFAULT),
+        )?,
+        output_buffer_size,
+        log_file: matches.value_of("log-file").map(PathBuf::from),
+        log_append: matches.is_present("append"),
+        whole_seconds: matches.is_present("no-subsecond"),
+        verbose_env: matches.is_present("verbose-env").then_some(settings),
+        dump_state: matches.is_present("dump-state"),
+    })
 }
 
-type Result<T> = std::result::Result<T, LucidError>;
+/// The `--color` choice, which also applies to the error that lucid exits with.
+fn color_choice(matches: &ArgMatches) -> ColorChoice {
+    match matches.value_of("color") {
+        Some("always") => ColorChoice::Always,
+        Some("never") => ColorChoice::Never,
+        _ => ColorChoice::Auto,
+    }
+}
 
-fn duration_as_str(duration: &time::Duration) -> String {
-    format!("{}.{:03}s", duration.as_secs(), duration.subsec_millis())
+/// Writes shell completions (`completions SHELL`) or a man page (`man`) to stdout, see
+/// `--generate`.
+fn generate(what: &[&str]) -> Result<()> {
+    let mut command = build_command();
+    let mut stdout = io::stdout();
+    match what {
+        ["completions", shell] => {
+            let shell = shell
+                .parse::<Shell>()
+                .map_err(|_| LucidError::InvalidArgument("--generate"))?;
+            clap_complete::generate(shell, &mut command, crate_name!(), &mut stdout);
+        }
+        ["man"] => {
+            // Fails only if stdout is closed, in which case nobody is interested anyway
+            clap_mangen::Man::new(command).render(&mut stdout).ok();
+        }
+        _ => return Err(LucidError::InvalidArgument("--generate")),
+    }
+    Ok(())
 }
 
-fn duration_from_float(duration_sec: f64) -> Result<time::Duration> {
-    if duration_sec < 0.0 {
-        return Err(LucidError::DurationNegative);
+fn run(matches: &ArgMatches) -> Result<ExitCode> {
+    if let Some(what) = matches.values_of("generate") {
+        generate(&what.collect::<Vec<_>>())?;
+        return Ok(0);
     }
 
-    let secs = duration_sec.floor() as u64;
-    let millisecs = ((duration_sec - secs as f64) * 1e3).round() as u64;
+    let settings = Settings::load(matches)?;
 
-    Ok(time::Duration::from_millis(secs * 1000 + millisecs))
+    if matches.is_present("print-config") {
+        for (key, value, source) in settings.describe() {
+            println!("{} = {} ({})", key, value, source);
+        }
+        return Ok(0);
+    }
+
+    lucid::run(config_from_matches(matches, settings)?)
 }
 
-fn run() -> Result<ExitCode> {
-    let app = Command::new(crate_name!())
+fn build_command() -> Command<'static> {
+    Command::new(crate_name!())
         .setting(AppSettings::DeriveDisplayOrder)
         .version(crate_version!())
         .arg(Arg::new("duration").help(
-            "Sleep time in seconds. If no duration is given, \
-             the process will sleep forever.",
+            "Sleep time, e.g. '1.5', '500ms' or '1h30m'. Numbers without a unit are seconds. \
+             If no duration is given, the process will sleep forever.",
+        ))
+        .arg(
+            Arg::new("script")
+                .long("script")
+                .takes_value(true)
+                .value_name("SCRIPT")
+                .conflicts_with_all(&["duration", "script-file"])
+                .help(
+                    "Follow a timeline instead of sleeping, e.g. 'sleep 2; print \"ready\"; \
+                     stderr \"warning\"; sleep 3; exit 7'",
+                ),
+        )
+        .arg(
+            Arg::new("script-file")
+                .long("script-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with("duration")
+                .help("Read the '--script' timeline from FILE (one step per line)"),
+        )
+        .arg(Arg::new("strict-duration").long("strict-duration").help(
+            "Require an explicit unit ('ns', 'us', 'ms', 's', 'm', 'h' or 'd') for all \
+             durations, such that a bare '5' is rejected in favor of '5s'",
         ))
+        .arg(
+            Arg::new("between")
+                .long("between")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["T1", "T2"])
+                .conflicts_with("duration")
+                .help(
+                    "Sleep for the time span between two timestamps, given as seconds \
+                     since the epoch or in RFC 3339 format",
+                ),
+        )
+        .arg(
+            Arg::new("clamp-negative")
+                .long("clamp-negative")
+                .requires("between")
+                .help("Sleep zero seconds instead of failing if T2 lies before T1"),
+        )
+        .arg(
+            Arg::new("until-end-of")
+                .long("until-end-of")
+                .takes_value(true)
+                .value_name("UNIT")
+                .possible_values(["minute", "hour", "day"])
+                .conflicts_with_all(&["duration", "between"])
+                .help("Sleep until the start of the next minute, hour or day (local time)"),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .takes_value(true)
+                .value_name("TIME")
+                .conflicts_with_all(&["duration", "between", "until-end-of"])
+                .help(
+                    "Sleep until the given wall-clock time, e.g. '14:30' (the next occurrence, \
+                     today or tomorrow), '2024-01-01T09:00:00' (local time) or an RFC 3339 \
+                     timestamp",
+                ),
+        )
+        .arg(
+            Arg::new("random")
+                .long("random")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["MIN", "MAX"])
+                .conflicts_with_all(&["duration", "between", "until-end-of", "until"])
+                .help("Sleep for a random duration between MIN and MAX (see '--seed')"),
+        )
+        .arg(
+            Arg::new("stdin")
+                .long("stdin")
+                .conflicts_with_all(&["duration", "between", "until-end-of", "until", "random"])
+                .help(
+                    "Read the duration from the first line of stdin. An empty line means \
+                     sleeping forever",
+                ),
+        )
+        .arg(
+            Arg::new("stdin-mode")
+                .long("stdin-mode")
+                .takes_value(true)
+                .value_name("MODE")
+                .possible_values(["ignore", "consume", "echo", "close"])
+                .conflicts_with("any-key")
+                .help(
+                    "What to do with stdin while sleeping: never read it (the default), \
+                     consume and discard it, echo every line (as a message) or close it. \
+                     Applies to what is left after '--stdin'",
+                ),
+        )
         .arg(
             Arg::new("ignored")
                 .help("Additional arguments are ignored")
@@ -121,7 +755,29 @@ fn run() -> Result<ExitCode> {
                 .value_name("CODE")
                 .allow_hyphen_values(true)
                 .default_value("0")
-                .help("Terminate with the given exit code"),
+                .help(
+                    "Terminate with the given exit code, or with a random one for \
+                     'random[:MIN-MAX]' (between 0 and 255 by default, see '--seed')",
+                ),
+        )
+        .arg(
+            Arg::new("fail-probability")
+                .long("fail-probability")
+                .takes_value(true)
+                .value_name("P")
+                .conflicts_with("exit-code-file")
+                .help(
+                    "Fail with probability P (between 0 and 1) and exit with 0 otherwise. \
+                     Failures use the exit code, or 1 if that is zero",
+                ),
+        )
+        .arg(
+            Arg::new("exit-code-file")
+                .long("exit-code-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .conflicts_with("exit-code")
+                .help("Terminate with the exit code read from PATH after waking up"),
         )
         .arg(
             Arg::new("daemon")
@@ -129,6 +785,199 @@ fn run() -> Result<ExitCode> {
                 .short('d')
                 .help("Daemonize the process after launching"),
         )
+        .arg(
+            Arg::new("chdir")
+                .long("chdir")
+                .takes_value(true)
+                .value_name("DIR")
+                .requires("daemon")
+                .help(
+                    "Change the working directory of the daemon to DIR. Relative '--pidfile' \
+                     and '--log-file' paths are resolved from there",
+                ),
+        )
+        .arg(
+            Arg::new("umask")
+                .long("umask")
+                .takes_value(true)
+                .value_name("MASK")
+                .requires("daemon")
+                .help("Set the file mode creation mask of the daemon (octal, e.g. 022)"),
+        )
+        .arg(
+            Arg::new("daemon-stdin")
+                .long("daemon-stdin")
+                .takes_value(true)
+                .value_name("PATH")
+                .requires("daemon")
+                .help("Redirect the standard input of the daemon from PATH (e.g. /dev/null)"),
+        )
+        .arg(
+            Arg::new("daemon-stdout")
+                .long("daemon-stdout")
+                .takes_value(true)
+                .value_name("PATH")
+                .requires("daemon")
+                .help("Redirect the standard output of the daemon to PATH (appending to it)"),
+        )
+        .arg(
+            Arg::new("daemon-stderr")
+                .long("daemon-stderr")
+                .takes_value(true)
+                .value_name("PATH")
+                .requires("daemon")
+                .help("Redirect the standard error of the daemon to PATH (appending to it)"),
+        )
+        .arg(Arg::new("die-with-parent").long("die-with-parent").help(
+            "Exit as soon as the parent process exits, via PR_SET_PDEATHSIG on Linux \
+             (Unix only)",
+        ))
+        .arg(
+            Arg::new("orphan")
+                .long("orphan")
+                .conflicts_with("daemon")
+                .help(
+                    "Continue sleeping in a forked child process while the original one \
+                     exits, so that the child gets reparented (Unix only)",
+                ),
+        )
+        .arg(
+            Arg::new("user")
+                .long("user")
+                .takes_value(true)
+                .value_name("NAME|UID")
+                .help(
+                    "Switch to this user once the PID file, '--listen' socket etc. are set up \
+                     (Unix only)",
+                ),
+        )
+        .arg(
+            Arg::new("group")
+                .long("group")
+                .takes_value(true)
+                .value_name("NAME|GID")
+                .help("Switch to this group, see '--user' [default: the user's primary group]"),
+        )
+        .arg(
+            Arg::new("pidfile")
+                .long("pidfile")
+                .visible_alias("pid-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .help(
+                    "Write the PID to PATH (after daemonizing) and remove the file again \
+                     before exiting",
+                ),
+        )
+        .arg(
+            Arg::new("rlimit")
+                .long("rlimit")
+                .takes_value(true)
+                .value_name("LIMITS")
+                .multiple_occurrences(true)
+                .help(
+                    "Apply resource limits to lucid itself at startup, e.g. \
+                     'NOFILE=64,CPU=2,AS=256M' (soft limits, append ':HARD' to also set the hard \
+                     limit). Supported: CPU, FSIZE, DATA, STACK, CORE, NOFILE, AS (Unix only)",
+                ),
+        )
+        .arg(
+            Arg::new("nice")
+                .long("nice")
+                .takes_value(true)
+                .value_name("N")
+                .allow_hyphen_values(true)
+                .help("Set the nice value of lucid to N at startup (Unix only)"),
+        )
+        .arg(
+            Arg::new("cpu-affinity")
+                .long("cpu-affinity")
+                .takes_value(true)
+                .value_name("CPUS")
+                .help("Restrict lucid to these CPUs at startup, e.g. '0,2-3' (Linux only)"),
+        )
+        .arg(
+            Arg::new("open-fds")
+                .long("open-fds")
+                .takes_value(true)
+                .value_name("N")
+                .help("Open N file descriptors (see '--fd-target') and hold them while sleeping"),
+        )
+        .arg(
+            Arg::new("fd-target")
+                .long("fd-target")
+                .takes_value(true)
+                .value_name("PATH|pipe")
+                .requires("open-fds")
+                .help(
+                    "What the '--open-fds' descriptors refer to: a file that is opened for \
+                     reading or both ends of pipes (Unix only) [default: /dev/null]",
+                ),
+        )
+        .arg(
+            Arg::new("lock")
+                .long("lock")
+                .takes_value(true)
+                .value_name("PATH")
+                .help(
+                    "Lock PATH (creating it if necessary) before sleeping and hold the lock \
+                     until exiting. Waits while someone else holds it (Unix only)",
+                ),
+        )
+        .arg(
+            Arg::new("lock-mode")
+                .long("lock-mode")
+                .takes_value(true)
+                .value_name("MODE")
+                .possible_values(["flock", "fcntl"])
+                .requires("lock")
+                .help(
+                    "Whether '--lock' uses a BSD lock (flock) or a POSIX record lock (fcntl) \
+                     [default: flock]",
+                ),
+        )
+        .arg(
+            Arg::new("lock-shared")
+                .long("lock-shared")
+                .requires("lock")
+                .help("Acquire a shared lock with '--lock', instead of an exclusive one"),
+        )
+        .arg(
+            Arg::new("touch")
+                .long("touch")
+                .takes_value(true)
+                .value_name("PATH")
+                .help(
+                    "Update the modification time of PATH (creating it if necessary) while \
+                     sleeping, and remove it again before exiting",
+                ),
+        )
+        .arg(
+            Arg::new("touch-interval")
+                .long("touch-interval")
+                .takes_value(true)
+                .value_name("DURATION")
+                .requires("touch")
+                .help("How often to update the '--touch' file [default: 1s]"),
+        )
+        .arg(
+            Arg::new("touch-final")
+                .long("touch-final")
+                .takes_value(true)
+                .value_name("MARKER")
+                .requires("touch")
+                .help("Write MARKER to the '--touch' file before exiting, instead of removing it"),
+        )
+        .arg(
+            Arg::new("control-socket")
+                .long("control-socket")
+                .takes_value(true)
+                .value_name("PATH")
+                .help(
+                    "Accept the commands STATUS, WAKE, EXIT <code> and SET-VERBOSITY <level> \
+                     (one per line) on a Unix socket at PATH (Unix only)",
+                ),
+        )
         .arg(
             Arg::new("no-interrupt")
                 .long("no-interrupt")
@@ -142,7 +991,755 @@ fn run() -> Result<ExitCode> {
                 .takes_value(true)
                 .value_name("PREFIX")
                 .default_value("lucid")
-                .help("Prefix all messages with the given string"),
+                .help(
+                    "Prefix all messages with the given string. The placeholders {pid}, {ppid}, \
+                     {index} (of a '--fork' instance) and {time} are expanded for every message",
+                ),
+        )
+        .arg(
+            Arg::new("pre-hook")
+                .long("pre-hook")
+                .takes_value(true)
+                .value_name("CMD")
+                .help(
+                    "Run the given shell command before going to sleep. If it fails, \
+                     terminate with its exit code",
+                ),
+        )
+        .arg(Arg::new("then").long("then").requires("command").help(
+            "After waking up, replace this process with the command given after '--'. \
+             The command is not run if the sleep was interrupted or timed out",
+        ))
+        .arg(
+            Arg::new("forward-ignored")
+                .long("forward-ignored")
+                .requires("then")
+                .help(
+                    "Send the signals that were ignored during the sleep (see '--ignore' and \
+                     '--no-interrupt') to the '--then' command once it has started (Unix only)",
+                ),
+        )
+        .arg(
+            Arg::new("command")
+                .value_name("COMMAND")
+                .multiple_values(true)
+                .last(true)
+                .requires("then")
+                .help("The command for '--then'"),
+        )
+        .arg(Arg::new("forward-signals").long("forward-signals").help(
+            "Run the pre-hook in its own process group and forward termination \
+             signals to it (as SIGTERM). Waits up to one second for the \
+             processes in that group to exit. The '--children', the '--init' \
+             command and the '--fork' instances share another process group, to \
+             which SIGINT, SIGTERM, SIGHUP, SIGUSR1 and SIGUSR2 are re-sent as \
+             they arrive (Unix only)",
+        ))
+        .arg(
+            Arg::new("random-start-delay")
+                .long("random-start-delay")
+                .takes_value(true)
+                .value_name("DURATION")
+                .help("Delay the start by a random time between zero and DURATION"),
+        )
+        .arg(
+            Arg::new("jitter")
+                .long("jitter")
+                .takes_value(true)
+                .value_name("DURATION|PERCENT")
+                .help(
+                    "Randomly lengthen or shorten the sleep by up to DURATION, or by up to \
+                     PERCENT of it (e.g. '10%')",
+                ),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .takes_value(true)
+                .value_name("N")
+                .help("Seed for the random number generator, to make random choices reproducible"),
+        )
+        .arg(
+            Arg::new("poll-interval")
+                .long("poll-interval")
+                .takes_value(true)
+                .value_name("MS")
+                .help(
+                    "Check for signals and other wake-up conditions every MS milliseconds \
+                     [default: 100]. Must not exceed the sleep duration",
+                ),
+        )
+        .arg(
+            Arg::new("report-granularity")
+                .long("report-granularity")
+                .help("Report the observed sleep granularity of the platform before sleeping"),
+        )
+        .arg(
+            Arg::new("barrier")
+                .long("barrier")
+                .takes_value(true)
+                .value_name("FILE")
+                .requires("barrier-count")
+                .help(
+                    "Wait until the given number of processes arrived at the barrier FILE \
+                     (the duration acts as a timeout)",
+                ),
+        )
+        .arg(
+            Arg::new("barrier-count")
+                .long("barrier-count")
+                .takes_value(true)
+                .value_name("N")
+                .requires("barrier")
+                .help("Number of processes that need to arrive at the barrier"),
+        )
+        .arg(
+            Arg::new("wait-for")
+                .long("wait-for")
+                .takes_value(true)
+                .value_name("PATH")
+                .conflicts_with_all(&["wait-for-gone", "wait-for-change"])
+                .help("Wait until PATH exists (the duration acts as a timeout)"),
+        )
+        .arg(
+            Arg::new("wait-for-gone")
+                .long("wait-for-gone")
+                .takes_value(true)
+                .value_name("PATH")
+                .conflicts_with("wait-for-change")
+                .help("Wait until PATH no longer exists (the duration acts as a timeout)"),
+        )
+        .arg(
+            Arg::new("wait-for-change")
+                .long("wait-for-change")
+                .takes_value(true)
+                .value_name("PATH")
+                .help(
+                    "Wait until PATH is created, removed or modified (the duration acts as a \
+                     timeout)",
+                ),
+        )
+        .arg(
+            Arg::new("wait-signal")
+                .long("wait-signal")
+                .takes_value(true)
+                .value_name("SIGNALS")
+                .help(
+                    "Wait until one of the given signals is received, e.g. \
+                     'SIGUSR1=10,SIGUSR2=20'. The optional code after each signal is used \
+                     as the exit code.",
+                ),
+        )
+        .arg(
+            Arg::new("exit-on")
+                .long("exit-on")
+                .takes_value(true)
+                .value_name("SIGNALS")
+                .help(
+                    "Wake up when one of the given signals is received, e.g. 'SIGUSR1=42'. \
+                     Unlike with '--wait-signal', the duration is not a timeout.",
+                ),
+        )
+        .arg(
+            Arg::new("dump-core-on")
+                .long("dump-core-on")
+                .takes_value(true)
+                .value_name("SIGNAL")
+                .help("Dump core when SIGNAL is received, like '--crash-mode core' (Unix only)"),
+        )
+        .arg(
+            Arg::new("ignore")
+                .long("ignore")
+                .takes_value(true)
+                .value_name("SIGNALS")
+                .help("Log, but otherwise ignore the given signals, e.g. 'SIGTERM,SIGHUP'"),
+        )
+        .arg(
+            Arg::new("on-wake-signal")
+                .long("on-wake-signal")
+                .takes_value(true)
+                .value_name("PID:SIGNAL")
+                .help("Send SIGNAL to the process PID after waking up (unless interrupted)"),
+        )
+        .arg(
+            Arg::new("on-interrupt-signal")
+                .long("on-interrupt-signal")
+                .takes_value(true)
+                .value_name("PID:SIGNAL")
+                .help("Send SIGNAL to the process PID if the sleep is interrupted"),
+        )
+        .arg(
+            Arg::new("interrupt-exit-code")
+                .long("interrupt-exit-code")
+                .visible_alias("exit-code-on-interrupt")
+                .takes_value(true)
+                .value_name("CODE")
+                .allow_hyphen_values(true)
+                .help(
+                    "Terminate with the given exit code if the sleep is interrupted by a \
+                     termination signal (instead of '--exit-code')",
+                ),
+        )
+        .arg(
+            Arg::new("signal-exit-codes")
+                .long("signal-exit-codes")
+                .conflicts_with("interrupt-exit-code")
+                .help(
+                    "Terminate with 128 + the signal number if the sleep is interrupted by a \
+                     termination signal, like a process that was killed by it",
+                ),
+        )
+        .arg(
+            Arg::new("timeout-code")
+                .long("timeout-code")
+                .takes_value(true)
+                .value_name("CODE")
+                .allow_hyphen_values(true)
+                .default_value("124")
+                .help(
+                    "Terminate with the given exit code if the duration elapses before \
+                     the awaited condition is met (e.g. '--barrier')",
+                ),
+        )
+        .arg(
+            Arg::new("require-accuracy")
+                .long("require-accuracy")
+                .takes_value(true)
+                .value_name("PERCENT")
+                .help(
+                    "Repeat the sleep if it overshoots the given duration by more than \
+                     PERCENT percent",
+                ),
+        )
+        .arg(Arg::new("measure").long("measure").help(
+            "Measure by how much each sleep overshoots and how late heartbeats are, and print \
+             the minimum, maximum and mean (in microseconds) before exiting",
+        ))
+        .arg(
+            Arg::new("max-retries")
+                .long("max-retries")
+                .takes_value(true)
+                .value_name("N")
+                .requires("require-accuracy")
+                .default_value("3")
+                .help("Maximum number of times to repeat an inaccurate sleep before failing"),
+        )
+        .arg(
+            Arg::new("busy")
+                .long("busy")
+                .help("Keep CPUs busy while sleeping, see '--cpus' and '--load'"),
+        )
+        .arg(
+            Arg::new("cpus")
+                .long("cpus")
+                .takes_value(true)
+                .value_name("N")
+                .requires("busy")
+                .help("Number of threads to keep busy [default: number of CPUs]"),
+        )
+        .arg(
+            Arg::new("load")
+                .long("load")
+                .takes_value(true)
+                .value_name("PERCENT")
+                .requires("busy")
+                .default_value("100%")
+                .help("Fraction of the time each busy thread spins, e.g. '75%'"),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with("cpus")
+                .help("Spawn N idle threads, or keep N threads busy with '--busy'"),
+        )
+        .arg(
+            Arg::new("thread-name")
+                .long("thread-name")
+                .takes_value(true)
+                .value_name("PREFIX")
+                .help("Name the '--threads' or '--busy' threads PREFIX-1, PREFIX-2, …"),
+        )
+        .arg(
+            Arg::new("memory")
+                .long("memory")
+                .takes_value(true)
+                .value_name("SIZE")
+                .help("Allocate and touch SIZE of memory at startup, e.g. '512M'"),
+        )
+        .arg(
+            Arg::new("leak-rate")
+                .long("leak-rate")
+                .takes_value(true)
+                .value_name("RATE")
+                .help("Allocate more memory while sleeping at the given RATE, e.g. '10M/s'"),
+        )
+        .arg(
+            Arg::new("write-rate")
+                .long("write-rate")
+                .takes_value(true)
+                .value_name("RATE")
+                .requires("write-file")
+                .help("Write to the '--write-file' at the given RATE while sleeping, e.g. '10M/s'"),
+        )
+        .arg(
+            Arg::new("write-file")
+                .long("write-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .requires("write-rate")
+                .help("File for '--write-rate', which is truncated first"),
+        )
+        .arg(
+            Arg::new("fsync")
+                .long("fsync")
+                .requires("write-rate")
+                .help("Flush the data of '--write-rate' to the disk every 100ms"),
+        )
+        .arg(
+            Arg::new("read-rate")
+                .long("read-rate")
+                .takes_value(true)
+                .value_name("RATE")
+                .requires("read-file")
+                .help(
+                    "Read the '--read-file' at the given RATE while sleeping (starting over at \
+                     its end), e.g. '10M/s'",
+                ),
+        )
+        .arg(
+            Arg::new("read-file")
+                .long("read-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .requires("read-rate")
+                .help("File for '--read-rate'"),
+        )
+        .arg(
+            Arg::new("spam-stdout")
+                .long("spam-stdout")
+                .takes_value(true)
+                .value_name("RATE")
+                .help("Write RATE lines per second to stdout while sleeping, e.g. '1000/s'"),
+        )
+        .arg(
+            Arg::new("spam-stderr")
+                .long("spam-stderr")
+                .takes_value(true)
+                .value_name("RATE")
+                .help("Write RATE lines per second to stderr while sleeping, e.g. '1000/s'"),
+        )
+        .arg(
+            Arg::new("line-length")
+                .long("line-length")
+                .takes_value(true)
+                .value_name("N")
+                .help(
+                    "Number of characters (without the newline) per line of '--spam-stdout' \
+                     and '--spam-stderr' [default: 80]",
+                ),
+        )
+        .arg(
+            Arg::new("max-duration")
+                .long("max-duration")
+                .takes_value(true)
+                .value_name("DURATION")
+                .help("Never sleep longer than DURATION, even when sleeping forever"),
+        )
+        .arg(
+            Arg::new("grace")
+                .long("grace")
+                .takes_value(true)
+                .value_name("DURATION")
+                .conflicts_with("no-interrupt")
+                .help(
+                    "Defer termination signals received during the first DURATION of the \
+                     sleep until DURATION has passed",
+                ),
+        )
+        .arg(
+            Arg::new("die-after-signals")
+                .long("die-after-signals")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with("no-interrupt")
+                .help("Ignore the first N-1 termination signals and only exit on the N-th"),
+        )
+        .arg(
+            Arg::new("shutdown-delay")
+                .long("shutdown-delay")
+                .takes_value(true)
+                .value_name("DURATION")
+                .help(
+                    "Keep running for DURATION after a termination signal, to simulate a \
+                     slow shutdown. Further termination signals are ignored meanwhile",
+                ),
+        )
+        .arg(
+            Arg::new("crash-after")
+                .long("crash-after")
+                .takes_value(true)
+                .value_name("DURATION")
+                .help("Terminate abnormally after DURATION (see '--crash-mode')"),
+        )
+        .arg(
+            Arg::new("crash-mode")
+                .long("crash-mode")
+                .takes_value(true)
+                .value_name("MODE")
+                .possible_values(["segfault", "abort", "panic", "oom", "core"])
+                .default_value("abort")
+                .help(
+                    "How '--crash-after' terminates: die from SIGSEGV or SIGABRT, panic, \
+                     allocate memory until that fails, or dump core (SIGQUIT with RLIMIT_CORE \
+                     raised)",
+                ),
+        )
+        .arg(
+            Arg::new("children")
+                .long("children")
+                .takes_value(true)
+                .value_name("N")
+                .help(
+                    "Spawn N child processes that sleep with the same options (except for \
+                     '--pidfile', '--listen' and '--control-socket'). They are terminated \
+                     when lucid is interrupted and reaped before it exits",
+                ),
+        )
+        .arg(
+            Arg::new("fork")
+                .long("fork")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with("daemon")
+                .help(
+                    "Run N instances with the same options (their prefixes, '--pidfile' and \
+                     '--control-socket' paths get the index appended, e.g. 'lucid-1', and \
+                     their '--listen' ports are counted up) and wait for them. Termination \
+                     signals are forwarded, and the highest exit code among the instances is \
+                     returned",
+                ),
+        )
+        .arg(
+            Arg::new("depth")
+                .long("depth")
+                .takes_value(true)
+                .value_name("D")
+                .multiple_occurrences(true)
+                .requires("children")
+                .help(
+                    "Number of levels in the tree of child processes, each child spawns \
+                     '--children' processes of its own (default: 1)",
+                ),
+        )
+        .arg(Arg::new("notify").long("notify").help(
+            "Notify the service manager via $NOTIFY_SOCKET (sd_notify): READY=1 \
+             after starting (see '--ready-after'), WATCHDOG=1 at half of \
+             $WATCHDOG_USEC and STOPPING=1 when waking up (Unix only)",
+        ))
+        .arg(
+            Arg::new("ready-after")
+                .long("ready-after")
+                .takes_value(true)
+                .value_name("DURATION")
+                .help(
+                    "Only send READY=1 (see '--notify') and start listening (see '--listen') \
+                     after DURATION, to simulate a slow start",
+                ),
+        )
+        .arg(
+            Arg::new("listen")
+                .long("listen")
+                .takes_value(true)
+                .value_name("ADDR:PORT")
+                .help(
+                    "Accept TCP connections on ADDR:PORT while sleeping, for port-based \
+                     readiness checks. Connections are closed right away",
+                ),
+        )
+        .arg(
+            Arg::new("http-response")
+                .long("http-response")
+                .takes_value(true)
+                .value_name("BODY")
+                .requires("listen")
+                .help("Answer connections to '--listen' with an HTTP 200 response with BODY"),
+        )
+        .arg(
+            Arg::new("zombies")
+                .long("zombies")
+                .takes_value(true)
+                .value_name("N")
+                .help(
+                    "Create N child processes that exit immediately, but are not reaped \
+                     until lucid exits (Unix only)",
+                ),
+        )
+        .arg(
+            Arg::new("init")
+                .long("init")
+                .conflicts_with_all(&["zombies", "forward-ignored"])
+                .help(
+                    "Behave like a minimal init: reap all child processes (including \
+                     orphaned descendants) and forward termination signals to them. The \
+                     '--then' command is started right away, and lucid exits with its exit \
+                     code once it exits (Unix only)",
+                ),
+        )
+        .arg(
+            Arg::new("repeat")
+                .long("repeat")
+                .visible_alias("cycles")
+                .takes_value(true)
+                .value_name("N")
+                .help("Sleep N times in a row, printing a message after each cycle"),
+        )
+        .arg(
+            Arg::new("forever")
+                .long("forever")
+                .conflicts_with("repeat")
+                .help("Like '--repeat', but keep sleeping again until interrupted"),
+        )
+        .arg(
+            Arg::new("extend")
+                .long("extend")
+                .takes_value(true)
+                .value_name("DURATION")
+                .help(
+                    "Extend the sleep by DURATION whenever SIGHUP is received, instead of \
+                     waking up",
+                ),
+        )
+        .arg(
+            Arg::new("reload-on-hup")
+                .long("reload-on-hup")
+                .conflicts_with("extend")
+                .help(
+                    "Simulate reloading the configuration whenever SIGHUP is received: \
+                     restart the sleep (re-reading the '--script-file'), instead of waking up",
+                ),
+        )
+        .arg(Arg::new("exclude-paused").long("exclude-paused").help(
+            "Do not count the time during which lucid was stopped (SIGSTOP/SIGCONT) \
+             towards the sleep duration",
+        ))
+        .arg(Arg::new("report-stops").long("report-stops").help(
+            "Report how long lucid was stopped (SIGSTOP, SIGTSTP) once it is continued, and how \
+             much of the sleep remains. SIGTSTP is reported before stopping (Unix only)",
+        ))
+        .arg(
+            Arg::new("heartbeat")
+                .long("heartbeat")
+                .visible_alias("interval")
+                .takes_value(true)
+                .value_name("DURATION")
+                .help(
+                    "Print the remaining time every DURATION while sleeping, even with \
+                     '--quiet'",
+                ),
+        )
+        .arg(Arg::new("progress").long("progress").help(
+            "Show a live countdown with the remaining time on stderr if it is a terminal. \
+             Otherwise, print the remaining time every second (see '--heartbeat')",
+        ))
+        .arg(
+            Arg::new("countdown")
+                .long("countdown")
+                .conflicts_with("progress")
+                .help(
+                    "Rewrite a single line on stdout with the remaining time while sleeping, if \
+                     stdout is a terminal, the duration is finite and messages go to stdout \
+                     with normal verbosity and format",
+                ),
+        )
+        .arg(
+            Arg::new("heartbeat-url")
+                .long("heartbeat-url")
+                .takes_value(true)
+                .value_name("URL")
+                .help(
+                    "Periodically POST a JSON heartbeat (pid, elapsed and remaining \
+                     seconds) to URL. Failed requests are reported but do not end the sleep.",
+                ),
+        )
+        .arg(
+            Arg::new("heartbeat-interval")
+                .long("heartbeat-interval")
+                .takes_value(true)
+                .value_name("DURATION")
+                .requires("heartbeat-url")
+                .default_value("30s")
+                .help("Time between two heartbeats sent to '--heartbeat-url'"),
+        )
+        .arg(
+            Arg::new("metrics-file")
+                .long("metrics-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("Write metrics about the run to PATH in Prometheus text format"),
+        )
+        .arg(
+            Arg::new("any-key")
+                .long("any-key")
+                .help("Wake up when a key is pressed (if stdin is a terminal)"),
+        )
+        .arg(
+            Arg::new("start-marker")
+                .long("start-marker")
+                .help("Print 'LUCID_START <epoch_ns>' right before the sleep begins"),
+        )
+        .arg(
+            Arg::new("end-marker")
+                .long("end-marker")
+                .help("Print 'LUCID_END <epoch_ns>' right after the sleep ends"),
+        )
+        .arg(
+            Arg::new("marker-fd")
+                .long("marker-fd")
+                .takes_value(true)
+                .value_name("FD")
+                .help("Write start and end markers to the given file descriptor instead of stdout"),
+        )
+        .arg(Arg::new("print-config").long("print-config").help(
+            "Print the effective configuration (merged from config files, environment \
+             variables and the command line) and exit",
+        ))
+        .arg(
+            Arg::new("generate")
+                .long("generate")
+                .takes_value(true)
+                .min_values(1)
+                .max_values(2)
+                .value_name("WHAT")
+                .help(
+                    "Print shell completions ('completions bash|zsh|fish|elvish|powershell') \
+                     or a man page ('man') and exit",
+                ),
+        )
+        .arg(
+            Arg::new("verbose-env")
+                .long("verbose-env")
+                .help("Print the LUCID_* environment variables and how they were interpreted"),
+        )
+        .arg(Arg::new("dump-state").long("dump-state").help(
+            "Print the environment, and on Unix the umask, open file descriptors, resource \
+             limits and ignored or blocked signals that lucid inherited",
+        ))
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(["human", "csv", "json"])
+                .default_value("human")
+                .help(
+                    "Output format. 'json' prints one JSON object per message (with the \
+                     fields prefix, level, msg, elapsed_ms and ts, as well as event and \
+                     duration_ms for the sleep_start, heartbeat, signal and wake events). \
+                     'csv' only prints a single \
+                     summary line with the columns requested_s, actual_s, interrupted, reason, \
+                     exit_code and cycles",
+                ),
+        )
+        .arg(
+            Arg::new("csv-header")
+                .long("csv-header")
+                .help("Print a header line before the CSV summary"),
+        )
+        .arg(Arg::new("summary").long("summary").help(
+            "Print a JSON object with the requested and actual duration, why lucid finished \
+             (e.g. 'elapsed', 'interrupted', 'crash' or 'control_exit'), the number of \
+             termination signals, the exit code and how often each signal was received right \
+             before exiting (or crashing). Goes to stderr with '--quiet' or '--stderr'",
+        ))
+        .arg(
+            Arg::new("timestamps")
+                .long("timestamps")
+                .conflicts_with("timestamp-format")
+                .help(
+                    "Prefix all messages with the current local time, e.g. \
+                     '[2024-05-01T12:00:00.123]'",
+                ),
+        )
+        .arg(
+            Arg::new("timestamp-format")
+                .long("timestamp-format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .help(
+                    "Prefix all messages with the current time in the given strftime-like \
+                     FORMAT (e.g. '%H:%M:%S')",
+                ),
+        )
+        .arg(
+            Arg::new("timestamp")
+                .long("timestamp")
+                .takes_value(true)
+                .value_name("STYLE")
+                .possible_values(["rfc3339", "unix", "elapsed"])
+                .min_values(0)
+                .require_equals(true)
+                .default_missing_value("rfc3339")
+                .conflicts_with_all(&["timestamps", "timestamp-format"])
+                .help(
+                    "Prefix all messages with a timestamp: the current time according to \
+                     RFC 3339 (the default), seconds since the Unix epoch, or the time since \
+                     lucid started. Use as '--timestamp' or '--timestamp=STYLE'",
+                ),
+        )
+        .arg(
+            Arg::new("log-target")
+                .long("log-target")
+                .takes_value(true)
+                .value_name("TARGETS")
+                .help(
+                    "Send messages to a comma-separated list of 'stdout' (the default, or \
+                     stderr with '--stderr'), 'syslog' and 'journald'. The prefix is used \
+                     as the syslog tag (Unix only)",
+                ),
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .help(
+                    "Write all messages to the file at PATH instead of stdout (useful with \
+                     '--daemon'). The file is truncated unless '--append' is given",
+                ),
+        )
+        .arg(
+            Arg::new("append")
+                .long("append")
+                .requires("log-file")
+                .help("Append to the '--log-file' instead of truncating it"),
+        )
+        .arg(
+            Arg::new("message-format")
+                .long("message-format")
+                .takes_value(true)
+                .value_name("TEMPLATE")
+                .help(
+                    "Render messages according to TEMPLATE, with the placeholders {prefix}, \
+                     {msg}, {pid} and {elapsed} [default: '[{prefix}]: {msg}']",
+                ),
+        )
+        .arg(
+            Arg::new("output-buffer-size")
+                .long("output-buffer-size")
+                .takes_value(true)
+                .value_name("BYTES")
+                .help(
+                    "Buffer up to BYTES of output instead of writing every message \
+                     immediately. Buffered output is flushed at least once per second \
+                     while messages are printed, and on exit.",
+                ),
+        )
+        .arg(
+            Arg::new("no-subsecond")
+                .long("no-subsecond")
+                .help("Round durations in messages to whole seconds"),
         )
         .arg(
             Arg::new("verbose")
@@ -157,187 +1754,140 @@ fn run() -> Result<ExitCode> {
                 .conflicts_with("verbose")
                 .help("Do not output anything"),
         )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .takes_value(true)
+                .value_name("WHEN")
+                .possible_values(["auto", "always", "never"])
+                .default_value("auto")
+                .help(
+                    "Color the prefix (by its name, to tell several instances apart), signals \
+                     and errors. 'auto' only colors output to a terminal, unless NO_COLOR is set",
+                ),
+        )
         .arg(
             Arg::new("stderr")
                 .long("stderr")
                 .short('e')
                 .help("Print all messages to stderr"),
-        );
-
-    let matches = app.get_matches();
-
-    let sleeping_duration = match matches.value_of("duration") {
-        None => None,
-        Some(duration) => Some(
-            duration
-                .parse::<f64>()
-                .map_err(|_| LucidError::DurationParseError)
-                .and_then(duration_from_float)?,
-        ),
-    };
-
-    let verbosity_level = if matches.is_present("verbose") {
-        VerbosityLevel::Verbose
-    } else if matches.is_present("quiet") {
-        VerbosityLevel::Quiet
-    } else {
-        VerbosityLevel::Normal
-    };
-
-    let no_interrupt = matches.is_present("no-interrupt");
-
-    let prefix = matches.value_of("prefix").unwrap_or("lucid");
-
-    let exit_code = matches
-        .value_of("exit-code")
-        .and_then(|c| c.parse::<i32>().ok())
-        .unwrap_or(0i32);
-
-    let stdout = io::stdout();
-    let stderr = io::stderr();
-    let mut output = OutputHandler::new(
-        stdout.lock(),
-        stderr.lock(),
-        prefix,
-        verbosity_level,
-        matches.is_present("stderr"),
-    );
-
-    if matches.is_present("daemon") {
-        output.print_verbose("Daemonizing..");
-        unistd::daemon(true, true).map_err(|_| LucidError::FailedToDaemonize)?;
-    }
-
-    // Print status information
-    output.print_verbose(&format!(
-        "getcwd() = {}",
-        unistd::getcwd()
-            .map(|p| p.to_string_lossy().into_owned())
-            .map(|s| format!("\"{}\"", s))
-            .unwrap_or_else(|_| "<error: could not read current working directory>".into())
-    ));
-    output.print_verbose(&format!("getpid() = {}", unistd::getpid()));
-
-    match sleeping_duration {
-        None => {
-            output.print("Going to sleep forever");
-        }
-        Some(sleeping_duration) => {
-            output.print(&format!(
-                "Going to sleep for {}",
-                duration_as_str(&sleeping_duration)
-            ));
-        }
-    }
-
-    let start_time = time::Instant::now();
-
-    // Set up signal handler
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
-    ctrlc::set_handler(move || {
-        r.store(false, Ordering::SeqCst);
-    })
-    .expect("Error while setting up signal handler.");
-
-    // Main loop
-    let cycle_time = time::Duration::from_millis(100);
-    loop {
-        let since_start = start_time.elapsed();
-
-        if !running.load(Ordering::SeqCst) {
-            if no_interrupt {
-                output.print("Ignoring termination signal.");
-                running.store(true, Ordering::SeqCst);
-            } else {
-                output.print("Caught termination signal - interrupting sleep.");
-                break;
-            }
-        }
-
-        if let Some(sleeping_duration) = sleeping_duration {
-            if since_start >= sleeping_duration {
-                break;
-            }
-
-            if since_start + cycle_time > sleeping_duration {
-                if sleeping_duration > since_start {
-                    thread::sleep(sleeping_duration - since_start);
-                } else {
-                    break;
-                }
-            } else {
-                thread::sleep(cycle_time);
-            }
-        } else {
-            thread::sleep(cycle_time);
-        }
-
-        output.print_verbose(&format!(
-            "Still dreaming after {}",
-            duration_as_str(&since_start)
-        ));
-    }
-
-    output.print(&format!(
-        "Woke up after {}",
-        duration_as_str(&start_time.elapsed())
-    ));
-
-    Ok(exit_code)
+        )
 }
 
 fn main() {
-    let result = run();
+    let matches = build_command().get_matches();
+    let result = run(&matches);
     match result {
         Err(err) => {
-            eprintln!("Error: {}", err.message());
-            std::process::exit(1);
+            eprintln!("{}", color_choice(&matches).format_error(&err.message()));
+            process::exit(1);
         }
         Ok(exit_code) => {
-            std::process::exit(exit_code);
+            process::exit(exit_code);
         }
     }
 }
 
 #[test]
-fn test_duration_from_float() {
-    assert_eq!(Ok(time::Duration::from_secs(14)), duration_from_float(14.0));
+fn test_build_command() {
+    build_command().debug_assert();
+}
+
+#[test]
+fn test_without_exclusive_options() {
+    let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
     assert_eq!(
-        Ok(time::Duration::from_secs(14)),
-        duration_from_float(14.0001)
+        args(&["10", "--verbose", "--children", "2"]),
+        without_exclusive_options(args(&[
+            "10",
+            "--pidfile",
+            "/run/lucid.pid",
+            "--verbose",
+            "--listen=127.0.0.1:8080",
+            "--children",
+            "2",
+            "--control-socket",
+            "/run/lucid.sock",
+        ]))
     );
+    assert_eq!(
+        args(&["--then", "--", "cat", "--pidfile", "x"]),
+        without_exclusive_options(args(&["--then", "--", "cat", "--pidfile", "x"]))
+    );
+}
 
-    assert_eq!(Ok(time::Duration::from_secs(0)), duration_from_float(0.0));
-
+#[test]
+fn test_instance_address() {
     assert_eq!(
-        Ok(time::Duration::from_millis(12345)),
-        duration_from_float(12.345)
+        Ok("127.0.0.1:8080".to_string()),
+        instance_address("127.0.0.1:8080", None)
     );
     assert_eq!(
-        Ok(time::Duration::from_millis(12345)),
-        duration_from_float(12.3454)
+        Ok("127.0.0.1:8080".to_string()),
+        instance_address("127.0.0.1:8080", Some("1"))
     );
     assert_eq!(
-        Ok(time::Duration::from_millis(12346)),
-        duration_from_float(12.3456)
+        Ok("[::1]:8082".to_string()),
+        instance_address("[::1]:8080", Some("3"))
     );
+    assert_eq!(
+        Ok("localhost:0".to_string()),
+        instance_address("localhost:0", Some("3"))
+    );
+    assert_eq!(
+        Err(LucidError::InvalidArgument("--listen")),
+        instance_address("127.0.0.1:65535", Some("2"))
+    );
+    assert_eq!(
+        PathBuf::from("/run/lucid.pid-2"),
+        instance_path("/run/lucid.pid", Some("2"))
+    );
+}
 
+#[test]
+fn test_parse_load() {
+    assert_eq!(Ok(0.75), parse_load("75%"));
+    assert_eq!(Ok(1.0), parse_load("100"));
+    assert_eq!(Err(LucidError::InvalidArgument("--load")), parse_load("0%"));
     assert_eq!(
-        Ok(time::Duration::from_millis(1)),
-        duration_from_float(0.001)
+        Err(LucidError::InvalidArgument("--load")),
+        parse_load("150%")
     );
     assert_eq!(
-        Ok(time::Duration::from_millis(1100)),
-        duration_from_float(1.1)
+        Err(LucidError::InvalidArgument("--load")),
+        parse_load("lots")
     );
+}
 
-    assert_eq!(Err(LucidError::DurationNegative), duration_from_float(-1.2));
+#[test]
+fn test_strict_duration() {
+    assert_eq!(
+        Err(LucidError::DurationUnitMissing),
+        parse_duration_checked("5", true)
+    );
+    assert_eq!(
+        Ok(time::Duration::from_secs(5)),
+        parse_duration_checked("5s", true)
+    );
+    assert_eq!(
+        Err(LucidError::InvalidArgument("--foo")),
+        parse_duration_option("five", "--foo", true)
+    );
 }
 
 #[test]
-fn test_verbosity_level() {
-    assert!(VerbosityLevel::Normal > VerbosityLevel::Quiet);
-    assert!(VerbosityLevel::Verbose > VerbosityLevel::Normal);
-    assert!(VerbosityLevel::Verbose > VerbosityLevel::Quiet);
+fn test_parse_jitter() {
+    assert_eq!(Ok(Jitter::Relative(0.1)), parse_jitter("10%", false));
+    assert_eq!(
+        Ok(Jitter::Fixed(time::Duration::from_millis(500))),
+        parse_jitter("500ms", true)
+    );
+    assert_eq!(
+        Err(LucidError::InvalidArgument("--jitter")),
+        parse_jitter("150%", false)
+    );
+    assert_eq!(
+        Err(LucidError::DurationUnitMissing),
+        parse_jitter("2", true)
+    );
 }
@@ -0,0 +1,51 @@
+//! Timer accuracy statistics, see `--measure`.
+
+use std::time::Duration;
+
+/// Collects how late something happened, e.g. the end of a sleep.
+#[derive(Default)]
+pub struct Latencies {
+    samples: Vec<Duration>,
+}
+
+impl Latencies {
+    pub fn add(&mut self, latency: Duration) {
+        self.samples.push(latency);
+    }
+
+    /// Renders the statistics in microseconds, e.g. `min 52us, max 120us, mean 80.5us (3
+    /// samples)`. `None` if nothing was measured.
+    pub fn describe(&self) -> Option<String> {
+        let min = self.samples.iter().min()?;
+        let max = self.samples.iter().max()?;
+        let total: Duration = self.samples.iter().sum();
+        let count = self.samples.len();
+        Some(format!(
+            "min {}us, max {}us, mean {:.1}us ({} sample{})",
+            min.as_micros(),
+            max.as_micros(),
+            total.as_secs_f64() * 1e6 / count as f64,
+            count,
+            if count == 1 { "" } else { "s" }
+        ))
+    }
+}
+
+#[test]
+fn test_latencies() {
+    let mut latencies = Latencies::default();
+    assert_eq!(None, latencies.describe());
+
+    latencies.add(Duration::from_micros(120));
+    assert_eq!(
+        Some("min 120us, max 120us, mean 120.0us (1 sample)".to_string()),
+        latencies.describe()
+    );
+
+    latencies.add(Duration::from_micros(52));
+    latencies.add(Duration::from_nanos(69_500));
+    assert_eq!(
+        Some("min 52us, max 120us, mean 80.5us (3 samples)".to_string()),
+        latencies.describe()
+    );
+}
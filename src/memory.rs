@@ -0,0 +1,106 @@
+use crate::{LucidError, Result};
+
+/// Pages are touched at this interval, such that allocations count towards the RSS.
+const PAGE_SIZE: usize = 4096;
+
+/// Parses a size like `512M`, `1.5G`, `64k` or `1000` (bytes). Units are powers of 1024.
+pub fn parse_size(input: &str) -> Result<u64> {
+    let input = input.trim();
+    let (number, factor) = match input.char_indices().last() {
+        Some((index, unit)) if unit.is_ascii_alphabetic() => {
+            let factor = match unit.to_ascii_uppercase() {
+                'B' => 1u64,
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                'T' => 1 << 40,
+                _ => return Err(LucidError::SizeParseError),
+            };
+            (&input[..index], factor)
+        }
+        _ => (input, 1),
+    };
+    let value = number
+        .parse::<f64>()
+        .map_err(|_| LucidError::SizeParseError)?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(LucidError::SizeParseError);
+    }
+    Ok((value * factor as f64).round() as u64)
+}
+
+/// Memory that is allocated on purpose, see `--memory` and `--leak-rate`.
+#[derive(Default)]
+pub struct MemoryHog {
+    blocks: Vec<Vec<u8>>,
+    allocated: u64,
+}
+
+impl MemoryHog {
+    /// Allocates `bytes` more bytes and writes to every page of them.
+    pub fn allocate(&mut self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        let mut block = vec![0u8; bytes as usize];
+        for byte in block.iter_mut().step_by(PAGE_SIZE) {
+            // Volatile, such that the writes are not optimized away
+            // SAFETY: `byte` is a valid, exclusive reference.
+            unsafe { std::ptr::write_volatile(byte, 1) };
+        }
+        self.blocks.push(block);
+        self.allocated += bytes;
+    }
+
+    /// Allocates as much as needed to reach a total of `bytes`.
+    pub fn grow_to(&mut self, bytes: u64) {
+        self.allocate(bytes.saturating_sub(self.allocated));
+    }
+
+    pub fn allocated(&self) -> u64 {
+        self.allocated
+    }
+}
+
+/// Formats a number of bytes for display, e.g. `1.5 MiB`.
+pub fn size_as_str(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for name in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = name;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(Ok(1000), parse_size("1000"));
+    assert_eq!(Ok(512 << 20), parse_size("512M"));
+    assert_eq!(Ok(64 << 10), parse_size("64k"));
+    assert_eq!(Ok(3 << 29), parse_size("1.5G"));
+    assert_eq!(Ok(10), parse_size("10B"));
+    assert_eq!(Err(LucidError::SizeParseError), parse_size("10X"));
+    assert_eq!(Err(LucidError::SizeParseError), parse_size("M"));
+    assert_eq!(Err(LucidError::SizeParseError), parse_size("-1M"));
+
+    assert_eq!("512 B", size_as_str(512));
+    assert_eq!("1.5 MiB", size_as_str(3 << 19));
+}
+
+#[test]
+fn test_memory_hog() {
+    let mut hog = MemoryHog::default();
+    hog.allocate(10_000);
+    hog.grow_to(5_000);
+    assert_eq!(10_000, hog.allocated());
+    hog.grow_to(20_000);
+    assert_eq!(20_000, hog.allocated());
+}
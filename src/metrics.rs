@@ -0,0 +1,84 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::summary::RunSummary;
+
+/// Exports the outcome of a run in Prometheus text format.
+pub struct Metrics<'a>(pub &'a RunSummary);
+
+impl<'a> Metrics<'a> {
+    fn render(&self) -> String {
+        let summary = self.0;
+        let requested = summary
+            .requested
+            .map(|d| d.as_secs_f64().to_string())
+            .unwrap_or_else(|| "+Inf".into());
+
+        let mut text = String::new();
+        let mut add = |name: &str, help: &str, value: &str| {
+            text.push_str(&format!(
+                "# HELP {0} {1}\n# TYPE {0} gauge\n{0} {2}\n",
+                name, help, value
+            ));
+        };
+        add(
+            "lucid_sleep_requested_seconds",
+            "Requested sleep duration in seconds.",
+            &requested,
+        );
+        add(
+            "lucid_sleep_actual_seconds",
+            "Actual sleep duration in seconds.",
+            &summary.actual.as_secs_f64().to_string(),
+        );
+        add(
+            "lucid_interrupted",
+            "Whether the sleep was interrupted by a termination signal.",
+            if summary.interrupted() { "1" } else { "0" },
+        );
+        add(
+            "lucid_exit_code",
+            "Exit code of the process.",
+            &summary.exit_code.to_string(),
+        );
+        text
+    }
+
+    /// Writes the metrics to `path`. The file is written to a temporary location first and
+    /// then renamed, such that readers never see a partially written file.
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        let mut tmp_name = path.as_os_str().to_owned();
+        tmp_name.push(format!(".{}.tmp", std::process::id()));
+        let tmp_path = Path::new(&tmp_name);
+
+        let result = fs::File::create(tmp_path)
+            .and_then(|mut file| file.write_all(self.render().as_bytes()))
+            .and_then(|_| fs::rename(tmp_path, path));
+        if result.is_err() {
+            fs::remove_file(tmp_path).ok();
+        }
+        result
+    }
+}
+
+#[test]
+fn test_render_metrics() {
+    use std::time;
+
+    let summary = RunSummary {
+        requested: None,
+        actual: time::Duration::from_millis(1500),
+        cycles: 15,
+        reason: crate::WakeReason::Interrupted,
+        termination_signals: 1,
+        signals: vec![],
+        exit_code: 3,
+    };
+    let text = Metrics(&summary).render();
+    assert!(text.contains("\nlucid_sleep_requested_seconds +Inf\n"));
+    assert!(text.contains("\nlucid_sleep_actual_seconds 1.5\n"));
+    assert!(text.contains("\nlucid_interrupted 1\n"));
+    assert!(text.contains("\nlucid_exit_code 3\n"));
+    assert!(text.contains("# TYPE lucid_exit_code gauge\n"));
+}
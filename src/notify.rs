@@ -0,0 +1,108 @@
+//! The sd_notify protocol, which tells systemd about the state of a `Type=notify` service.
+
+use std::env;
+use std::time;
+
+use crate::Result;
+
+#[cfg(unix)]
+mod imp {
+    use std::io;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    use crate::{LucidError, Result};
+
+    pub struct Socket {
+        socket: UnixDatagram,
+        address: SocketAddr,
+    }
+
+    /// A leading `@` denotes an abstract socket.
+    #[cfg(target_os = "linux")]
+    fn address(path: &str) -> io::Result<SocketAddr> {
+        use std::os::linux::net::SocketAddrExt;
+
+        match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name),
+            None => SocketAddr::from_pathname(path),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn address(path: &str) -> io::Result<SocketAddr> {
+        SocketAddr::from_pathname(path)
+    }
+
+    impl Socket {
+        pub fn new(path: &str) -> Result<Socket> {
+            let address = address(path).map_err(|_| LucidError::NotifyError)?;
+            let socket = UnixDatagram::unbound().map_err(|_| LucidError::NotifyError)?;
+            Ok(Socket { socket, address })
+        }
+
+        pub fn send(&self, message: &str) -> io::Result<()> {
+            self.socket
+                .send_to_addr(message.as_bytes(), &self.address)
+                .map(|_| ())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::io;
+
+    use crate::{LucidError, Result};
+
+    pub struct Socket;
+
+    impl Socket {
+        pub fn new(_: &str) -> Result<Socket> {
+            Err(LucidError::Unsupported("--notify"))
+        }
+
+        pub fn send(&self, _: &str) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
+
+/// Sends notifications to the socket in `$NOTIFY_SOCKET`.
+pub struct Notifier {
+    socket: imp::Socket,
+    watchdog_interval: Option<time::Duration>,
+}
+
+impl Notifier {
+    /// Returns `None` if `$NOTIFY_SOCKET` is not set, i.e. lucid does not run as a service.
+    pub fn from_environment() -> Result<Option<Notifier>> {
+        let path = match env::var("NOTIFY_SOCKET") {
+            Ok(path) if !path.is_empty() => path,
+            _ => return Ok(None),
+        };
+
+        // The watchdog is meant for us unless WATCHDOG_PID names another process
+        let watchdog_pid = env::var("WATCHDOG_PID").ok();
+        let watchdog_interval = env::var("WATCHDOG_USEC")
+            .ok()
+            .and_then(|usec| usec.parse::<u64>().ok())
+            .filter(|&usec| usec > 0)
+            .filter(|_| watchdog_pid.is_none_or(|pid| pid == std::process::id().to_string()))
+            .map(|usec| time::Duration::from_micros(usec) / 2);
+
+        Ok(Some(Notifier {
+            socket: imp::Socket::new(&path)?,
+            watchdog_interval,
+        }))
+    }
+
+    /// How often to send `WATCHDOG=1` (half of the watchdog timeout), if a watchdog is enabled.
+    pub fn watchdog_interval(&self) -> Option<time::Duration> {
+        self.watchdog_interval
+    }
+
+    /// Sends a state like `READY=1`. Returns a description of the error if that failed.
+    pub fn notify(&self, state: &str) -> std::result::Result<(), String> {
+        self.socket.send(state).map_err(|err| err.to_string())
+    }
+}
@@ -0,0 +1,90 @@
+//! Noticing when the parent process changes, see `--die-with-parent`.
+
+use crate::{platform, Result};
+
+/// How often the parent PID is checked where the kernel does not send a signal.
+#[cfg(all(unix, not(target_os = "linux")))]
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Wakes up the main loop (see `signals::wake`) when the parent process exits and lucid is
+/// reparented. On Linux, the kernel sends a signal for that (`PR_SET_PDEATHSIG`), elsewhere a
+/// background thread polls `getppid`.
+pub struct ParentWatch {
+    parent: u32,
+}
+
+impl ParentWatch {
+    pub fn start() -> Result<ParentWatch> {
+        let watch = ParentWatch {
+            parent: platform::parent_id(),
+        };
+        watch.arm()?;
+        Ok(watch)
+    }
+
+    pub fn parent(&self) -> u32 {
+        self.parent
+    }
+
+    /// Returns the old and the new parent PID if it changed since the last call.
+    pub fn check(&mut self) -> Option<(u32, u32)> {
+        let parent = platform::parent_id();
+        if parent == self.parent {
+            return None;
+        }
+        Some((std::mem::replace(&mut self.parent, parent), parent))
+    }
+
+    /// Requests the parent-death signal. This has to be repeated after switching users, since
+    /// the kernel clears the request then.
+    #[cfg(target_os = "linux")]
+    pub fn arm(&self) -> Result<()> {
+        extern "C" fn wake(_: libc::c_int) {
+            crate::signals::wake();
+        }
+
+        // A real-time signal, since all the others might be claimed by other options
+        let signum = libc::SIGRTMIN();
+        // SAFETY: `action` is fully initialized (all-zero bytes are valid for `sigaction`), and
+        // the handler only calls `signals::wake`, which is async-signal-safe.
+        let result = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = wake as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signum, &action, std::ptr::null_mut()) == 0 {
+                libc::prctl(libc::PR_SET_PDEATHSIG, signum as libc::c_ulong)
+            } else {
+                -1
+            }
+        };
+        if result != 0 {
+            return Err(crate::LucidError::Unsupported("--die-with-parent"));
+        }
+        Ok(())
+    }
+
+    #[cfg(all(unix, not(target_os = "linux")))]
+    pub fn arm(&self) -> Result<()> {
+        use std::sync::Once;
+
+        static POLLER: Once = Once::new();
+        let mut parent = self.parent;
+        POLLER.call_once(move || {
+            std::thread::spawn(move || loop {
+                std::thread::sleep(POLL_INTERVAL);
+                let current = platform::parent_id();
+                if current != parent {
+                    parent = current;
+                    crate::signals::wake();
+                }
+            });
+        });
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn arm(&self) -> Result<()> {
+        Err(crate::LucidError::Unsupported("--die-with-parent"))
+    }
+}
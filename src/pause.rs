@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+/// Keeps track of the time during which the process was suspended (`--exclude-paused` and
+/// `--report-stops`).
+///
+/// SIGSTOP can not be caught, so a pause can only be detected after the fact: the main loop
+/// announces how long it is going to sleep before each cycle. When the next cycle starts after
+/// a SIGCONT was received, the time that passed beyond the announced sleep is counted as
+/// paused. Without a SIGCONT, longer cycles (e.g. on a busy system) are not counted.
+pub struct PauseTracker {
+    last_check: Instant,
+    expected: Duration,
+    paused: Duration,
+    /// Whether pauses are excluded from the sleep, or only detected.
+    exclude: bool,
+}
+
+impl PauseTracker {
+    pub fn new(now: Instant, exclude: bool) -> PauseTracker {
+        PauseTracker {
+            last_check: now,
+            expected: Duration::ZERO,
+            paused: Duration::ZERO,
+            exclude,
+        }
+    }
+
+    /// Announces that the process stops itself right now (after SIGTSTP), such that the
+    /// pause is measured exactly.
+    pub fn stopping(&mut self, now: Instant) {
+        self.last_check = now;
+        self.expected = Duration::ZERO;
+    }
+
+    /// Announces that the next check is expected after `duration`.
+    pub fn expect(&mut self, duration: Duration) {
+        self.expected = duration;
+    }
+
+    /// Called at the start of every cycle. Returns the length of a newly detected pause.
+    pub fn check(&mut self, now: Instant, resumed: bool) -> Option<Duration> {
+        let gap = now.saturating_duration_since(self.last_check);
+        self.last_check = now;
+        if !resumed || gap <= self.expected {
+            return None;
+        }
+        let pause = gap - self.expected;
+        if self.exclude {
+            self.paused += pause;
+        }
+        Some(pause)
+    }
+
+    /// The total time spent suspended so far, which is zero unless pauses are excluded.
+    pub fn paused(&self) -> Duration {
+        self.paused
+    }
+}
+
+#[test]
+fn test_pause_tracker() {
+    let start = Instant::now();
+    let mut tracker = PauseTracker::new(start, true);
+
+    tracker.expect(Duration::from_millis(100));
+    assert_eq!(
+        None,
+        tracker.check(start + Duration::from_millis(100), false)
+    );
+
+    tracker.expect(Duration::from_millis(100));
+    assert_eq!(
+        Some(Duration::from_secs(5)),
+        tracker.check(start + Duration::from_millis(5200), true)
+    );
+
+    // A slow cycle without SIGCONT is not a pause
+    tracker.expect(Duration::from_millis(100));
+    assert_eq!(None, tracker.check(start + Duration::from_secs(7), false));
+    assert_eq!(Duration::from_secs(5), tracker.paused());
+
+    // A sleep of 10s which was paused for 5s ends 5s later
+    let deadline = Duration::from_secs(10);
+    let since_start = Duration::from_secs(7) - tracker.paused();
+    assert_eq!(Duration::from_secs(8), deadline - since_start);
+
+    // Stopping after SIGTSTP is measured from the moment of stopping
+    let mut tracker = PauseTracker::new(start, false);
+    tracker.expect(Duration::from_secs(1));
+    tracker.stopping(start + Duration::from_millis(200));
+    assert_eq!(
+        Some(Duration::from_secs(3)),
+        tracker.check(start + Duration::from_millis(3200), true)
+    );
+    assert_eq!(Duration::ZERO, tracker.paused());
+}
@@ -0,0 +1,58 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::{LucidError, Result};
+
+/// A file containing the PID of this process. The file is removed again when the `Pidfile`
+/// is dropped, such that it also disappears on early returns.
+pub struct Pidfile {
+    path: Option<PathBuf>,
+}
+
+impl Pidfile {
+    /// Writes the PID of the current process (newline-terminated) to `path`.
+    pub fn create(path: &Path) -> Result<Pidfile> {
+        fs::write(path, format!("{}\n", process::id())).map_err(|_| LucidError::PidfileError)?;
+        Ok(Pidfile {
+            path: Some(path.into()),
+        })
+    }
+
+    /// Removes the file, reporting failures (in contrast to dropping the `Pidfile`).
+    pub fn remove(mut self) -> Result<()> {
+        match self.path.take() {
+            Some(path) => fs::remove_file(path).map_err(|_| LucidError::PidfileError),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Pidfile {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.path {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+#[test]
+fn test_pidfile() {
+    let path = crate::TempPath::new("pidfile");
+
+    let pidfile = Pidfile::create(&path).unwrap();
+    assert_eq!(
+        format!("{}\n", std::process::id()),
+        fs::read_to_string(&path).unwrap()
+    );
+    pidfile.remove().unwrap();
+    assert!(!path.exists());
+
+    drop(Pidfile::create(&path).unwrap());
+    assert!(!path.exists());
+
+    assert_eq!(
+        Err(LucidError::PidfileError),
+        Pidfile::create(&path.join("missing")).map(|_| ())
+    );
+}
@@ -0,0 +1,442 @@
+//! Functionality that is only available on some platforms. Elsewhere, the corresponding
+//! options fail with `LucidError::Unsupported`.
+
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::signals::Signal;
+use crate::{ExitCode, LucidError, Result};
+
+/// How to set up the daemon process, see `daemonize`.
+#[derive(Debug, Clone, Default)]
+pub struct DaemonOptions {
+    /// Working directory of the daemon (unchanged if not set).
+    pub chdir: Option<PathBuf>,
+    pub umask: Option<u32>,
+    /// Files to redirect the standard streams to (they are inherited if not set). Output files
+    /// are appended to.
+    pub stdin: Option<PathBuf>,
+    pub stdout: Option<PathBuf>,
+    pub stderr: Option<PathBuf>,
+}
+
+/// Detaches from the controlling terminal and continues in the background, like a classic
+/// SysV daemon: fork, `setsid`, fork again (so that the daemon can never reacquire a
+/// controlling terminal), then apply the `options`. The original process exits.
+#[cfg(unix)]
+pub fn daemonize(options: &DaemonOptions) -> Result<()> {
+    use std::fs::OpenOptions;
+    use std::os::unix::io::AsRawFd;
+
+    use nix::sys::stat::{umask, Mode};
+    use nix::unistd::{chdir, dup2, fork, setsid, ForkResult};
+
+    // Opened first, such that relative paths refer to the original directory
+    let redirects = [
+        (0, &options.stdin, false),
+        (1, &options.stdout, true),
+        (2, &options.stderr, true),
+    ]
+    .into_iter()
+    .filter_map(|(fd, path, write)| {
+        let path = path.as_ref()?;
+        let file = OpenOptions::new()
+            .read(!write)
+            .append(write)
+            .create(write)
+            .open(path);
+        Some(file.map(|file| (fd, file)))
+    })
+    .collect::<io::Result<Vec<_>>>()
+    .map_err(|_| LucidError::FailedToDaemonize)?;
+
+    for session_leader in [true, false] {
+        // SAFETY: no other threads have been started yet, and the parent exits right away.
+        match unsafe { fork() } {
+            Ok(ForkResult::Parent { .. }) => unsafe { libc::_exit(0) },
+            Ok(ForkResult::Child) => {}
+            Err(_) => return Err(LucidError::FailedToDaemonize),
+        }
+        if session_leader {
+            setsid().map_err(|_| LucidError::FailedToDaemonize)?;
+        }
+    }
+
+    if let Some(ref dir) = options.chdir {
+        chdir(dir).map_err(|_| LucidError::FailedToDaemonize)?;
+    }
+    if let Some(mask) = options.umask {
+        umask(Mode::from_bits_truncate(mask as libc::mode_t));
+    }
+    for (fd, file) in redirects {
+        dup2(file.as_raw_fd(), fd).map_err(|_| LucidError::FailedToDaemonize)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn daemonize(_: &DaemonOptions) -> Result<()> {
+    Err(LucidError::Unsupported("--daemon"))
+}
+
+/// Forks and returns the PID of the child in the parent, which is supposed to exit right away,
+/// so that the child is reparented. Returns `None` in the child.
+#[cfg(unix)]
+pub fn fork_orphan() -> Result<Option<i32>> {
+    use nix::unistd::{fork, ForkResult};
+
+    // SAFETY: no other threads have been started yet.
+    match unsafe { fork() } {
+        Ok(ForkResult::Parent { child }) => Ok(Some(child.as_raw())),
+        Ok(ForkResult::Child) => Ok(None),
+        Err(_) => Err(LucidError::FailedToSpawnChildren),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn fork_orphan() -> Result<Option<i32>> {
+    Err(LucidError::Unsupported("--orphan"))
+}
+
+/// The PID of the parent process (0 where this is not available).
+#[cfg(unix)]
+pub fn parent_id() -> u32 {
+    std::os::unix::process::parent_id()
+}
+
+#[cfg(not(unix))]
+pub fn parent_id() -> u32 {
+    0
+}
+
+/// Closes stdin, such that writing to the other end of a pipe fails. `/dev/null` takes its
+/// place, such that file descriptor 0 is not reused for another file.
+#[cfg(unix)]
+pub fn close_stdin() -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let null = File::open("/dev/null").map_err(|_| LucidError::StdinError)?;
+    nix::unistd::dup2(null.as_raw_fd(), 0).map_err(|_| LucidError::StdinError)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn close_stdin() -> Result<()> {
+    Err(LucidError::Unsupported("--stdin-mode close"))
+}
+
+/// Takes ownership of an inherited, open file descriptor.
+#[cfg(unix)]
+pub fn open_fd(fd: i32) -> Result<File> {
+    use nix::fcntl::{fcntl, FcntlArg};
+    use std::os::unix::io::FromRawFd;
+
+    if fcntl(fd, FcntlArg::F_GETFD).is_err() {
+        return Err(LucidError::InvalidArgument("--marker-fd"));
+    }
+    // SAFETY: the descriptor was checked to be open, and the caller takes exclusive ownership.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+pub fn open_fd(_: i32) -> Result<File> {
+    Err(LucidError::Unsupported("--marker-fd"))
+}
+
+/// Makes the command run in the process group `group`, or in a new one (whose ID is the PID
+/// of the child) if `group` is 0.
+#[cfg(unix)]
+pub fn use_process_group(command: &mut Command, group: i32) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    command.process_group(group);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn use_process_group(_: &mut Command, _: i32) -> Result<()> {
+    Err(LucidError::Unsupported("--forward-signals"))
+}
+
+/// Replaces the current process with the command. Only returns if that failed.
+#[cfg(unix)]
+pub fn exec(command: &mut Command) -> io::Result<ExitCode> {
+    use std::os::unix::process::CommandExt;
+
+    Err(command.exec())
+}
+
+/// Runs the command and returns its exit code, since processes can not be replaced here.
+#[cfg(not(unix))]
+pub fn exec(command: &mut Command) -> io::Result<ExitCode> {
+    command.status().map(|status| status.code().unwrap_or(1))
+}
+
+/// Like `exec`, but `signals` are sent to the command right after it replaced this process.
+///
+/// A helper process waits for the exec (when the close-on-exec pipe is closed) to do so. It is
+/// forked twice, such that the command does not inherit it as a zombie.
+#[cfg(unix)]
+pub fn exec_forwarding(command: &mut Command, signals: &[Signal]) -> io::Result<ExitCode> {
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+    use nix::sys::wait::waitpid;
+    use nix::unistd::{close, fork, pipe, ForkResult};
+
+    if signals.is_empty() {
+        return exec(command);
+    }
+
+    let target = nix::unistd::getpid().as_raw();
+    let (read_end, write_end) = pipe()?;
+    fcntl(write_end, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+
+    // SAFETY: the helpers only call the async-signal-safe `fork`, `read`, `kill` and `_exit`.
+    match unsafe { fork() } {
+        Ok(ForkResult::Child) => unsafe {
+            libc::close(write_end);
+            if libc::fork() != 0 {
+                libc::_exit(0);
+            }
+            let mut byte = 0u8;
+            // End of file means that the exec succeeded, a byte that it failed
+            if libc::read(read_end, &mut byte as *mut u8 as *mut libc::c_void, 1) == 0 {
+                for &signal in signals {
+                    libc::kill(target, signal as libc::c_int);
+                }
+            }
+            libc::_exit(0)
+        },
+        Ok(ForkResult::Parent { child }) => {
+            close(read_end).ok();
+            waitpid(child, None).ok();
+            let err = exec(command);
+            // SAFETY: writing a byte from a local buffer to our own pipe.
+            unsafe { libc::write(write_end, b"x".as_ptr() as *const libc::c_void, 1) };
+            close(write_end).ok();
+            err
+        }
+        Err(err) => {
+            close(read_end).ok();
+            close(write_end).ok();
+            Err(err.into())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn exec_forwarding(command: &mut Command, _: &[Signal]) -> io::Result<ExitCode> {
+    exec(command)
+}
+
+/// Creates a command that runs `script` in the system shell.
+#[cfg(unix)]
+pub fn shell_command(script: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script);
+    command
+}
+
+#[cfg(not(unix))]
+pub fn shell_command(script: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(script);
+    command
+}
+
+/// Forks a child process that exits immediately. It stays a zombie until `reap` is called.
+#[cfg(unix)]
+pub fn spawn_zombie() -> Result<i32> {
+    use nix::unistd::{fork, ForkResult};
+
+    // SAFETY: the child only calls the async-signal-safe `_exit`.
+    match unsafe { fork() } {
+        Ok(ForkResult::Parent { child }) => Ok(child.as_raw()),
+        Ok(ForkResult::Child) => unsafe { libc::_exit(0) },
+        Err(_) => Err(LucidError::FailedToSpawnChildren),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn spawn_zombie() -> Result<i32> {
+    Err(LucidError::Unsupported("--zombies"))
+}
+
+/// Waits for the child process `pid` to exit.
+#[cfg(unix)]
+pub fn reap(pid: i32) -> bool {
+    nix::sys::wait::waitpid(nix::unistd::Pid::from_raw(pid), None).is_ok()
+}
+
+#[cfg(not(unix))]
+pub fn reap(_: i32) -> bool {
+    false
+}
+
+/// The exit code of a child process, or 128 + the signal number if it was killed by a signal.
+pub fn exit_code_of(status: std::process::ExitStatus) -> ExitCode {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
+}
+
+/// A child process that exited, see `reap_any`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exited {
+    pub pid: u32,
+    /// The exit code, or 128 + the signal number if the process was killed by a signal.
+    pub code: ExitCode,
+    /// Describes how the process exited, e.g. `exit code 3` or `killed by SIGTERM`.
+    pub status: String,
+}
+
+/// Reaps all child processes that have exited so far (also reparented ones), without blocking.
+#[cfg(unix)]
+pub fn reap_any() -> Vec<Exited> {
+    use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+
+    let mut exited = vec![];
+    loop {
+        match waitpid(nix::unistd::Pid::from_raw(-1), Some(WaitPidFlag::WNOHANG)) {
+            Ok(WaitStatus::Exited(pid, code)) => exited.push(Exited {
+                pid: pid.as_raw() as u32,
+                code,
+                status: format!("exit code {}", code),
+            }),
+            Ok(WaitStatus::Signaled(pid, signal, _)) => exited.push(Exited {
+                pid: pid.as_raw() as u32,
+                code: 128 + signal as i32,
+                status: format!("killed by {}", signal),
+            }),
+            // Nothing left to reap (or no children at all)
+            Ok(WaitStatus::StillAlive) | Err(_) => return exited,
+            Ok(_) => {}
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn reap_any() -> Vec<Exited> {
+    vec![]
+}
+
+/// Makes orphaned descendants get reparented to this process instead of to PID 1. Returns
+/// false where that is not supported.
+#[cfg(target_os = "linux")]
+pub fn become_subreaper() -> bool {
+    // SAFETY: `PR_SET_CHILD_SUBREAPER` only changes a flag of the process.
+    unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1) == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn become_subreaper() -> bool {
+    false
+}
+
+/// The user and group to switch to after startup, see `--user` and `--group`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credentials {
+    /// The user name (if there is a passwd entry) and ID.
+    pub user: Option<(Option<String>, u32)>,
+    /// The group ID, given explicitly or as the primary group of the user.
+    pub gid: Option<u32>,
+}
+
+impl Credentials {
+    /// Looks up users and groups, given as names or numeric IDs.
+    #[cfg(unix)]
+    pub fn lookup(user: Option<&str>, group: Option<&str>) -> Result<Credentials> {
+        use nix::unistd::{Gid, Group, Uid, User};
+
+        let user = user
+            .map(|user| {
+                let entry = match user.parse() {
+                    Ok(uid) => User::from_uid(Uid::from_raw(uid)).ok().flatten(),
+                    Err(_) => User::from_name(user).ok().flatten(),
+                };
+                match (entry, user.parse()) {
+                    (Some(entry), _) => Ok((Some(entry.name), entry.uid.as_raw(), Some(entry.gid))),
+                    (None, Ok(uid)) => Ok((None, uid, None)),
+                    (None, Err(_)) => Err(LucidError::NoSuchUser(user.into())),
+                }
+            })
+            .transpose()?;
+        let gid = match group {
+            Some(group) => Some(match group.parse() {
+                Ok(gid) => gid,
+                Err(_) => Group::from_name(group)
+                    .ok()
+                    .flatten()
+                    .ok_or_else(|| LucidError::NoSuchGroup(group.into()))?
+                    .gid
+                    .as_raw(),
+            }),
+            None => user.as_ref().and_then(|(_, _, gid)| gid.map(Gid::as_raw)),
+        };
+        Ok(Credentials {
+            user: user.map(|(name, uid, _)| (name, uid)),
+            gid,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn lookup(user: Option<&str>, _: Option<&str>) -> Result<Credentials> {
+        Err(LucidError::Unsupported(if user.is_some() {
+            "--user"
+        } else {
+            "--group"
+        }))
+    }
+
+    /// Switches to the group (replacing the supplementary groups) and then to the user. Only
+    /// root can do that, for everyone else it fails unless the IDs are the current ones.
+    #[cfg(unix)]
+    pub fn drop_privileges(&self) -> io::Result<()> {
+        use nix::unistd::{geteuid, setgid, setuid, Gid, Uid};
+        use std::ffi::CString;
+
+        if let Some(gid) = self.gid {
+            if geteuid().is_root() {
+                // SAFETY: the group list and the name are valid for the duration of the calls.
+                let result = unsafe {
+                    match self.user {
+                        Some((Some(ref name), _)) => {
+                            let name = CString::new(name.as_str())?;
+                            libc::initgroups(name.as_ptr(), gid as _)
+                        }
+                        _ => libc::setgroups(1, &gid),
+                    }
+                };
+                if result != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            setgid(Gid::from_raw(gid))?;
+        }
+        if let Some((_, uid)) = self.user {
+            setuid(Uid::from_raw(uid))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn drop_privileges(&self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "switching users is not supported on this platform",
+        ))
+    }
+
+    /// Describes the credentials, e.g. `user nobody (65534), group 65534`.
+    pub fn describe(&self) -> String {
+        let user = self.user.as_ref().map(|(name, uid)| match name {
+            Some(name) => format!("user {} ({})", name, uid),
+            None => format!("user {}", uid),
+        });
+        let group = self.gid.map(|gid| format!("group {}", gid));
+        user.into_iter().chain(group).collect::<Vec<_>>().join(", ")
+    }
+}
@@ -0,0 +1,103 @@
+//! A live countdown on the terminal, see `--progress`.
+
+use std::io::{self, IsTerminal, Write};
+use std::time;
+
+/// How often the countdown is redrawn.
+pub const REDRAW_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+/// Number of characters between the brackets of the bar.
+const WIDTH: usize = 30;
+
+/// Draws a single line on stderr (or stdout) that is overwritten on every update. Nothing else
+/// should be printed while it is shown, so it is cleared before other output.
+pub struct Countdown {
+    shown: bool,
+    on_stdout: bool,
+}
+
+impl Countdown {
+    /// Returns `None` if stderr is not a TTY.
+    pub fn new() -> Option<Countdown> {
+        io::stderr().is_terminal().then_some(Countdown {
+            shown: false,
+            on_stdout: false,
+        })
+    }
+
+    /// Like `new`, but draws on stdout (see `--countdown`).
+    pub fn on_stdout() -> Option<Countdown> {
+        io::stdout().is_terminal().then_some(Countdown {
+            shown: false,
+            on_stdout: true,
+        })
+    }
+
+    /// Shows `fraction` (between 0 and 1) of a sleep as done, or only `text` without a
+    /// fraction.
+    pub fn draw(&mut self, fraction: Option<f64>, text: &str) {
+        self.write(&format!("\r{}\x1b[K", render(fraction, text)));
+        self.shown = true;
+    }
+
+    /// Draws `text` a last time and ends the line, such that it stays visible.
+    pub fn finish(&mut self, text: &str) {
+        self.write(&format!("\r{}\x1b[K\n", text));
+        self.shown = false;
+    }
+
+    pub fn clear(&mut self) {
+        if self.shown {
+            self.write("\r\x1b[K");
+            self.shown = false;
+        }
+    }
+
+    fn write(&self, text: &str) {
+        let mut stream: Box<dyn Write> = if self.on_stdout {
+            Box::new(io::stdout())
+        } else {
+            Box::new(io::stderr())
+        };
+        write!(stream, "{}", text).ok();
+        stream.flush().ok();
+    }
+}
+
+impl Drop for Countdown {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Renders a line like `[=======>          ]  25% 7.500s remaining`.
+fn render(fraction: Option<f64>, text: &str) -> String {
+    let Some(fraction) = fraction else {
+        return text.to_string();
+    };
+    let fraction = fraction.clamp(0.0, 1.0);
+    let done = (fraction * WIDTH as f64).floor() as usize;
+    let bar = if done == WIDTH {
+        "=".repeat(WIDTH)
+    } else {
+        format!("{}>{}", "=".repeat(done), " ".repeat(WIDTH - done - 1))
+    };
+    format!("[{}] {:>3}% {}", bar, (fraction * 100.0).floor(), text)
+}
+
+#[test]
+fn test_render() {
+    assert_eq!(
+        format!("[>{}]   0% 10s remaining", " ".repeat(WIDTH - 1)),
+        render(Some(0.0), "10s remaining")
+    );
+    assert_eq!(
+        format!("[{}>{}]  50% 5s", "=".repeat(15), " ".repeat(14)),
+        render(Some(0.5), "5s")
+    );
+    assert_eq!(
+        format!("[{}] 100% 0s", "=".repeat(WIDTH)),
+        render(Some(1.2), "0s")
+    );
+    assert_eq!("Sleeping for 3s", render(None, "Sleeping for 3s"));
+}
@@ -0,0 +1,210 @@
+//! Resource limits that lucid applies to itself, see `--rlimit`.
+
+use std::io;
+
+use crate::memory::parse_size;
+use crate::{LucidError, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resource {
+    /// CPU time in seconds.
+    Cpu,
+    /// Size of created files in bytes.
+    Fsize,
+    /// Size of the data segment in bytes.
+    Data,
+    /// Size of the stack in bytes.
+    Stack,
+    /// Size of core dumps in bytes.
+    Core,
+    /// Number of open file descriptors.
+    Nofile,
+    /// Size of the address space in bytes.
+    As,
+}
+
+impl Resource {
+    pub const ALL: [Resource; 7] = [
+        Resource::Cpu,
+        Resource::Fsize,
+        Resource::Data,
+        Resource::Stack,
+        Resource::Core,
+        Resource::Nofile,
+        Resource::As,
+    ];
+
+    pub fn parse(name: &str) -> Option<Resource> {
+        match name.to_ascii_uppercase().trim_start_matches("RLIMIT_") {
+            "CPU" => Some(Resource::Cpu),
+            "FSIZE" => Some(Resource::Fsize),
+            "DATA" => Some(Resource::Data),
+            "STACK" => Some(Resource::Stack),
+            "CORE" => Some(Resource::Core),
+            "NOFILE" => Some(Resource::Nofile),
+            "AS" => Some(Resource::As),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Resource::Cpu => "RLIMIT_CPU",
+            Resource::Fsize => "RLIMIT_FSIZE",
+            Resource::Data => "RLIMIT_DATA",
+            Resource::Stack => "RLIMIT_STACK",
+            Resource::Core => "RLIMIT_CORE",
+            Resource::Nofile => "RLIMIT_NOFILE",
+            Resource::As => "RLIMIT_AS",
+        }
+    }
+
+    /// Whether the limit is a size, which may be given with a unit like `256M`.
+    fn is_size(self) -> bool {
+        !matches!(self, Resource::Cpu | Resource::Nofile)
+    }
+
+    /// Renders a limit for messages, e.g. `256.0 MiB` or `unlimited`.
+    pub fn format(self, limit: Option<u64>) -> String {
+        match limit {
+            None => "unlimited".into(),
+            Some(value) if self.is_size() => crate::memory::size_as_str(value),
+            Some(value) => value.to_string(),
+        }
+    }
+
+    /// The current soft and hard limit.
+    #[cfg(unix)]
+    pub fn current(self) -> io::Result<(Option<u64>, Option<u64>)> {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `limit` is a valid `rlimit` struct that outlives the call.
+        if unsafe { libc::getrlimit(self.id() as _, &mut limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((from_raw(limit.rlim_cur), from_raw(limit.rlim_max)))
+    }
+
+    #[cfg(unix)]
+    fn id(self) -> i32 {
+        (match self {
+            Resource::Cpu => libc::RLIMIT_CPU,
+            Resource::Fsize => libc::RLIMIT_FSIZE,
+            Resource::Data => libc::RLIMIT_DATA,
+            Resource::Stack => libc::RLIMIT_STACK,
+            Resource::Core => libc::RLIMIT_CORE,
+            Resource::Nofile => libc::RLIMIT_NOFILE,
+            Resource::As => libc::RLIMIT_AS,
+        }) as i32
+    }
+}
+
+/// A soft limit and, optionally, a new hard limit; `None` stands for no limit at all.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rlimit {
+    pub resource: Resource,
+    pub soft: Option<u64>,
+    pub hard: Option<Option<u64>>,
+}
+
+impl Rlimit {
+    /// Parses a comma-separated list like `NOFILE=64,CPU=2,AS=256M`. A hard limit can be given
+    /// after a colon, e.g. `NOFILE=64:128`.
+    pub fn parse_list(input: &str) -> Result<Vec<Rlimit>> {
+        input
+            .split(',')
+            .map(|item| {
+                let (name, value) = item.split_once('=').ok_or(LucidError::RlimitParseError)?;
+                let resource = Resource::parse(name.trim()).ok_or(LucidError::RlimitParseError)?;
+                let limit = |value: &str| match value.trim() {
+                    "unlimited" | "infinity" => Ok(None),
+                    value if resource.is_size() => parse_size(value)
+                        .map(Some)
+                        .map_err(|_| LucidError::RlimitParseError),
+                    value => value
+                        .parse()
+                        .map(Some)
+                        .map_err(|_| LucidError::RlimitParseError),
+                };
+                let (soft, hard) = match value.split_once(':') {
+                    Some((soft, hard)) => (limit(soft)?, Some(limit(hard)?)),
+                    None => (limit(value)?, None),
+                };
+                Ok(Rlimit {
+                    resource,
+                    soft,
+                    hard,
+                })
+            })
+            .collect()
+    }
+
+    /// Sets the limit for this process. Returns the hard limit that is in effect afterwards.
+    #[cfg(unix)]
+    pub fn apply(&self) -> io::Result<Option<u64>> {
+        let to_raw = |limit: Option<u64>| limit.map_or(libc::RLIM_INFINITY, |v| v as libc::rlim_t);
+        let mut current = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `current` is a valid `rlimit` struct that outlives both calls.
+        unsafe {
+            if libc::getrlimit(self.resource.id() as _, &mut current) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            current.rlim_cur = to_raw(self.soft);
+            if let Some(hard) = self.hard {
+                current.rlim_max = to_raw(hard);
+            }
+            if libc::setrlimit(self.resource.id() as _, &current) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(from_raw(current.rlim_max))
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply(&self) -> io::Result<Option<u64>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "resource limits are not supported on this platform",
+        ))
+    }
+}
+
+#[cfg(unix)]
+fn from_raw(limit: libc::rlim_t) -> Option<u64> {
+    // `rlim_t` is not `u64` on every platform
+    #[allow(clippy::unnecessary_cast)]
+    (limit != libc::RLIM_INFINITY).then_some(limit as u64)
+}
+
+#[test]
+fn test_parse_list() {
+    assert_eq!(
+        Ok(vec![
+            Rlimit {
+                resource: Resource::Nofile,
+                soft: Some(64),
+                hard: None
+            },
+            Rlimit {
+                resource: Resource::Cpu,
+                soft: Some(2),
+                hard: Some(None)
+            },
+            Rlimit {
+                resource: Resource::As,
+                soft: Some(256 << 20),
+                hard: None
+            },
+        ]),
+        Rlimit::parse_list("NOFILE=64,cpu=2:unlimited,RLIMIT_AS=256M")
+    );
+    assert!(Rlimit::parse_list("NOFILE").is_err());
+    assert!(Rlimit::parse_list("NOFILE=64K").is_err());
+    assert!(Rlimit::parse_list("NICE=1").is_err());
+    assert!(Rlimit::parse_list("").is_err());
+}
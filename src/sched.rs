@@ -0,0 +1,103 @@
+//! Scheduling settings that lucid applies to itself, see `--nice` and `--cpu-affinity`.
+
+use std::io;
+
+use crate::{LucidError, Result};
+
+/// Parses a list of CPUs like `0,2-3` (sorted and without duplicates).
+pub fn parse_cpu_list(input: &str) -> Result<Vec<usize>> {
+    let mut cpus = vec![];
+    for item in input.split(',') {
+        let range = match item.trim().split_once('-') {
+            Some((first, last)) => (first.trim().parse::<usize>(), last.trim().parse()),
+            None => (item.trim().parse(), item.trim().parse()),
+        };
+        match range {
+            (Ok(first), Ok(last)) if first <= last => cpus.extend(first..=last),
+            _ => return Err(LucidError::InvalidArgument("--cpu-affinity")),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+/// Renders CPUs as a list like `0,2-3`, the inverse of `parse_cpu_list`.
+pub fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for &cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == cpu => *last = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}-{}", first, last)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Sets the nice value of this process. On Linux, this only applies to the calling thread (and
+/// the threads and processes it creates afterwards), so it is done before anything is spawned.
+#[cfg(unix)]
+pub fn set_nice(nice: i32) -> io::Result<()> {
+    // SAFETY: `setpriority` has no memory-safety preconditions.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn set_nice(_: i32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "nice values are not supported on this platform",
+    ))
+}
+
+/// Restricts this process to the given CPUs. Returns the CPUs it may run on afterwards.
+#[cfg(target_os = "linux")]
+pub fn set_cpu_affinity(cpus: &[usize]) -> io::Result<Vec<usize>> {
+    use nix::sched::{sched_getaffinity, sched_setaffinity, CpuSet};
+    use nix::unistd::Pid;
+
+    let mut cpu_set = CpuSet::new();
+    for &cpu in cpus {
+        cpu_set.set(cpu)?;
+    }
+    sched_setaffinity(Pid::from_raw(0), &cpu_set)?;
+    let cpu_set = sched_getaffinity(Pid::from_raw(0))?;
+    Ok((0..CpuSet::count())
+        .filter(|&cpu| cpu_set.is_set(cpu).unwrap_or(false))
+        .collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_cpu_affinity(_: &[usize]) -> io::Result<Vec<usize>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "CPU affinity is not supported on this platform",
+    ))
+}
+
+#[test]
+fn test_cpu_list() {
+    assert_eq!(Ok(vec![0, 2, 3]), parse_cpu_list("0,2-3"));
+    assert_eq!(Ok(vec![1, 2, 3, 7]), parse_cpu_list("7, 1-3,2"));
+    assert_eq!(Ok(vec![5]), parse_cpu_list("5-5"));
+    assert!(parse_cpu_list("3-1").is_err());
+    assert!(parse_cpu_list("0,").is_err());
+    assert!(parse_cpu_list("a").is_err());
+
+    assert_eq!("0,2-3", format_cpu_list(&[0, 2, 3]));
+    assert_eq!("1-3,7", format_cpu_list(&[1, 2, 3, 7]));
+    assert_eq!("", format_cpu_list(&[]));
+}
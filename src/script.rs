@@ -0,0 +1,187 @@
+use std::time;
+
+use crate::{parse_duration, ExitCode, LucidError, Result};
+
+/// Output of a script step.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Print(String),
+    Stderr(String),
+}
+
+/// A timeline of actions, see `--script`. Steps are separated by `;` or newlines:
+///
+/// * `sleep DURATION` waits before the next step,
+/// * `print TEXT` and `stderr TEXT` write a line to stdout or stderr,
+/// * `exit CODE` ends the run with the given exit code (later steps are never executed).
+///
+/// `TEXT` can be quoted (`"..."`, with `\"`, `\\` and `\n` escapes) to include `;`.
+/// Lines starting with `#` are comments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    /// Actions along with the time after the start at which they happen.
+    actions: Vec<(time::Duration, Action)>,
+    duration: time::Duration,
+    exit_code: Option<ExitCode>,
+}
+
+/// Splits the script into its steps, keeping separators inside quotes.
+fn split_steps(script: &str) -> Vec<String> {
+    let mut steps = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in script.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' | '\n' if !quoted => {
+                steps.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    steps.push(current);
+    steps
+}
+
+fn parse_text(text: &str) -> Option<String> {
+    let inner = match text.strip_prefix('"') {
+        None => return Some(text.into()),
+        Some(rest) => rest.strip_suffix('"')?,
+    };
+    let mut unescaped = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => unescaped.push('\n'),
+            c @ ('"' | '\\') => unescaped.push(c),
+            _ => return None,
+        }
+    }
+    Some(unescaped)
+}
+
+impl Script {
+    pub fn parse(script: &str) -> Result<Script> {
+        let mut actions = vec![];
+        let mut offset = time::Duration::from_secs(0);
+        let mut exit_code = None;
+
+        for step in split_steps(script) {
+            let step = step.trim();
+            if step.is_empty() || step.starts_with('#') {
+                continue;
+            }
+            let invalid = || LucidError::ScriptError(step.into());
+            let (command, argument) = step.split_once(char::is_whitespace).unwrap_or((step, ""));
+            let argument = argument.trim();
+            match command {
+                "sleep" => {
+                    offset = parse_duration(argument)
+                        .ok()
+                        .and_then(|duration| offset.checked_add(duration))
+                        .ok_or_else(invalid)?
+                }
+                "print" => actions.push((
+                    offset,
+                    Action::Print(parse_text(argument).ok_or_else(invalid)?),
+                )),
+                "stderr" => actions.push((
+                    offset,
+                    Action::Stderr(parse_text(argument).ok_or_else(invalid)?),
+                )),
+                "exit" => {
+                    exit_code = Some(argument.parse().map_err(|_| invalid())?);
+                    break;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(Script {
+            actions,
+            duration: offset,
+            exit_code,
+        })
+    }
+
+    /// The time until the last step.
+    pub fn duration(&self) -> time::Duration {
+        self.duration
+    }
+
+    pub fn exit_code(&self) -> Option<ExitCode> {
+        self.exit_code
+    }
+
+    /// The actions that are due after `elapsed`, starting with action number `next`.
+    pub fn due(&self, next: usize, elapsed: time::Duration) -> &[(time::Duration, Action)] {
+        let due = self.actions[next..]
+            .iter()
+            .take_while(|(offset, _)| *offset <= elapsed)
+            .count();
+        &self.actions[next..next + due]
+    }
+
+    /// The time at which action number `next` is due, if there is one.
+    pub fn next_due(&self, next: usize) -> Option<time::Duration> {
+        self.actions.get(next).map(|&(offset, _)| offset)
+    }
+}
+
+#[test]
+fn test_parse_script() {
+    let script = Script::parse(
+        "sleep 2; print \"ready; set\"\nstderr warning\n# comment\nsleep 3; exit 7; print never",
+    )
+    .unwrap();
+    assert_eq!(time::Duration::from_secs(5), script.duration());
+    assert_eq!(Some(7), script.exit_code());
+    assert_eq!(
+        vec![
+            (
+                time::Duration::from_secs(2),
+                Action::Print("ready; set".into())
+            ),
+            (
+                time::Duration::from_secs(2),
+                Action::Stderr("warning".into())
+            ),
+        ],
+        script.actions
+    );
+
+    assert_eq!(0, script.due(0, time::Duration::from_secs(1)).len());
+    assert_eq!(2, script.due(0, time::Duration::from_secs(2)).len());
+    assert_eq!(1, script.due(1, time::Duration::from_secs(2)).len());
+    assert_eq!(None, script.next_due(2));
+
+    assert_eq!(
+        Ok(Action::Print("a \"b\"\n".into())),
+        Script::parse(r#"print "a \"b\"\n""#).map(|s| s.actions[0].1.clone())
+    );
+    assert_eq!(
+        Err(LucidError::ScriptError("jump 3".into())),
+        Script::parse("sleep 1; jump 3")
+    );
+    assert_eq!(
+        Err(LucidError::ScriptError("sleep soon".into())),
+        Script::parse("sleep soon")
+    );
+    assert_eq!(
+        Err(LucidError::ScriptError("sleep 1e19".into())),
+        Script::parse("sleep 1e19; sleep 1e19")
+    );
+    assert_eq!(
+        Err(LucidError::ScriptError("print \"open".into())),
+        Script::parse("print \"open")
+    );
+}
@@ -0,0 +1,278 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{ArgMatches, ValueSource};
+
+use crate::{ExitCode, LucidError, Result, VerbosityLevel};
+
+/// Location of the system-wide configuration file.
+const SYSTEM_CONFIG_PATH: &str = "/etc/lucid/config";
+
+/// Configuration keys and the environment variables that can be used to set them. `verbose`
+/// and `quiet` are boolean shorthands for `verbosity`. They are applied in this order, so
+/// quiet wins if both are set.
+pub const ENVIRONMENT_VARIABLES: &[(&str, &str)] = &[
+    ("prefix", "LUCID_PREFIX"),
+    ("exit-code", "LUCID_EXIT_CODE"),
+    ("verbosity", "LUCID_VERBOSITY"),
+    ("verbose", "LUCID_VERBOSE"),
+    ("quiet", "LUCID_QUIET"),
+    ("stderr", "LUCID_STDERR"),
+    ("no-interrupt", "LUCID_NO_INTERRUPT"),
+];
+
+/// Where the value of a setting came from. Later sources override earlier ones.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Default,
+    SystemConfig(PathBuf),
+    UserConfig(PathBuf),
+    Environment(&'static str),
+    CommandLine,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::SystemConfig(path) => write!(f, "system config '{}'", path.display()),
+            Source::UserConfig(path) => write!(f, "user config '{}'", path.display()),
+            Source::Environment(name) => write!(f, "environment variable {}", name),
+            Source::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T, source: Source) -> Setting<T> {
+        Setting { value, source }
+    }
+}
+
+/// The effective settings, merged from (in this order) built-in defaults, the system
+/// config file, the user config file, `LUCID_*` environment variables and the command line.
+///
+/// Config files consist of `key = value` lines, where `#` starts a comment. The keys are the
+/// ones listed in `ENVIRONMENT_VARIABLES`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub prefix: Setting<String>,
+    pub exit_code: Setting<ExitCode>,
+    pub verbosity: Setting<VerbosityLevel>,
+    pub stderr: Setting<bool>,
+    pub no_interrupt: Setting<bool>,
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses `quiet`, `normal` or `verbose`.
+pub fn parse_verbosity(value: &str) -> Option<VerbosityLevel> {
+    match value {
+        "quiet" => Some(VerbosityLevel::Quiet),
+        "normal" => Some(VerbosityLevel::Normal),
+        "verbose" => Some(VerbosityLevel::Verbose),
+        _ => None,
+    }
+}
+
+fn verbosity_name(verbosity: VerbosityLevel) -> &'static str {
+    match verbosity {
+        VerbosityLevel::Quiet => "quiet",
+        VerbosityLevel::Normal => "normal",
+        VerbosityLevel::Verbose => "verbose",
+    }
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("lucid").join("config"))
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            prefix: Setting::new("lucid".into(), Source::Default),
+            exit_code: Setting::new(0, Source::Default),
+            verbosity: Setting::new(VerbosityLevel::Normal, Source::Default),
+            stderr: Setting::new(false, Source::Default),
+            no_interrupt: Setting::new(false, Source::Default),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(matches: &ArgMatches) -> Result<Settings> {
+        let mut settings = Settings::default();
+
+        let system_config = PathBuf::from(SYSTEM_CONFIG_PATH);
+        settings.apply_file(&system_config, Source::SystemConfig(system_config.clone()))?;
+        if let Some(user_config) = user_config_path() {
+            settings.apply_file(&user_config, Source::UserConfig(user_config.clone()))?;
+        }
+        settings.apply_environment()?;
+        settings.apply_command_line(matches);
+
+        Ok(settings)
+    }
+
+    /// Sets the value for `key`. Returns `None` if the key is unknown or the value is invalid.
+    fn set(&mut self, key: &str, value: &str, source: Source) -> Option<()> {
+        match key {
+            "prefix" => self.prefix = Setting::new(value.into(), source),
+            "exit-code" => self.exit_code = Setting::new(value.parse().ok()?, source),
+            "verbosity" => self.verbosity = Setting::new(parse_verbosity(value)?, source),
+            "verbose" => {
+                if parse_bool(value)? {
+                    self.verbosity = Setting::new(VerbosityLevel::Verbose, source);
+                }
+            }
+            "quiet" => {
+                if parse_bool(value)? {
+                    self.verbosity = Setting::new(VerbosityLevel::Quiet, source);
+                }
+            }
+            "stderr" => self.stderr = Setting::new(parse_bool(value)?, source),
+            "no-interrupt" => self.no_interrupt = Setting::new(parse_bool(value)?, source),
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn apply_file(&mut self, path: &Path, source: Source) -> Result<()> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) if !path.exists() => return Ok(()),
+            Err(_) => return Err(LucidError::ConfigFileError(path.into(), 0)),
+        };
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            line.split_once('=')
+                .and_then(|(key, value)| self.set(key.trim(), value.trim(), source.clone()))
+                .ok_or_else(|| LucidError::ConfigFileError(path.into(), index + 1))?;
+        }
+        Ok(())
+    }
+
+    fn apply_environment(&mut self) -> Result<()> {
+        for &(key, name) in ENVIRONMENT_VARIABLES {
+            if let Ok(value) = env::var(name) {
+                self.set(key, &value, Source::Environment(name))
+                    .ok_or(LucidError::InvalidEnvironmentVariable(name))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_command_line(&mut self, matches: &ArgMatches) {
+        if matches.value_source("prefix") == Some(ValueSource::CommandLine) {
+            if let Some(prefix) = matches.value_of("prefix") {
+                self.prefix = Setting::new(prefix.into(), Source::CommandLine);
+            }
+        }
+        if matches.value_source("exit-code") == Some(ValueSource::CommandLine) {
+            let exit_code = matches
+                .value_of("exit-code")
+                .and_then(|c| c.parse::<i32>().ok())
+                .unwrap_or(0i32);
+            self.exit_code = Setting::new(exit_code, Source::CommandLine);
+        }
+        if matches.is_present("verbose") {
+            self.verbosity = Setting::new(VerbosityLevel::Verbose, Source::CommandLine);
+        } else if matches.is_present("quiet") {
+            self.verbosity = Setting::new(VerbosityLevel::Quiet, Source::CommandLine);
+        }
+        if matches.is_present("stderr") {
+            self.stderr = Setting::new(true, Source::CommandLine);
+        }
+        if matches.is_present("no-interrupt") {
+            self.no_interrupt = Setting::new(true, Source::CommandLine);
+        }
+    }
+
+    /// Returns the source of the setting with the given key.
+    pub fn source(&self, key: &str) -> Option<&Source> {
+        let key = match key {
+            "verbose" | "quiet" => "verbosity",
+            key => key,
+        };
+        self.describe()
+            .into_iter()
+            .find(|(k, _, _)| *k == key)
+            .map(|(_, _, source)| source)
+    }
+
+    /// Lists all settings as `(key, value, source)`.
+    pub fn describe(&self) -> Vec<(&'static str, String, &Source)> {
+        vec![
+            (
+                "prefix",
+                format!("\"{}\"", self.prefix.value),
+                &self.prefix.source,
+            ),
+            (
+                "exit-code",
+                self.exit_code.value.to_string(),
+                &self.exit_code.source,
+            ),
+            (
+                "verbosity",
+                verbosity_name(self.verbosity.value).into(),
+                &self.verbosity.source,
+            ),
+            ("stderr", self.stderr.value.to_string(), &self.stderr.source),
+            (
+                "no-interrupt",
+                self.no_interrupt.value.to_string(),
+                &self.no_interrupt.source,
+            ),
+        ]
+    }
+}
+
+#[test]
+fn test_config_file() {
+    let path = crate::TempPath::new("config");
+    fs::write(
+        &path,
+        "# comment\nprefix = worker # trailing comment\n\nverbosity=quiet\nno-interrupt = yes\n",
+    )
+    .unwrap();
+
+    let mut settings = Settings::default();
+    settings
+        .apply_file(&path, Source::UserConfig(path.to_path_buf()))
+        .unwrap();
+    assert_eq!(
+        Setting::new("worker".to_string(), Source::UserConfig(path.to_path_buf())),
+        settings.prefix
+    );
+    assert_eq!(VerbosityLevel::Quiet, settings.verbosity.value);
+    assert!(settings.no_interrupt.value);
+    assert_eq!(Setting::new(0, Source::Default), settings.exit_code);
+
+    fs::write(&path, "prefix = a\nexit-code = x\n").unwrap();
+    assert_eq!(
+        Err(LucidError::ConfigFileError(path.to_path_buf(), 2)),
+        settings.apply_file(&path, Source::UserConfig(path.to_path_buf()))
+    );
+}
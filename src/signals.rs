@@ -0,0 +1,688 @@
+use std::fmt;
+
+use crate::{ExitCode, LucidError, Result};
+
+/// A received termination signal (SIGINT, SIGTERM or SIGHUP, or a console event like Ctrl-C
+/// on Windows).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Termination {
+    /// The signal, unless the platform does not have signals.
+    pub signal: Option<Signal>,
+    /// The sending process, if known.
+    pub sender: Option<Pid>,
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.signal {
+            Some(signal) => write!(f, "{}", signal)?,
+            None => write!(f, "Ctrl-C")?,
+        }
+        if let Some(sender) = self.sender {
+            write!(f, " from PID {}", sender)?;
+        }
+        Ok(())
+    }
+}
+
+impl Termination {
+    /// The conventional exit code of a process that was killed by this signal (128 + signal
+    /// number). Ctrl-C counts as SIGINT.
+    pub fn exit_code(&self) -> ExitCode {
+        128 + self.signal.map_or(2, number)
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+    use std::{thread, time};
+
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
+    use nix::libc::{self, c_int, c_void, siginfo_t};
+    use nix::poll::{poll, PollFd, PollFlags};
+    pub use nix::sys::signal::Signal;
+    use nix::sys::signal::{self, sigaction, SaFlags, SigAction, SigHandler, SigSet};
+    pub use nix::unistd::Pid;
+
+    use super::Termination;
+    use crate::{LucidError, Result};
+
+    /// Both ends of the self-pipe that wakes up `wait` (-1 until it is created).
+    static WAKE_PIPE: [AtomicI32; 2] = [const { AtomicI32::new(-1) }; 2];
+
+    /// The most recently received signal that was installed via `install_handlers` (0 if none).
+    static RECEIVED: AtomicI32 = AtomicI32::new(0);
+
+    /// The number of status requests (SIGUSR1) that have not been handled yet.
+    static STATUS_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+    /// The number of SIGUSR2 deliveries, which toggle verbose output, not handled yet.
+    static VERBOSITY_TOGGLES: AtomicUsize = AtomicUsize::new(0);
+
+    /// The number of SIGHUP deliveries (see `install_hangup_handler`) not handled yet.
+    static HANGUPS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Whether SIGCONT was received since the last call to `take_resumed`.
+    static RESUMED: AtomicBool = AtomicBool::new(false);
+
+    /// Whether SIGTSTP was received since the last call to `take_stop_request`.
+    static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+    /// Per signal number, how often it was received and ignored (see `install_ignore_handlers`).
+    static IGNORED: [AtomicUsize; 65] = [const { AtomicUsize::new(0) }; 65];
+
+    /// The termination signals and, per signal, how often it was received and the PID of the
+    /// most recent sender (0 if unknown).
+    const TERMINATION_SIGNALS: [Signal; 3] = [Signal::SIGINT, Signal::SIGTERM, Signal::SIGHUP];
+    static TERMINATIONS: [AtomicUsize; 3] = [const { AtomicUsize::new(0) }; 3];
+    static SENDERS: [AtomicI32; 3] = [const { AtomicI32::new(0) }; 3];
+
+    /// Per signal number, how often it was received by any of the handlers (except SIGCHLD),
+    /// see `received_counts`.
+    static DELIVERED: [AtomicUsize; 65] = [const { AtomicUsize::new(0) }; 65];
+
+    fn count_delivery(signum: c_int) {
+        if let Some(count) = DELIVERED.get(signum as usize) {
+            count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    extern "C" fn record_signal(signum: c_int) {
+        count_delivery(signum);
+        RECEIVED.store(signum, Ordering::SeqCst);
+        wake();
+    }
+
+    extern "C" fn record_status_request(signum: c_int) {
+        count_delivery(signum);
+        STATUS_REQUESTS.fetch_add(1, Ordering::SeqCst);
+        wake();
+    }
+
+    extern "C" fn record_verbosity_toggle(signum: c_int) {
+        count_delivery(signum);
+        VERBOSITY_TOGGLES.fetch_add(1, Ordering::SeqCst);
+        wake();
+    }
+
+    extern "C" fn record_hangup(signum: c_int) {
+        count_delivery(signum);
+        HANGUPS.fetch_add(1, Ordering::SeqCst);
+        wake();
+    }
+
+    extern "C" fn record_child_exit(_: c_int) {
+        wake();
+    }
+
+    extern "C" fn record_resume(signum: c_int) {
+        count_delivery(signum);
+        RESUMED.store(true, Ordering::SeqCst);
+        wake();
+    }
+
+    extern "C" fn record_stop_request(signum: c_int) {
+        count_delivery(signum);
+        STOP_REQUESTED.store(true, Ordering::SeqCst);
+        wake();
+    }
+
+    extern "C" fn record_termination(signum: c_int, info: *mut siginfo_t, _: *mut c_void) {
+        count_delivery(signum);
+        if let Some(index) = TERMINATION_SIGNALS
+            .iter()
+            .position(|&s| s as c_int == signum)
+        {
+            // SAFETY: with SA_SIGINFO, the kernel passes a valid `siginfo_t` for this signal.
+            let sender = unsafe { info.as_ref().map_or(0, |info| info.si_pid()) };
+            SENDERS[index].store(sender, Ordering::SeqCst);
+            TERMINATIONS[index].fetch_add(1, Ordering::SeqCst);
+        }
+        wake();
+    }
+
+    extern "C" fn record_ignored(signum: c_int) {
+        count_delivery(signum);
+        if let Some(count) = IGNORED.get(signum as usize) {
+            count.fetch_add(1, Ordering::SeqCst);
+        }
+        wake();
+    }
+
+    /// Makes `wait` return, e.g. from another thread. Only calls `write`, which is
+    /// async-signal-safe.
+    pub fn wake() {
+        let fd = WAKE_PIPE[1].load(Ordering::SeqCst);
+        if fd >= 0 {
+            // SAFETY: writes one byte from a valid buffer. If the pipe is full, `wait` is going
+            // to return anyway.
+            unsafe { libc::write(fd, [1u8].as_ptr() as *const c_void, 1) };
+        }
+    }
+
+    /// Creates the self-pipe for `wait`, unless that has already happened.
+    fn create_wake_pipe() -> Result<()> {
+        if WAKE_PIPE[0].load(Ordering::SeqCst) >= 0 {
+            return Ok(());
+        }
+        let (read, write) = nix::unistd::pipe().map_err(|_| LucidError::InvalidSignal)?;
+        for fd in [read, write] {
+            fcntl(fd, FcntlArg::F_SETFL(OFlag::O_NONBLOCK))
+                .and_then(|_| fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)))
+                .map_err(|_| LucidError::InvalidSignal)?;
+        }
+        WAKE_PIPE[0].store(read, Ordering::SeqCst);
+        WAKE_PIPE[1].store(write, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Blocks until one of the handlers installed here runs, or until `timeout` (forever if
+    /// not set) has passed. May also return early for other reasons, so callers have to check
+    /// the time themselves.
+    pub fn wait(timeout: Option<time::Duration>) {
+        let fd = WAKE_PIPE[0].load(Ordering::SeqCst);
+        if fd < 0 {
+            thread::sleep(timeout.unwrap_or(time::Duration::from_millis(100)));
+            return;
+        }
+
+        // A timeout too long to be represented as a deadline is as good as none
+        let deadline = timeout.and_then(|timeout| time::Instant::now().checked_add(timeout));
+        // poll only supports whole milliseconds, the rest is slept below
+        let millis = deadline.and(timeout).map_or(-1, |timeout| {
+            timeout.as_millis().min(i32::MAX as u128) as i32
+        });
+        let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+        match poll(&mut fds, millis) {
+            Ok(0) => {
+                if let Some(deadline) = deadline {
+                    thread::sleep(deadline.saturating_duration_since(time::Instant::now()));
+                }
+            }
+            Ok(_) => {
+                let mut buffer = [0u8; 64];
+                while nix::unistd::read(fd, &mut buffer).is_ok_and(|n| n > 0) {}
+            }
+            // Interrupted by a signal
+            Err(_) => {}
+        }
+    }
+
+    /// How often each signal was received so far (by signal number, leaving out signals that
+    /// were not received), e.g. `[("SIGUSR1", 2), ("SIGTERM", 1)]`.
+    pub fn received_counts() -> Vec<(String, usize)> {
+        DELIVERED
+            .iter()
+            .enumerate()
+            .filter_map(|(signum, count)| {
+                let count = count.load(Ordering::SeqCst);
+                let name = match Signal::try_from(signum as c_int) {
+                    Ok(signal) => signal.as_str().to_string(),
+                    Err(_) => format!("signal {}", signum),
+                };
+                (count > 0).then_some((name, count))
+            })
+            .collect()
+    }
+
+    /// Parses a signal name like `SIGUSR1`, `usr1` or a signal number like `10`.
+    pub fn parse_signal(name: &str) -> Option<Signal> {
+        if let Ok(number) = name.parse::<i32>() {
+            return Signal::try_from(number).ok();
+        }
+        let name = name.to_uppercase();
+        if name.starts_with("SIG") {
+            Signal::from_str(&name).ok()
+        } else {
+            Signal::from_str(&format!("SIG{}", name)).ok()
+        }
+    }
+
+    fn install(signal: Signal, handler: extern "C" fn(c_int)) -> Result<()> {
+        let action = SigAction::new(
+            SigHandler::Handler(handler),
+            SaFlags::SA_RESTART,
+            SigSet::empty(),
+        );
+        // SAFETY: the handlers only perform atomic operations, which are async-signal-safe.
+        unsafe { sigaction(signal, &action) }.map_err(|_| LucidError::InvalidSignal)?;
+        Ok(())
+    }
+
+    /// Installs the handler for SIGINT, SIGTERM and SIGHUP, see `take_terminations`.
+    pub fn install_termination_handlers() -> Result<()> {
+        create_wake_pipe()?;
+        let action = SigAction::new(
+            SigHandler::SigAction(record_termination),
+            SaFlags::SA_RESTART | SaFlags::SA_SIGINFO,
+            SigSet::empty(),
+        );
+        for signal in TERMINATION_SIGNALS {
+            // SAFETY: the handler only performs atomic operations, which are async-signal-safe.
+            unsafe { sigaction(signal, &action) }.map_err(|_| LucidError::InvalidSignal)?;
+        }
+        Ok(())
+    }
+
+    /// Returns (and resets) the termination signals received since the last call, once per
+    /// delivery. If a signal arrived several times, all of them report the latest sender.
+    pub fn take_terminations() -> Vec<Termination> {
+        let mut terminations = vec![];
+        for (index, &signal) in TERMINATION_SIGNALS.iter().enumerate() {
+            let count = TERMINATIONS[index].swap(0, Ordering::SeqCst);
+            let sender = match SENDERS[index].load(Ordering::SeqCst) {
+                0 => None,
+                pid => Some(Pid::from_raw(pid)),
+            };
+            let termination = Termination {
+                signal: Some(signal),
+                sender,
+            };
+            terminations.extend(std::iter::repeat_n(termination, count));
+        }
+        terminations
+    }
+
+    /// Installs a handler that records the delivery of each of the given signals.
+    pub fn install_handlers(signals: &[Signal]) -> Result<()> {
+        for &signal in signals {
+            install(signal, record_signal)?;
+        }
+        Ok(())
+    }
+
+    /// Installs a handler that only counts deliveries of each of the given signals, such that
+    /// they neither terminate the process nor wake it up. This replaces any earlier handler.
+    pub fn install_ignore_handlers(signals: &[Signal]) -> Result<()> {
+        for &signal in signals {
+            install(signal, record_ignored)?;
+        }
+        Ok(())
+    }
+
+    /// Returns (and resets) the ignored signals since the last call, once per delivery.
+    pub fn take_ignored() -> Vec<Signal> {
+        let mut ignored = vec![];
+        for (signum, count) in IGNORED.iter().enumerate() {
+            let count = count.swap(0, Ordering::SeqCst);
+            if let Ok(signal) = Signal::try_from(signum as i32) {
+                ignored.extend(std::iter::repeat_n(signal, count));
+            }
+        }
+        ignored
+    }
+
+    /// Installs a handler that counts SIGUSR1 deliveries as status requests. Signals passed to
+    /// `install_handlers` later take precedence.
+    pub fn install_status_handler() -> Result<()> {
+        install(Signal::SIGUSR1, record_status_request)
+    }
+
+    /// Installs a handler that counts SIGUSR2 deliveries as requests to toggle verbose output.
+    /// Signals passed to `install_handlers` later take precedence.
+    pub fn install_verbosity_handler() -> Result<()> {
+        install(Signal::SIGUSR2, record_verbosity_toggle)
+    }
+
+    /// Returns (and clears) the signal received since the last call, if any.
+    pub fn take_received() -> Option<Signal> {
+        match RECEIVED.swap(0, Ordering::SeqCst) {
+            0 => None,
+            signum => Signal::try_from(signum).ok(),
+        }
+    }
+
+    /// Installs a handler that counts SIGHUP deliveries (for `--extend` or `--reload-on-hup`,
+    /// named by `_option`). This replaces the termination handler for SIGHUP, so it has to be
+    /// installed after that one.
+    pub fn install_hangup_handler(_option: &'static str) -> Result<()> {
+        install(Signal::SIGHUP, record_hangup)
+    }
+
+    /// Installs a handler that records when the process is continued after being stopped.
+    pub fn install_resume_handler(_option: &'static str) -> Result<()> {
+        install(Signal::SIGCONT, record_resume)
+    }
+
+    /// Installs a handler for SIGTSTP, such that the process only stops (see `stop`) once it
+    /// reported the request.
+    pub fn install_stop_handler() -> Result<()> {
+        install(Signal::SIGTSTP, record_stop_request)
+    }
+
+    /// Installs a handler that only wakes up `wait` when a child process exits (SIGCHLD), such
+    /// that it can be reaped right away.
+    pub fn install_child_handler() -> Result<()> {
+        install(Signal::SIGCHLD, record_child_exit)
+    }
+
+    /// Returns (and resets) the number of status requests since the last call.
+    pub fn take_status_requests() -> usize {
+        STATUS_REQUESTS.swap(0, Ordering::SeqCst)
+    }
+
+    /// Returns (and resets) the number of verbosity toggles since the last call.
+    pub fn take_verbosity_toggles() -> usize {
+        VERBOSITY_TOGGLES.swap(0, Ordering::SeqCst)
+    }
+
+    /// Returns (and resets) the number of SIGHUP deliveries since the last call.
+    pub fn take_hangups() -> usize {
+        HANGUPS.swap(0, Ordering::SeqCst)
+    }
+
+    /// Returns true if the process was continued (SIGCONT) since the last call.
+    pub fn take_resumed() -> bool {
+        RESUMED.swap(false, Ordering::SeqCst)
+    }
+
+    /// Returns true if SIGTSTP was received since the last call.
+    pub fn take_stop_request() -> bool {
+        STOP_REQUESTED.swap(false, Ordering::SeqCst)
+    }
+
+    /// Stops the process (with SIGSTOP, which can not be caught) until it is continued.
+    pub fn stop() {
+        signal::raise(Signal::SIGSTOP).ok();
+    }
+
+    /// Sends `signal` to the process `pid`. Without a signal, only checks that the process exists.
+    pub fn kill(pid: Pid, signal: Option<Signal>) -> std::result::Result<(), String> {
+        signal::kill(pid, signal).map_err(|err| err.to_string())
+    }
+
+    pub fn number(signal: Signal) -> i32 {
+        signal as i32
+    }
+
+    /// Sends SIGTERM to the process `pid`.
+    pub fn terminate(pid: Pid) -> std::result::Result<(), String> {
+        signal::kill(pid, Signal::SIGTERM).map_err(|err| err.to_string())
+    }
+
+    /// Sends SIGTERM to all processes in the process group `group`.
+    pub fn terminate_group(group: Pid) -> std::result::Result<(), String> {
+        signal::killpg(group, Signal::SIGTERM).map_err(|err| err.to_string())
+    }
+
+    /// Sends `signal` to all members of the process group `group`.
+    pub fn signal_group(group: Pid, signal: Signal) -> std::result::Result<(), String> {
+        signal::killpg(group, signal).map_err(|err| err.to_string())
+    }
+
+    /// Returns true if there is at least one process left in the process group `group`.
+    pub fn group_exists(group: Pid) -> bool {
+        signal::killpg(group, None).is_ok()
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    //! Fallback for platforms without POSIX signals. No signal names can be parsed, so options
+    //! that take signals are rejected, and there is nothing to send or to wait for.
+
+    use std::fmt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Condvar, Mutex};
+    use std::time;
+
+    use super::Termination;
+    use crate::{LucidError, Result};
+
+    /// The number of console events (Ctrl-C, Ctrl-Break or closing the console) not handled yet.
+    static TERMINATIONS: AtomicUsize = AtomicUsize::new(0);
+
+    /// The number of console events received so far, see `received_counts`.
+    static DELIVERED: AtomicUsize = AtomicUsize::new(0);
+
+    /// Whether a console event arrived since `wait` last returned, and how to notify it.
+    static WOKEN: Mutex<bool> = Mutex::new(false);
+    static WAKE: Condvar = Condvar::new();
+
+    /// A signal. There are none on this platform.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Signal {}
+
+    impl fmt::Display for Signal {
+        fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+            match *self {}
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Pid(i32);
+
+    impl Pid {
+        pub fn from_raw(pid: i32) -> Pid {
+            Pid(pid)
+        }
+    }
+
+    impl fmt::Display for Pid {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    pub fn parse_signal(_: &str) -> Option<Signal> {
+        None
+    }
+
+    pub fn number(signal: Signal) -> i32 {
+        match signal {}
+    }
+
+    pub fn install_termination_handlers() -> Result<()> {
+        ctrlc::set_handler(|| {
+            TERMINATIONS.fetch_add(1, Ordering::SeqCst);
+            DELIVERED.fetch_add(1, Ordering::SeqCst);
+            wake();
+        })
+        .map_err(|_| LucidError::InvalidSignal)
+    }
+
+    /// Makes `wait` return, e.g. from another thread.
+    pub fn wake() {
+        *WOKEN.lock().unwrap_or_else(|err| err.into_inner()) = true;
+        WAKE.notify_all();
+    }
+
+    /// Blocks until a console event arrives, or until `timeout` (forever if not set) has
+    /// passed.
+    pub fn wait(timeout: Option<time::Duration>) {
+        let woken = WOKEN.lock().unwrap_or_else(|err| err.into_inner());
+        let mut woken = match timeout {
+            Some(timeout) => {
+                WAKE.wait_timeout_while(woken, timeout, |woken| !*woken)
+                    .unwrap_or_else(|err| err.into_inner())
+                    .0
+            }
+            None => WAKE
+                .wait_while(woken, |woken| !*woken)
+                .unwrap_or_else(|err| err.into_inner()),
+        };
+        *woken = false;
+    }
+
+    /// Console events are reported as `Ctrl-C`, like in messages.
+    pub fn received_counts() -> Vec<(String, usize)> {
+        match DELIVERED.load(Ordering::SeqCst) {
+            0 => vec![],
+            count => vec![("Ctrl-C".into(), count)],
+        }
+    }
+
+    pub fn take_terminations() -> Vec<Termination> {
+        let termination = Termination {
+            signal: None,
+            sender: None,
+        };
+        vec![termination; TERMINATIONS.swap(0, Ordering::SeqCst)]
+    }
+
+    pub fn install_handlers(signals: &[Signal]) -> Result<()> {
+        match signals.first() {
+            None => Ok(()),
+            Some(&signal) => match signal {},
+        }
+    }
+
+    /// Status requests are sent via SIGUSR1, so this is a no-op.
+    pub fn install_status_handler() -> Result<()> {
+        Ok(())
+    }
+
+    pub fn install_verbosity_handler() -> Result<()> {
+        Ok(())
+    }
+
+    pub fn install_hangup_handler(option: &'static str) -> Result<()> {
+        Err(LucidError::Unsupported(option))
+    }
+
+    pub fn install_resume_handler(option: &'static str) -> Result<()> {
+        Err(LucidError::Unsupported(option))
+    }
+
+    pub fn install_stop_handler() -> Result<()> {
+        Err(LucidError::Unsupported("--report-stops"))
+    }
+
+    pub fn install_child_handler() -> Result<()> {
+        Err(LucidError::Unsupported("--init"))
+    }
+
+    pub fn install_ignore_handlers(signals: &[Signal]) -> Result<()> {
+        install_handlers(signals)
+    }
+
+    pub fn take_ignored() -> Vec<Signal> {
+        vec![]
+    }
+
+    pub fn take_received() -> Option<Signal> {
+        None
+    }
+
+    pub fn take_status_requests() -> usize {
+        0
+    }
+
+    pub fn take_hangups() -> usize {
+        0
+    }
+
+    pub fn take_verbosity_toggles() -> usize {
+        0
+    }
+
+    pub fn take_resumed() -> bool {
+        false
+    }
+
+    pub fn take_stop_request() -> bool {
+        false
+    }
+
+    pub fn stop() {}
+
+    pub fn kill(_: Pid, _: Option<Signal>) -> std::result::Result<(), String> {
+        Err("signals are not supported on this platform".into())
+    }
+
+    pub fn terminate(_: Pid) -> std::result::Result<(), String> {
+        Err("signals are not supported on this platform".into())
+    }
+
+    pub fn terminate_group(_: Pid) -> std::result::Result<(), String> {
+        Err("signals are not supported on this platform".into())
+    }
+
+    pub fn signal_group(_: Pid, signal: Signal) -> std::result::Result<(), String> {
+        match signal {}
+    }
+
+    pub fn group_exists(_: Pid) -> bool {
+        false
+    }
+}
+
+pub use imp::*;
+
+/// Parses a comma-separated list of signals, each optionally followed by `=CODE`, e.g.
+/// `SIGUSR1=10,SIGUSR2`.
+pub fn parse_signal_list(list: &str) -> Result<Vec<(Signal, Option<ExitCode>)>> {
+    list.split(',')
+        .map(|entry| {
+            let (name, code) = match entry.split_once('=') {
+                Some((name, code)) => (name, Some(code)),
+                None => (entry, None),
+            };
+            let signal = parse_signal(name.trim()).ok_or(LucidError::InvalidSignal)?;
+            let code = code
+                .map(|code| code.trim().parse::<ExitCode>())
+                .transpose()
+                .map_err(|_| LucidError::InvalidSignal)?;
+            Ok((signal, code))
+        })
+        .collect()
+}
+
+/// Parses a comma-separated list of signal names, e.g. `SIGTERM,HUP`.
+pub fn parse_signal_names(list: &str) -> Result<Vec<Signal>> {
+    list.split(',')
+        .map(|name| parse_signal(name.trim()).ok_or(LucidError::InvalidSignal))
+        .collect()
+}
+
+/// Parses a `PID:SIGNAL` specification, e.g. `1234:SIGUSR1`.
+pub fn parse_signal_target(spec: &str) -> Option<(Pid, Signal)> {
+    let (pid, signal) = spec.split_once(':')?;
+    let pid = pid.trim().parse::<i32>().ok().filter(|&pid| pid > 0)?;
+    Some((Pid::from_raw(pid), parse_signal(signal.trim())?))
+}
+
+#[cfg(unix)]
+#[test]
+fn test_parse_signal() {
+    assert_eq!(Some(Signal::SIGUSR1), parse_signal("SIGUSR1"));
+    assert_eq!(Some(Signal::SIGUSR2), parse_signal("usr2"));
+    assert_eq!(Some(Signal::SIGTERM), parse_signal("15"));
+    assert_eq!(None, parse_signal("SIGFOO"));
+
+    assert_eq!(
+        Ok(vec![
+            (Signal::SIGUSR1, Some(10)),
+            (Signal::SIGUSR2, Some(20)),
+            (Signal::SIGHUP, None)
+        ]),
+        parse_signal_list("SIGUSR1=10,SIGUSR2=20,HUP")
+    );
+    assert_eq!(
+        Err(LucidError::InvalidSignal),
+        parse_signal_list("SIGUSR1=ten")
+    );
+
+    assert_eq!(
+        Ok(vec![Signal::SIGTERM, Signal::SIGHUP]),
+        parse_signal_names("SIGTERM, hup")
+    );
+    assert_eq!(Err(LucidError::InvalidSignal), parse_signal_names("TERM="));
+
+    assert_eq!(
+        Some((Pid::from_raw(1234), Signal::SIGUSR1)),
+        parse_signal_target("1234:USR1")
+    );
+    assert_eq!(None, parse_signal_target("0:SIGUSR1"));
+    assert_eq!(None, parse_signal_target("1234"));
+
+    let termination = |signal| Termination {
+        signal,
+        sender: None,
+    };
+    assert_eq!(143, termination(Some(Signal::SIGTERM)).exit_code());
+    assert_eq!(130, termination(None).exit_code());
+}
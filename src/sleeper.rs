@@ -0,0 +1,103 @@
+use std::time;
+
+use crate::{run, Config, ExitCode, Result, Sleep, VerbosityLevel};
+
+/// Builder for running lucid in-process, e.g. as a mock worker in integration tests:
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let code = lucid::Sleeper::new()
+///     .sleep_for(Duration::from_millis(200))
+///     .exit_code(3)
+///     .quiet()
+///     .run()?;
+/// assert_eq!(3, code);
+/// # Ok::<(), lucid::LucidError>(())
+/// ```
+///
+/// Options without a dedicated method can be set with `configure`. Note that `run` installs
+/// process-wide signal handlers.
+#[derive(Debug, Clone, Default)]
+pub struct Sleeper {
+    config: Config,
+}
+
+impl Sleeper {
+    /// A sleeper with the defaults of the command-line tool, which sleeps forever.
+    pub fn new() -> Sleeper {
+        Sleeper::default()
+    }
+
+    pub fn sleep_for(mut self, duration: time::Duration) -> Sleeper {
+        self.config.sleep = Sleep::For(duration);
+        self
+    }
+
+    pub fn sleep_forever(mut self) -> Sleeper {
+        self.config.sleep = Sleep::Forever;
+        self
+    }
+
+    pub fn exit_code(mut self, code: ExitCode) -> Sleeper {
+        self.config.exit_code = code;
+        self
+    }
+
+    pub fn prefix(mut self, prefix: &str) -> Sleeper {
+        self.config.prefix = prefix.into();
+        self
+    }
+
+    pub fn quiet(mut self) -> Sleeper {
+        self.config.verbosity = VerbosityLevel::Quiet;
+        self
+    }
+
+    pub fn verbose(mut self) -> Sleeper {
+        self.config.verbosity = VerbosityLevel::Verbose;
+        self
+    }
+
+    pub fn no_interrupt(mut self) -> Sleeper {
+        self.config.no_interrupt = true;
+        self
+    }
+
+    /// Modifies any other part of the configuration.
+    pub fn configure(mut self, f: impl FnOnce(&mut Config)) -> Sleeper {
+        f(&mut self.config);
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Sleeps and returns the exit code, see `lucid::run`.
+    pub fn run(self) -> Result<ExitCode> {
+        run(self.config)
+    }
+}
+
+impl From<Config> for Sleeper {
+    fn from(config: Config) -> Sleeper {
+        Sleeper { config }
+    }
+}
+
+#[test]
+fn test_sleeper() {
+    let sleeper = Sleeper::new()
+        .sleep_for(time::Duration::from_millis(50))
+        .exit_code(3)
+        .prefix("worker")
+        .quiet()
+        .configure(|config| config.repeat = 2);
+    assert_eq!("worker", sleeper.config().prefix);
+    assert_eq!(2, sleeper.config().repeat);
+
+    let start = time::Instant::now();
+    assert_eq!(Ok(3), sleeper.run());
+    assert!(start.elapsed() >= time::Duration::from_millis(100));
+}
@@ -0,0 +1,88 @@
+//! Sustained output on stdout or stderr, to benchmark log pipelines (see `--spam-stdout`).
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::{thread, time};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stream::Stdout => "stdout",
+            Stream::Stderr => "stderr",
+        }
+    }
+}
+
+/// How far the writer may fall behind (e.g. when the pipe is full) before it stops trying to
+/// catch up.
+const MAX_BACKLOG: time::Duration = time::Duration::from_secs(1);
+
+/// Writes `rate` lines per second in a background thread, until dropped. A blocked stream only
+/// blocks that thread, and the thread stops if the stream is closed.
+pub struct Spammer {
+    stop: Arc<AtomicBool>,
+}
+
+impl Spammer {
+    pub fn start(stream: Stream, rate: f64, line_length: usize) -> Spammer {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        thread::spawn(move || {
+            let period = time::Duration::from_secs_f64(1.0 / rate);
+            let mut next = time::Instant::now();
+            for number in 0.. {
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let result = match stream {
+                    Stream::Stdout => io::stdout().lock().write_all(&line(number, line_length)),
+                    Stream::Stderr => io::stderr().lock().write_all(&line(number, line_length)),
+                };
+                if result.is_err() {
+                    break;
+                }
+
+                next += period;
+                let now = time::Instant::now();
+                if now > next + MAX_BACKLOG {
+                    next = now;
+                }
+                thread::sleep(next.saturating_duration_since(now));
+            }
+        });
+        Spammer { stop }
+    }
+}
+
+impl Drop for Spammer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+/// A numbered line with `length` characters (plus the newline).
+fn line(number: u64, length: usize) -> Vec<u8> {
+    let mut line = format!("{:09} ", number).into_bytes();
+    line.extend(
+        (b'a'..=b'z')
+            .cycle()
+            .take(length.saturating_sub(line.len())),
+    );
+    line.truncate(length);
+    line.push(b'\n');
+    line
+}
+
+#[test]
+fn test_line() {
+    assert_eq!(b"000000042 abcdefghij\n".to_vec(), line(42, 20));
+    assert_eq!(b"000000\n".to_vec(), line(0, 6));
+    assert_eq!(81, line(7, 80).len());
+}
@@ -0,0 +1,259 @@
+//! The state that lucid inherited from whoever started it, see `--dump-state`.
+
+use std::env;
+
+/// Describes the environment, and on Unix also the umask, the open file descriptors, the
+/// resource limits and the signal dispositions, one line per item.
+pub fn describe() -> Vec<String> {
+    let mut variables: Vec<_> = env::vars_os().collect();
+    variables.sort();
+    #[cfg_attr(not(unix), allow(unused_mut))]
+    let mut lines: Vec<_> = variables
+        .into_iter()
+        .map(|(name, value)| {
+            format!(
+                "environ: {}=\"{}\"",
+                name.to_string_lossy(),
+                value.to_string_lossy()
+            )
+        })
+        .collect();
+    #[cfg(unix)]
+    lines.extend(unix::describe());
+    lines
+}
+
+/// Describes the cgroup and its limits (on Linux), and whether lucid appears to run as the init
+/// process of a container.
+pub fn describe_container() -> Vec<String> {
+    #[cfg_attr(not(target_os = "linux"), allow(unused_mut))]
+    let mut lines = vec![];
+    #[cfg(target_os = "linux")]
+    lines.extend(cgroup::describe());
+    if std::process::id() == 1 {
+        lines.push("Running as PID 1, probably as the entrypoint of a container".into());
+    }
+    lines
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::fs;
+
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+    use nix::sys::signal::{SigSet, SigmaskHow, Signal};
+    use nix::sys::stat::{umask, Mode};
+
+    use crate::rlimit::Resource;
+
+    pub fn describe() -> Vec<String> {
+        // There is no way to read the umask without setting it
+        let mask = umask(Mode::empty());
+        umask(mask);
+        let mut lines = vec![format!("umask() = {:04o}", mask.bits())];
+
+        for fd in open_fds() {
+            let flags = match fcntl(fd, FcntlArg::F_GETFD) {
+                Ok(flags) if FdFlag::from_bits_truncate(flags).contains(FdFlag::FD_CLOEXEC) => {
+                    " (close-on-exec)"
+                }
+                _ => "",
+            };
+            match fs::read_link(format!("/proc/self/fd/{}", fd)) {
+                Ok(target) => lines.push(format!("fd {} -> {}{}", fd, target.display(), flags)),
+                Err(_) => lines.push(format!("fd {} is open{}", fd, flags)),
+            }
+        }
+
+        for resource in Resource::ALL {
+            if let Ok((soft, hard)) = resource.current() {
+                lines.push(format!(
+                    "getrlimit({}) = {} (hard limit: {})",
+                    resource.name(),
+                    resource.format(soft),
+                    resource.format(hard)
+                ));
+            }
+        }
+
+        // Handlers are reset by exec, so only ignored and blocked signals are inherited.
+        // SIGPIPE is left out, since the Rust runtime always ignores it before `main`.
+        let ignored: Vec<_> = Signal::iterator()
+            .filter(|&signal| signal != Signal::SIGPIPE)
+            .filter(|&signal| {
+                // SAFETY: without a new action, `sigaction` only writes the current one to the
+                // valid (all-zero bytes are fine) struct.
+                unsafe {
+                    let mut action: libc::sigaction = std::mem::zeroed();
+                    libc::sigaction(signal as libc::c_int, std::ptr::null(), &mut action) == 0
+                        && action.sa_sigaction == libc::SIG_IGN
+                }
+            })
+            .collect();
+        lines.push(format!("Ignored signals: {}", signal_list(&ignored)));
+
+        let mut mask = SigSet::empty();
+        if nix::sys::signal::sigprocmask(SigmaskHow::SIG_BLOCK, None, Some(&mut mask)).is_ok() {
+            let blocked: Vec<_> = Signal::iterator().filter(|&s| mask.contains(s)).collect();
+            lines.push(format!("Blocked signals: {}", signal_list(&blocked)));
+        }
+        lines
+    }
+
+    /// The open file descriptors, found via `/proc/self/fd` or `/dev/fd`.
+    fn open_fds() -> Vec<i32> {
+        let entries = fs::read_dir("/proc/self/fd").or_else(|_| fs::read_dir("/dev/fd"));
+        let mut fds: Vec<i32> = entries
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Reading the directory opened another descriptor, which is closed again by now
+        fds.retain(|&fd| fcntl(fd, FcntlArg::F_GETFD).is_ok());
+        fds.sort_unstable();
+        fds
+    }
+
+    fn signal_list(signals: &[Signal]) -> String {
+        if signals.is_empty() {
+            return "none".into();
+        }
+        signals
+            .iter()
+            .map(|signal| signal.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod cgroup {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::memory::size_as_str;
+
+    const ROOT: &str = "/sys/fs/cgroup";
+
+    pub fn describe() -> Vec<String> {
+        let cgroups = match fs::read_to_string("/proc/self/cgroup") {
+            Ok(content) => parse_cgroups(&content),
+            Err(_) => return vec![],
+        };
+        let mut lines = vec![];
+        // Only the hierarchies whose limits are reported below
+        let relevant = |controllers: &str| {
+            controllers.is_empty() || controllers.split(',').any(|c| c == "memory" || c == "cpu")
+        };
+        for (controllers, path) in cgroups.iter().filter(|(c, _)| relevant(c)) {
+            match controllers.as_str() {
+                "" => lines.push(format!("cgroup = \"{}\"", path)),
+                controllers => lines.push(format!("cgroup ({}) = \"{}\"", controllers, path)),
+            }
+        }
+
+        // cgroup v1 has one hierarchy per controller, v2 a single one (which has no controllers
+        // in a hybrid setup, so v1 takes precedence)
+        let read_v1 = |controller: &str, file: &str| {
+            let (_, path) = cgroups
+                .iter()
+                .find(|(controllers, _)| controllers.split(',').any(|c| c == controller))?;
+            read_limit(&Path::new(ROOT).join(controller), path, file)
+        };
+        let read_v2 = |file: &str| {
+            let (_, path) = cgroups
+                .iter()
+                .find(|(controllers, _)| controllers.is_empty())?;
+            read_limit(Path::new(ROOT), path, file)
+        };
+        let memory = read_v1("memory", "memory.limit_in_bytes")
+            .or_else(|| read_v2("memory.max"))
+            .map(|limit| parse_memory_limit(&limit));
+        let quota = read_v1("cpu", "cpu.cfs_quota_us").zip(read_v1("cpu", "cpu.cfs_period_us"));
+        let cpus = quota
+            .map(|(quota, period)| format!("{} {}", quota, period))
+            .or_else(|| read_v2("cpu.max"))
+            .map(|limit| parse_cpu_limit(&limit));
+        if let Some(memory) = memory {
+            lines.push(format!(
+                "Memory limit (cgroup): {}",
+                memory.map_or("none".into(), size_as_str)
+            ));
+        }
+        if let Some(cpus) = cpus {
+            lines.push(format!(
+                "CPU limit (cgroup): {}",
+                cpus.map_or("none".into(), |cpus| format!("{:.2} CPUs", cpus))
+            ));
+        }
+
+        let container = if Path::new("/.dockerenv").exists() {
+            Some("docker".into())
+        } else if Path::new("/run/.containerenv").exists() {
+            Some("podman".into())
+        } else {
+            // Set by systemd-nspawn, LXC and others
+            std::env::var("container")
+                .ok()
+                .filter(|name| !name.is_empty())
+        };
+        if let Some(container) = container {
+            lines.push(format!("Running in a container ({})", container));
+        }
+        lines
+    }
+
+    /// The controllers (empty for cgroup v2) and the path of each line in `/proc/self/cgroup`.
+    fn parse_cgroups(content: &str) -> Vec<(String, String)> {
+        content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, ':');
+                let (_, controllers, path) = (fields.next()?, fields.next()?, fields.next()?);
+                Some((controllers.to_string(), path.to_string()))
+            })
+            .collect()
+    }
+
+    /// Reads a file of the cgroup at `path` in the hierarchy mounted at `root`. Inside a cgroup
+    /// namespace, the cgroup of lucid is the root of the hierarchy instead.
+    fn read_limit(root: &Path, path: &str, file: &str) -> Option<String> {
+        fs::read_to_string(root.join(path.trim_start_matches('/')).join(file))
+            .or_else(|_| fs::read_to_string(root.join(file)))
+            .ok()
+            .map(|content| content.trim().to_string())
+    }
+
+    /// `max` (v2) or a huge number (v1) mean that there is no limit.
+    fn parse_memory_limit(limit: &str) -> Option<u64> {
+        limit.parse().ok().filter(|&bytes: &u64| bytes < 1 << 62)
+    }
+
+    /// Parses the quota and the period, e.g. `50000 100000` (half a CPU). A quota of `max` (v2)
+    /// or `-1` (v1) means that there is no limit.
+    fn parse_cpu_limit(limit: &str) -> Option<f64> {
+        let (quota, period) = limit.split_once(' ')?;
+        let (quota, period) = (quota.parse::<f64>().ok()?, period.parse::<f64>().ok()?);
+        (quota > 0.0 && period > 0.0).then(|| quota / period)
+    }
+
+    #[test]
+    fn test_parse_cgroups() {
+        assert_eq!(
+            vec![
+                ("memory".to_string(), "/docker/abc".to_string()),
+                ("cpu,cpuacct".to_string(), "/".to_string()),
+                (String::new(), "/system.slice/a:b.service".to_string()),
+            ],
+            parse_cgroups("4:memory:/docker/abc\n2:cpu,cpuacct:/\n0::/system.slice/a:b.service\n")
+        );
+        assert_eq!(Some(512 << 20), parse_memory_limit("536870912"));
+        assert_eq!(None, parse_memory_limit("max"));
+        assert_eq!(None, parse_memory_limit("9223372036854771712"));
+        assert_eq!(Some(1.5), parse_cpu_limit("150000 100000"));
+        assert_eq!(None, parse_cpu_limit("max 100000"));
+        assert_eq!(None, parse_cpu_limit("-1 100000"));
+    }
+}
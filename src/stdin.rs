@@ -0,0 +1,87 @@
+//! What to do with stdin while sleeping, see `--stdin-mode`.
+
+use std::io::{self, BufRead};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::{platform, signals, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StdinMode {
+    /// Never read from stdin, such that a writer eventually blocks.
+    Ignore,
+    /// Read and discard everything.
+    Consume,
+    /// Print every line that is read.
+    Echo,
+    /// Close stdin right away, such that a writer gets `EPIPE`.
+    Close,
+}
+
+impl StdinMode {
+    pub fn parse(value: &str) -> Option<StdinMode> {
+        match value {
+            "ignore" => Some(StdinMode::Ignore),
+            "consume" => Some(StdinMode::Consume),
+            "echo" => Some(StdinMode::Echo),
+            "close" => Some(StdinMode::Close),
+            _ => None,
+        }
+    }
+}
+
+/// Something that was read from stdin.
+pub enum Input {
+    Line(String),
+    /// The end of stdin was reached after this many bytes.
+    Closed(usize),
+}
+
+/// Reads stdin in a background thread, which wakes up the main loop (see `signals::wake`) for
+/// every line it echoes and at the end of stdin.
+pub struct StdinReader {
+    input: mpsc::Receiver<Input>,
+}
+
+impl StdinReader {
+    /// Applies the mode. Returns a reader for the modes that read stdin.
+    pub fn start(mode: StdinMode) -> Result<Option<StdinReader>> {
+        let echo = match mode {
+            StdinMode::Ignore => return Ok(None),
+            StdinMode::Close => return platform::close_stdin().map(|_| None),
+            StdinMode::Consume => false,
+            StdinMode::Echo => true,
+        };
+
+        let (sender, input) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stdin = io::stdin().lock();
+            let mut line = vec![];
+            let mut total = 0;
+            loop {
+                line.clear();
+                match stdin.read_until(b'\n', &mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => total += n,
+                }
+                if echo {
+                    let text = String::from_utf8_lossy(&line);
+                    let text = text.strip_suffix('\n').unwrap_or(&text);
+                    if sender.send(Input::Line(text.into())).is_err() {
+                        return;
+                    }
+                    signals::wake();
+                }
+            }
+            if sender.send(Input::Closed(total)).is_ok() {
+                signals::wake();
+            }
+        });
+        Ok(Some(StdinReader { input }))
+    }
+
+    /// Returns (without blocking) what was read since the last call.
+    pub fn take_input(&self) -> Vec<Input> {
+        self.input.try_iter().collect()
+    }
+}
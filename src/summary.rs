@@ -0,0 +1,113 @@
+use std::time;
+
+use crate::{json_string, signals, CrashMode, ExitCode, WakeReason};
+
+/// The outcome of a run.
+pub struct RunSummary {
+    pub requested: Option<time::Duration>,
+    pub actual: time::Duration,
+    /// Number of iterations of the main loop.
+    pub cycles: u64,
+    pub reason: WakeReason,
+    /// Number of termination signals received, including ignored ones.
+    pub termination_signals: usize,
+    /// How often each signal was received, see `signals::received_counts`.
+    pub signals: Vec<(String, usize)>,
+    pub exit_code: ExitCode,
+}
+
+impl RunSummary {
+    pub const CSV_HEADER: &'static str = "requested_s,actual_s,interrupted,reason,exit_code,cycles";
+
+    /// The summary of a run that is about to crash, with the exit code that the crash leads to.
+    pub fn before_crash(
+        requested: Option<time::Duration>,
+        actual: time::Duration,
+        cycles: u64,
+        termination_signals: usize,
+        mode: CrashMode,
+    ) -> RunSummary {
+        RunSummary {
+            requested,
+            actual,
+            cycles,
+            reason: WakeReason::Crashed,
+            termination_signals,
+            signals: signals::received_counts(),
+            exit_code: mode.exit_code(),
+        }
+    }
+
+    pub fn interrupted(&self) -> bool {
+        self.reason == WakeReason::Interrupted
+    }
+
+    /// Renders the summary as a single CSV line with the columns given in `CSV_HEADER`. The
+    /// requested duration is left empty when sleeping forever.
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{:.3},{},{},{},{}",
+            self.requested
+                .map(|d| format!("{:.3}", d.as_secs_f64()))
+                .unwrap_or_default(),
+            self.actual.as_secs_f64(),
+            self.interrupted(),
+            self.reason.name(),
+            self.exit_code,
+            self.cycles
+        )
+    }
+
+    /// Renders the summary as a single-line JSON object, see `--summary`.
+    pub fn to_json(&self) -> String {
+        let signals: Vec<_> = self
+            .signals
+            .iter()
+            .map(|(name, count)| format!("{}:{}", json_string(name), count))
+            .collect();
+        format!(
+            "{{\"requested_s\":{},\"actual_s\":{:.3},\"cycles\":{},\"interrupted\":{},\
+             \"reason\":{},\"termination_signals\":{},\"exit_code\":{},\"signals\":{{{}}}}}",
+            self.requested
+                .map(|d| format!("{:.3}", d.as_secs_f64()))
+                .unwrap_or_else(|| "null".into()),
+            self.actual.as_secs_f64(),
+            self.cycles,
+            self.interrupted(),
+            json_string(self.reason.name()),
+            self.termination_signals,
+            self.exit_code,
+            signals.join(",")
+        )
+    }
+}
+
+#[test]
+fn test_summary_to_csv() {
+    let summary = RunSummary {
+        requested: Some(time::Duration::from_millis(2500)),
+        actual: time::Duration::from_millis(1200),
+        cycles: 12,
+        reason: WakeReason::Interrupted,
+        termination_signals: 1,
+        signals: vec![("SIGUSR1".into(), 2), ("SIGTERM".into(), 1)],
+        exit_code: 3,
+    };
+    assert_eq!("2.500,1.200,true,interrupted,3,12", summary.to_csv());
+    assert_eq!(
+        "{\"requested_s\":2.500,\"actual_s\":1.200,\"cycles\":12,\"interrupted\":true,\
+         \"reason\":\"interrupted\",\"termination_signals\":1,\"exit_code\":3,\
+         \"signals\":{\"SIGUSR1\":2,\"SIGTERM\":1}}",
+        summary.to_json()
+    );
+
+    let summary = RunSummary {
+        requested: None,
+        reason: WakeReason::TimedOut,
+        signals: vec![],
+        ..summary
+    };
+    assert_eq!(",1.200,false,timeout,3,12", summary.to_csv());
+    assert!(summary.to_json().starts_with("{\"requested_s\":null,"));
+    assert!(summary.to_json().ends_with(",\"signals\":{}}"));
+}
@@ -0,0 +1,173 @@
+//! Sending messages to the system logger, see `--log-target`.
+
+use crate::{LucidError, Result};
+
+/// Where messages go. `Stdout` stands for the regular output, i.e. stderr with `--stderr`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogTarget {
+    Stdout,
+    Syslog,
+    Journald,
+}
+
+impl LogTarget {
+    /// Parses a comma-separated list like `stdout,journald` (without duplicates).
+    pub fn parse_list(input: &str) -> Result<Vec<LogTarget>> {
+        let mut targets = vec![];
+        for name in input.split(',') {
+            let target = match name.trim() {
+                "stdout" => LogTarget::Stdout,
+                "syslog" => LogTarget::Syslog,
+                "journald" => LogTarget::Journald,
+                _ => return Err(LucidError::InvalidArgument("--log-target")),
+            };
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        Ok(targets)
+    }
+}
+
+/// The syslog severity of a message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    /// Signals and other events that interrupt the sleep.
+    Notice = 5,
+    Info = 6,
+    /// Verbose messages.
+    Debug = 7,
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::ffi::CString;
+    use std::os::unix::net::UnixDatagram;
+
+    use super::{LogTarget, Priority};
+    use crate::{LucidError, Result};
+
+    const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+    /// A connection to syslog or journald. Messages are tagged with `tag` (the prefix) and
+    /// the PID.
+    pub enum SystemLogger {
+        /// The identifier has to stay alive while syslog is open.
+        Syslog(CString),
+        Journald(UnixDatagram, String),
+    }
+
+    impl SystemLogger {
+        pub fn open(target: LogTarget, tag: &str) -> Result<SystemLogger> {
+            match target {
+                LogTarget::Stdout => unreachable!("stdout is not a system logger"),
+                LogTarget::Syslog => {
+                    let ident = CString::new(tag).map_err(|_| LucidError::SystemLogError)?;
+                    // SAFETY: `ident` lives as long as the logger, which closes syslog again.
+                    unsafe { libc::openlog(ident.as_ptr(), libc::LOG_PID, libc::LOG_USER) };
+                    Ok(SystemLogger::Syslog(ident))
+                }
+                LogTarget::Journald => {
+                    let socket = UnixDatagram::unbound().map_err(|_| LucidError::SystemLogError)?;
+                    socket
+                        .connect(JOURNAL_SOCKET)
+                        .map_err(|_| LucidError::SystemLogError)?;
+                    Ok(SystemLogger::Journald(socket, tag.to_string()))
+                }
+            }
+        }
+
+        /// Messages that can not be delivered are dropped, like with syslog itself.
+        pub fn send(&self, priority: Priority, msg: &str) {
+            match self {
+                SystemLogger::Syslog(_) => {
+                    // Interior NUL bytes would end the message early
+                    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+                    // SAFETY: both strings are NUL-terminated, and the format consumes
+                    // exactly the one argument.
+                    unsafe { libc::syslog(priority as libc::c_int, c"%s".as_ptr(), msg.as_ptr()) };
+                }
+                SystemLogger::Journald(socket, tag) => {
+                    socket.send(&journal_entry(priority, tag, msg)).ok();
+                }
+            }
+        }
+    }
+
+    impl Drop for SystemLogger {
+        fn drop(&mut self) {
+            if let SystemLogger::Syslog(_) = self {
+                // SAFETY: `closelog` has no preconditions.
+                unsafe { libc::closelog() };
+            }
+        }
+    }
+
+    /// Encodes an entry in the native journal protocol. Values with line breaks are written
+    /// with a length prefix instead of after a `=`.
+    fn journal_entry(priority: Priority, tag: &str, msg: &str) -> Vec<u8> {
+        let mut entry = vec![];
+        let fields = [
+            ("PRIORITY", (priority as u8).to_string()),
+            ("SYSLOG_IDENTIFIER", tag.to_string()),
+            ("SYSLOG_PID", std::process::id().to_string()),
+            ("MESSAGE", msg.to_string()),
+        ];
+        for (name, value) in fields {
+            entry.extend_from_slice(name.as_bytes());
+            if value.contains('\n') {
+                entry.push(b'\n');
+                entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            } else {
+                entry.push(b'=');
+            }
+            entry.extend_from_slice(value.as_bytes());
+            entry.push(b'\n');
+        }
+        entry
+    }
+
+    #[test]
+    fn test_journal_entry() {
+        let entry = journal_entry(Priority::Notice, "lucid", "Caught SIGTERM");
+        let expected = format!(
+            "PRIORITY=5\nSYSLOG_IDENTIFIER=lucid\nSYSLOG_PID={}\nMESSAGE=Caught SIGTERM\n",
+            std::process::id()
+        );
+        assert_eq!(expected.as_bytes(), &entry[..]);
+
+        let entry = journal_entry(Priority::Info, "lucid", "a\nb");
+        assert!(entry.ends_with(b"MESSAGE\n\x03\0\0\0\0\0\0\0a\nb\n"));
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::{LogTarget, Priority};
+    use crate::{LucidError, Result};
+
+    pub enum SystemLogger {}
+
+    impl SystemLogger {
+        pub fn open(_: LogTarget, _: &str) -> Result<SystemLogger> {
+            Err(LucidError::Unsupported("--log-target"))
+        }
+
+        pub fn send(&self, _: Priority, _: &str) {
+            match *self {}
+        }
+    }
+}
+
+pub use imp::SystemLogger;
+
+#[test]
+fn test_parse_list() {
+    assert_eq!(Ok(vec![LogTarget::Syslog]), LogTarget::parse_list("syslog"));
+    assert_eq!(
+        Ok(vec![LogTarget::Stdout, LogTarget::Journald]),
+        LogTarget::parse_list("stdout, journald,stdout")
+    );
+    assert!(LogTarget::parse_list("file").is_err());
+    assert!(LogTarget::parse_list("").is_err());
+}
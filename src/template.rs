@@ -0,0 +1,193 @@
+use std::time::SystemTime;
+
+use crate::timestamp::TimestampFormat;
+use crate::{LucidError, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplateItem {
+    Literal(String),
+    Prefix,
+    Message,
+    Pid,
+    Elapsed,
+}
+
+/// The default template, which renders messages like `[lucid]: Going to sleep`.
+pub const DEFAULT: &str = "[{prefix}]: {msg}";
+
+/// A template for rendering messages, see `--message-format`.
+///
+/// Supported placeholders: `{prefix}`, `{msg}`, `{pid}` and `{elapsed}` (time since start).
+/// Literal braces are written as `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageTemplate {
+    items: Vec<TemplateItem>,
+}
+
+/// Splits a template into literals and placeholders, which are looked up by name. `None` if
+/// a placeholder is unknown or a brace is not closed.
+fn parse_items<T>(
+    template: &str,
+    literal_item: fn(String) -> T,
+    placeholder: fn(&str) -> Option<T>,
+) -> Option<Vec<T>> {
+    let mut items = vec![];
+    let mut literal = String::new();
+    let mut rest = template;
+
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '{' if rest.starts_with('{') => {
+                literal.push('{');
+                rest = &rest[1..];
+            }
+            '}' if rest.starts_with('}') => {
+                literal.push('}');
+                rest = &rest[1..];
+            }
+            '{' => {
+                let (name, after) = rest.split_once('}')?;
+                let item = placeholder(name)?;
+                if !literal.is_empty() {
+                    items.push(literal_item(std::mem::take(&mut literal)));
+                }
+                items.push(item);
+                rest = after;
+            }
+            '}' => return None,
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        items.push(literal_item(literal));
+    }
+    Some(items)
+}
+
+impl MessageTemplate {
+    pub fn parse(template: &str) -> Result<MessageTemplate> {
+        let items = parse_items(template, TemplateItem::Literal, |name| match name {
+            "prefix" => Some(TemplateItem::Prefix),
+            "msg" => Some(TemplateItem::Message),
+            "pid" => Some(TemplateItem::Pid),
+            "elapsed" => Some(TemplateItem::Elapsed),
+            _ => None,
+        })
+        .ok_or(LucidError::InvalidMessageFormat)?;
+        Ok(MessageTemplate { items })
+    }
+
+    pub fn render(&self, prefix: &str, msg: &str, pid: u32, elapsed: &str) -> String {
+        self.items
+            .iter()
+            .map(|item| match item {
+                TemplateItem::Literal(text) => text.as_str().into(),
+                TemplateItem::Prefix => prefix.into(),
+                TemplateItem::Message => msg.into(),
+                TemplateItem::Pid => pid.to_string(),
+                TemplateItem::Elapsed => elapsed.into(),
+            })
+            .collect::<Vec<String>>()
+            .concat()
+    }
+}
+
+impl Default for MessageTemplate {
+    fn default() -> Self {
+        MessageTemplate::parse(DEFAULT).expect("valid default template")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PrefixItem {
+    Literal(String),
+    Pid,
+    Ppid,
+    Index,
+    Time,
+}
+
+/// A `--prefix`, whose placeholders are expanded for every message.
+///
+/// Supported placeholders: `{pid}`, `{ppid}`, `{index}` (of a `--fork` instance) and `{time}`
+/// (the local time as `%H:%M:%S`). Literal braces are written as `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefixTemplate {
+    items: Vec<PrefixItem>,
+    time_format: TimestampFormat,
+}
+
+impl PrefixTemplate {
+    pub fn parse(template: &str) -> Result<PrefixTemplate> {
+        let items = parse_items(template, PrefixItem::Literal, |name| match name {
+            "pid" => Some(PrefixItem::Pid),
+            "ppid" => Some(PrefixItem::Ppid),
+            "index" => Some(PrefixItem::Index),
+            "time" => Some(PrefixItem::Time),
+            _ => None,
+        })
+        .ok_or(LucidError::InvalidPrefix)?;
+        Ok(PrefixTemplate {
+            items,
+            time_format: TimestampFormat::parse("%T").expect("valid timestamp format"),
+        })
+    }
+
+    pub fn render(&self, pid: u32, ppid: u32, index: &str, now: SystemTime) -> String {
+        self.items
+            .iter()
+            .map(|item| match item {
+                PrefixItem::Literal(text) => text.as_str().into(),
+                PrefixItem::Pid => pid.to_string(),
+                PrefixItem::Ppid => ppid.to_string(),
+                PrefixItem::Index => index.into(),
+                PrefixItem::Time => self.time_format.render(now),
+            })
+            .collect::<Vec<String>>()
+            .concat()
+    }
+}
+
+#[test]
+fn test_message_template() {
+    let render = |template: &str| {
+        MessageTemplate::parse(template).map(|t| t.render("lucid", "Going to sleep", 42, "1.500s"))
+    };
+
+    assert_eq!(Ok("[lucid]: Going to sleep".into()), render(DEFAULT));
+    assert_eq!(
+        Ok("lucid: Going to sleep".into()),
+        render("{prefix}: {msg}")
+    );
+    assert_eq!(Ok("42".into()), render("{pid}"));
+    assert_eq!(
+        Ok("+1.500s Going to sleep".into()),
+        render("+{elapsed} {msg}")
+    );
+    assert_eq!(Ok("{lucid} Ω".into()), render("{{{prefix}}} Ω"));
+    assert_eq!(Ok("".into()), render(""));
+
+    assert_eq!(Err(LucidError::InvalidMessageFormat), render("{level}"));
+    assert_eq!(Err(LucidError::InvalidMessageFormat), render("{msg"));
+    assert_eq!(Err(LucidError::InvalidMessageFormat), render("msg}"));
+}
+
+#[test]
+fn test_prefix_template() {
+    let render = |template: &str| {
+        PrefixTemplate::parse(template).map(|t| t.render(42, 1, "3", SystemTime::UNIX_EPOCH))
+    };
+
+    assert_eq!(Ok("lucid".into()), render("lucid"));
+    assert_eq!(
+        Ok("worker-3 (42/1)".into()),
+        render("worker-{index} ({pid}/{ppid})")
+    );
+    assert_eq!(Ok("{pid}".into()), render("{{pid}}"));
+    let time = render("{time}").unwrap();
+    assert_eq!(8, time.len(), "{}", time);
+
+    assert_eq!(Err(LucidError::InvalidPrefix), render("{msg}"));
+    assert_eq!(Err(LucidError::InvalidPrefix), render("{pid"));
+}
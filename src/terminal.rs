@@ -0,0 +1,80 @@
+#[cfg(unix)]
+mod imp {
+    use std::os::unix::io::RawFd;
+
+    use nix::poll::{poll, PollFd, PollFlags};
+    use nix::sys::termios::{self, LocalFlags, SetArg, SpecialCharacterIndices, Termios};
+    use nix::unistd;
+
+    use crate::{LucidError, Result};
+
+    const STDIN: RawFd = 0;
+
+    /// Watches the terminal on stdin for key presses. While active, the terminal is switched to
+    /// non-canonical mode without echo. `ISIG` is left untouched, so Ctrl-C still sends SIGINT.
+    /// The original terminal settings are restored when the listener is dropped.
+    pub struct KeyListener {
+        original: Termios,
+    }
+
+    impl KeyListener {
+        /// Sets up the terminal. Returns `None` if stdin is not a TTY.
+        pub fn new() -> Result<Option<KeyListener>> {
+            if !unistd::isatty(STDIN).unwrap_or(false) {
+                return Ok(None);
+            }
+
+            let original = termios::tcgetattr(STDIN).map_err(|_| LucidError::TerminalError)?;
+            let mut settings = original.clone();
+            settings
+                .local_flags
+                .remove(LocalFlags::ICANON | LocalFlags::ECHO);
+            settings.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+            settings.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+            termios::tcsetattr(STDIN, SetArg::TCSANOW, &settings)
+                .map_err(|_| LucidError::TerminalError)?;
+
+            Ok(Some(KeyListener { original }))
+        }
+
+        /// Returns true if a key has been pressed since the last call.
+        pub fn key_pressed(&self) -> bool {
+            let mut pressed = false;
+            let mut fds = [PollFd::new(STDIN, PollFlags::POLLIN)];
+            while poll(&mut fds, 0).unwrap_or(0) > 0 {
+                let mut buffer = [0u8; 64];
+                match unistd::read(STDIN, &mut buffer) {
+                    Ok(n) if n > 0 => pressed = true,
+                    _ => break,
+                }
+            }
+            pressed
+        }
+    }
+
+    impl Drop for KeyListener {
+        fn drop(&mut self) {
+            termios::tcsetattr(STDIN, SetArg::TCSANOW, &self.original).ok();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use crate::{LucidError, Result};
+
+    /// Key presses can only be detected on Unix terminals.
+    pub struct KeyListener;
+
+    impl KeyListener {
+        pub fn new() -> Result<Option<KeyListener>> {
+            Err(LucidError::Unsupported("--any-key"))
+        }
+
+        pub fn key_pressed(&self) -> bool {
+            false
+        }
+    }
+}
+
+pub use imp::KeyListener;
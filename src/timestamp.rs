@@ -0,0 +1,644 @@
+use std::time::{self, SystemTime};
+
+use crate::{LucidError, Result};
+
+/// Number of days between 1970-01-01 and the given date (proleptic Gregorian calendar).
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Converts a (possibly negative) number of nanoseconds since the epoch into a `SystemTime`.
+/// Fails if the result is outside of the range the platform can represent.
+fn system_time_from_nanos(nanos: i128) -> Result<SystemTime> {
+    let secs = u64::try_from(nanos.unsigned_abs() / 1_000_000_000)
+        .map_err(|_| LucidError::TimestampParseError)?;
+    let offset = time::Duration::new(secs, (nanos.unsigned_abs() % 1_000_000_000) as u32);
+    if nanos >= 0 {
+        time::UNIX_EPOCH.checked_add(offset)
+    } else {
+        time::UNIX_EPOCH.checked_sub(offset)
+    }
+    .ok_or(LucidError::TimestampParseError)
+}
+
+/// Parses a fixed-width decimal number from the start of `input`.
+fn take_number(input: &str, width: usize) -> Result<(u32, &str)> {
+    match input.as_bytes().get(..width) {
+        Some(digits) if digits.iter().all(|b| b.is_ascii_digit()) => {
+            // All ASCII digits, so `width` is a char boundary
+            let value = input[..width]
+                .parse()
+                .map_err(|_| LucidError::TimestampParseError)?;
+            Ok((value, &input[width..]))
+        }
+        _ => Err(LucidError::TimestampParseError),
+    }
+}
+
+fn expect_char<'a>(input: &'a str, expected: &[char]) -> Result<&'a str> {
+    match input.chars().next() {
+        Some(c) if expected.contains(&c) => Ok(&input[c.len_utf8()..]),
+        _ => Err(LucidError::TimestampParseError),
+    }
+}
+
+/// Parses an RFC 3339 timestamp like `2024-01-01T09:00:00.5+01:00`.
+fn parse_rfc3339(input: &str) -> Result<SystemTime> {
+    let (year, rest) = take_number(input, 4)?;
+    let rest = expect_char(rest, &['-'])?;
+    let (month, rest) = take_number(rest, 2)?;
+    let rest = expect_char(rest, &['-'])?;
+    let (day, rest) = take_number(rest, 2)?;
+    let rest = expect_char(rest, &['T', 't', ' '])?;
+    let (hour, rest) = take_number(rest, 2)?;
+    let rest = expect_char(rest, &[':'])?;
+    let (minute, rest) = take_number(rest, 2)?;
+    let rest = expect_char(rest, &[':'])?;
+    let (second, mut rest) = take_number(rest, 2)?;
+
+    let year = year as i64;
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return Err(LucidError::TimestampParseError);
+    }
+
+    let mut nanos = 0i128;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(|b| b.is_ascii_digit()).count();
+        if digits == 0 {
+            return Err(LucidError::TimestampParseError);
+        }
+        for (i, b) in fraction.bytes().take(digits.min(9)).enumerate() {
+            nanos += (b - b'0') as i128 * 10i128.pow(8 - i as u32);
+        }
+        rest = &fraction[digits..];
+    }
+
+    let offset_secs = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.chars().next() {
+                Some('+') => 1,
+                Some('-') => -1,
+                _ => return Err(LucidError::TimestampParseError),
+            };
+            let (offset_hours, rest) = take_number(&rest[1..], 2)?;
+            let rest = expect_char(rest, &[':'])?;
+            let (offset_minutes, rest) = take_number(rest, 2)?;
+            if !rest.is_empty() || offset_hours > 23 || offset_minutes > 59 {
+                return Err(LucidError::TimestampParseError);
+            }
+            sign * (offset_hours as i64 * 3600 + offset_minutes as i64 * 60)
+        }
+    };
+
+    let secs = days_from_civil(year, month, day) * 86400
+        + hour as i64 * 3600
+        + minute as i64 * 60
+        + second as i64
+        - offset_secs;
+
+    system_time_from_nanos(secs as i128 * 1_000_000_000 + nanos)
+}
+
+/// Parses a timestamp given either as (fractional) seconds since the Unix epoch or in
+/// RFC 3339 format.
+pub fn parse_timestamp(input: &str) -> Result<SystemTime> {
+    let input = input.trim();
+    if let Ok(epoch_secs) = input.parse::<f64>() {
+        if !epoch_secs.is_finite() {
+            return Err(LucidError::TimestampParseError);
+        }
+        return system_time_from_nanos((epoch_secs * 1e9).round() as i128);
+    }
+    parse_rfc3339(input)
+}
+
+#[cfg(unix)]
+fn local_tm(epoch_seconds: i64) -> libc::tm {
+    let time = epoch_seconds as libc::time_t;
+    // SAFETY: `tm` is a plain C struct for which all-zero bytes are a valid value, and
+    // `localtime_r` only writes to the provided buffer.
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&time, &mut tm);
+        tm
+    }
+}
+
+#[cfg(windows)]
+fn local_tm(epoch_seconds: i64) -> libc::tm {
+    let time = epoch_seconds as libc::time_t;
+    // SAFETY: as above, `localtime_s` only writes to the provided buffer.
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_s(&mut tm, &time);
+        tm
+    }
+}
+
+/// The offset of the local time zone from UTC in seconds, for the local time `tm` that
+/// corresponds to `epoch_seconds`. Computed from the fields, as `tm_gmtoff` is not portable.
+fn utc_offset(tm: &libc::tm, epoch_seconds: i64) -> i64 {
+    let local_seconds = days_from_civil(
+        tm.tm_year as i64 + 1900,
+        tm.tm_mon as u32 + 1,
+        tm.tm_mday as u32,
+    ) * 86400
+        + tm.tm_hour as i64 * 3600
+        + tm.tm_min as i64 * 60
+        + tm.tm_sec as i64;
+    local_seconds - epoch_seconds
+}
+
+#[cfg(unix)]
+use libc::mktime;
+
+#[cfg(windows)]
+extern "C" {
+    #[link_name = "_mktime64"]
+    fn mktime(tm: *mut libc::tm) -> libc::time_t;
+}
+
+fn epoch_seconds(t: SystemTime) -> i64 {
+    match t.duration_since(time::UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs_f64().ceil() as i64),
+    }
+}
+
+/// A point in time, broken down into its components in the local time zone.
+struct LocalTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    day_of_year: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    nanosecond: u32,
+    utc_offset: i64,
+    epoch_seconds: i64,
+}
+
+impl LocalTime {
+    fn from_system_time(t: SystemTime) -> LocalTime {
+        let (epoch_seconds, nanosecond) = match t.duration_since(time::UNIX_EPOCH) {
+            Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+            Err(e) => {
+                let d = e.duration();
+                if d.subsec_nanos() == 0 {
+                    (-(d.as_secs() as i64), 0)
+                } else {
+                    (-(d.as_secs() as i64) - 1, 1_000_000_000 - d.subsec_nanos())
+                }
+            }
+        };
+
+        let tm = local_tm(epoch_seconds);
+
+        LocalTime {
+            year: tm.tm_year as i64 + 1900,
+            month: tm.tm_mon as u32 + 1,
+            day: tm.tm_mday as u32,
+            day_of_year: tm.tm_yday as u32 + 1,
+            hour: tm.tm_hour as u32,
+            minute: tm.tm_min as u32,
+            second: tm.tm_sec as u32,
+            nanosecond,
+            utc_offset: utc_offset(&tm, epoch_seconds),
+            epoch_seconds,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Boundary {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Boundary {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Boundary::Minute => "minute",
+            Boundary::Hour => "hour",
+            Boundary::Day => "day",
+        }
+    }
+}
+
+/// Returns the first start of a minute, hour or day in local time that lies after `now`.
+/// Days are computed via `mktime`, so they can be shorter or longer than 24 hours across
+/// DST transitions.
+pub fn next_boundary(now: SystemTime, boundary: Boundary) -> Result<SystemTime> {
+    let secs = epoch_seconds(now);
+    let next = match boundary {
+        Boundary::Minute | Boundary::Hour => {
+            let length = if boundary == Boundary::Minute {
+                60
+            } else {
+                3600
+            };
+            let local_secs = secs + utc_offset(&local_tm(secs), secs);
+            secs + length - local_secs.rem_euclid(length)
+        }
+        Boundary::Day => {
+            let mut tm = local_tm(secs);
+            tm.tm_sec = 0;
+            tm.tm_min = 0;
+            tm.tm_hour = 0;
+            tm.tm_mday += 1;
+            return from_local_tm(tm);
+        }
+    };
+    system_time_from_nanos(next as i128 * 1_000_000_000)
+}
+
+/// Converts a broken-down local time into a `SystemTime`. Out-of-range fields (e.g. a day
+/// of month after the last day) are normalized by `mktime`.
+fn from_local_tm(mut tm: libc::tm) -> Result<SystemTime> {
+    tm.tm_isdst = -1;
+    // SAFETY: `tm` is a valid, initialized struct which `mktime` normalizes in place.
+    let secs = unsafe { mktime(&mut tm) as i64 };
+    system_time_from_nanos(secs as i128 * 1_000_000_000)
+}
+
+/// Parses a time of day `HH:MM[:SS]` from the start of `input`.
+fn take_time_of_day(input: &str) -> Result<((u32, u32, u32), &str)> {
+    let (hour, rest) = take_number(input, 2)?;
+    let rest = expect_char(rest, &[':'])?;
+    let (minute, rest) = take_number(rest, 2)?;
+    let (second, rest) = match rest.strip_prefix(':') {
+        Some(rest) => take_number(rest, 2)?,
+        None => (0, rest),
+    };
+    if hour > 23 || minute > 59 || second > 59 {
+        return Err(LucidError::TimestampParseError);
+    }
+    Ok(((hour, minute, second), rest))
+}
+
+/// Parses a wall-clock deadline for `--until`. Accepts a time of day `HH:MM[:SS]` (the next
+/// occurrence of that time in the local time zone, today or tomorrow), a local date and time
+/// `YYYY-MM-DDTHH:MM[:SS]` or anything accepted by `parse_timestamp`.
+pub fn parse_deadline(input: &str, now: SystemTime) -> Result<SystemTime> {
+    let input = input.trim();
+    if let Ok(((hour, minute, second), "")) = take_time_of_day(input) {
+        let mut tm = local_tm(epoch_seconds(now));
+        tm.tm_hour = hour as i32;
+        tm.tm_min = minute as i32;
+        tm.tm_sec = second as i32;
+        let today = from_local_tm(tm)?;
+        if today > now {
+            return Ok(today);
+        }
+        tm.tm_mday += 1;
+        return from_local_tm(tm);
+    }
+
+    if let Ok(deadline) = parse_timestamp(input) {
+        return Ok(deadline);
+    }
+
+    let (year, rest) = take_number(input, 4)?;
+    let rest = expect_char(rest, &['-'])?;
+    let (month, rest) = take_number(rest, 2)?;
+    let rest = expect_char(rest, &['-'])?;
+    let (day, rest) = take_number(rest, 2)?;
+    let rest = expect_char(rest, &['T', 't', ' '])?;
+    let ((hour, minute, second), rest) = take_time_of_day(rest)?;
+    if !rest.is_empty()
+        || !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year as i64, month)
+    {
+        return Err(LucidError::TimestampParseError);
+    }
+
+    // SAFETY: all-zero bytes are a valid value for the plain C struct `tm`.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = year as i32 - 1900;
+    tm.tm_mon = month as i32 - 1;
+    tm.tm_mday = day as i32;
+    tm.tm_hour = hour as i32;
+    tm.tm_min = minute as i32;
+    tm.tm_sec = second as i32;
+    from_local_tm(tm)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FormatItem {
+    Literal(String),
+    Year,
+    ShortYear,
+    Month,
+    Day,
+    DayOfYear,
+    Hour,
+    Minute,
+    Second,
+    Millisecond,
+    Microsecond,
+    Nanosecond,
+    UtcOffset,
+    UtcOffsetWithColon,
+    EpochSeconds,
+}
+
+/// Format for rendering timestamps according to RFC 3339.
+pub const RFC3339: &str = "%Y-%m-%dT%H:%M:%S%:z";
+
+/// Format for the `ts` field of JSON output, RFC 3339 with milliseconds.
+pub const RFC3339_MILLIS: &str = "%Y-%m-%dT%H:%M:%S.%L%:z";
+
+/// Format for the message timestamps of `--timestamps`, which sort lexicographically.
+pub const SORTABLE: &str = "%Y-%m-%dT%H:%M:%S.%L";
+
+/// A strftime-like format for rendering timestamps.
+///
+/// Supported specifiers: `%Y`, `%y`, `%m`, `%d`, `%j`, `%H`, `%M`, `%S`, `%L` (milliseconds),
+/// `%f` (microseconds), `%N` (nanoseconds), `%z`, `%:z`, `%s`, `%F` (`%Y-%m-%d`),
+/// `%T` (`%H:%M:%S`) and `%%`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimestampFormat {
+    items: Vec<FormatItem>,
+}
+
+impl TimestampFormat {
+    pub fn parse(format: &str) -> Result<TimestampFormat> {
+        let mut items = vec![];
+        let mut literal = String::new();
+        let mut chars = format.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                literal.push(c);
+                continue;
+            }
+
+            let specifier = chars.next().ok_or(LucidError::InvalidTimestampFormat)?;
+            let new_items = match specifier {
+                '%' => {
+                    literal.push('%');
+                    continue;
+                }
+                'Y' => vec![FormatItem::Year],
+                'y' => vec![FormatItem::ShortYear],
+                'm' => vec![FormatItem::Month],
+                'd' => vec![FormatItem::Day],
+                'j' => vec![FormatItem::DayOfYear],
+                'H' => vec![FormatItem::Hour],
+                'M' => vec![FormatItem::Minute],
+                'S' => vec![FormatItem::Second],
+                'L' => vec![FormatItem::Millisecond],
+                'f' => vec![FormatItem::Microsecond],
+                'N' => vec![FormatItem::Nanosecond],
+                'z' => vec![FormatItem::UtcOffset],
+                ':' if chars.next() == Some('z') => vec![FormatItem::UtcOffsetWithColon],
+                's' => vec![FormatItem::EpochSeconds],
+                'F' => vec![
+                    FormatItem::Year,
+                    FormatItem::Literal("-".into()),
+                    FormatItem::Month,
+                    FormatItem::Literal("-".into()),
+                    FormatItem::Day,
+                ],
+                'T' => vec![
+                    FormatItem::Hour,
+                    FormatItem::Literal(":".into()),
+                    FormatItem::Minute,
+                    FormatItem::Literal(":".into()),
+                    FormatItem::Second,
+                ],
+                _ => return Err(LucidError::InvalidTimestampFormat),
+            };
+
+            if !literal.is_empty() {
+                items.push(FormatItem::Literal(std::mem::take(&mut literal)));
+            }
+            items.extend(new_items);
+        }
+        if !literal.is_empty() {
+            items.push(FormatItem::Literal(literal));
+        }
+
+        Ok(TimestampFormat { items })
+    }
+
+    /// Renders the given point in time in the local time zone.
+    pub fn render(&self, t: SystemTime) -> String {
+        let local = LocalTime::from_system_time(t);
+        let offset_sign = if local.utc_offset < 0 { '-' } else { '+' };
+        let offset_hours = local.utc_offset.abs() / 3600;
+        let offset_minutes = local.utc_offset.abs() % 3600 / 60;
+
+        self.items
+            .iter()
+            .map(|item| match item {
+                FormatItem::Literal(text) => text.clone(),
+                FormatItem::Year => format!("{:04}", local.year),
+                FormatItem::ShortYear => format!("{:02}", local.year.rem_euclid(100)),
+                FormatItem::Month => format!("{:02}", local.month),
+                FormatItem::Day => format!("{:02}", local.day),
+                FormatItem::DayOfYear => format!("{:03}", local.day_of_year),
+                FormatItem::Hour => format!("{:02}", local.hour),
+                FormatItem::Minute => format!("{:02}", local.minute),
+                FormatItem::Second => format!("{:02}", local.second),
+                FormatItem::Millisecond => format!("{:03}", local.nanosecond / 1_000_000),
+                FormatItem::Microsecond => format!("{:06}", local.nanosecond / 1_000),
+                FormatItem::Nanosecond => format!("{:09}", local.nanosecond),
+                FormatItem::UtcOffset => {
+                    format!("{}{:02}{:02}", offset_sign, offset_hours, offset_minutes)
+                }
+                FormatItem::UtcOffsetWithColon => {
+                    format!("{}{:02}:{:02}", offset_sign, offset_hours, offset_minutes)
+                }
+                FormatItem::EpochSeconds => local.epoch_seconds.to_string(),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_parse_timestamp_epoch() {
+    assert_eq!(Ok(time::UNIX_EPOCH), parse_timestamp("0"));
+    assert_eq!(
+        Ok(time::UNIX_EPOCH + time::Duration::from_millis(1_700_000_000_500)),
+        parse_timestamp("1700000000.5")
+    );
+    assert_eq!(
+        Ok(time::UNIX_EPOCH - time::Duration::from_secs(10)),
+        parse_timestamp("-10")
+    );
+}
+
+#[test]
+fn test_parse_timestamp_rfc3339() {
+    assert_eq!(
+        Ok(time::UNIX_EPOCH + time::Duration::from_secs(1_704_099_600)),
+        parse_timestamp("2024-01-01T09:00:00Z")
+    );
+    assert_eq!(
+        Ok(time::UNIX_EPOCH + time::Duration::from_secs(1_704_099_600)),
+        parse_timestamp("2024-01-01T10:00:00+01:00")
+    );
+    assert_eq!(
+        Ok(time::UNIX_EPOCH + time::Duration::from_millis(951_782_400_250)),
+        parse_timestamp("2000-02-29 00:00:00.25z")
+    );
+
+    assert_eq!(
+        Err(LucidError::TimestampParseError),
+        parse_timestamp("2024-01-01T09:00:00")
+    );
+    assert_eq!(
+        Err(LucidError::TimestampParseError),
+        parse_timestamp("2023-02-29T09:00:00Z")
+    );
+    assert_eq!(
+        Err(LucidError::TimestampParseError),
+        parse_timestamp("yesterday")
+    );
+    assert_eq!(
+        Err(LucidError::TimestampParseError),
+        parse_timestamp("202é-01-01T00:00:00Z")
+    );
+    assert_eq!(
+        Err(LucidError::TimestampParseError),
+        parse_timestamp("2024-01-01T09:00:00+0é:00")
+    );
+}
+
+#[test]
+fn test_parse_timestamp_out_of_range() {
+    assert_eq!(
+        Err(LucidError::TimestampParseError),
+        parse_timestamp("1e30")
+    );
+    assert_eq!(
+        Err(LucidError::TimestampParseError),
+        parse_timestamp("-1e30")
+    );
+}
+
+#[test]
+fn test_timestamp_format() {
+    let t = time::UNIX_EPOCH + time::Duration::from_millis(1_704_099_600_123);
+
+    let format = TimestampFormat::parse("%s.%L|%N|100%%").unwrap();
+    assert_eq!("1704099600.123|123000000|100%", format.render(t));
+
+    let sortable = TimestampFormat::parse(SORTABLE).unwrap().render(t);
+    assert_eq!(23, sortable.len());
+    assert!(sortable.ends_with(".123"));
+
+    let rendered = TimestampFormat::parse("%F %T").unwrap().render(t);
+    assert_eq!(19, rendered.len());
+    assert_eq!(Ok(t - time::Duration::from_millis(123)), {
+        let offset = TimestampFormat::parse("%:z").unwrap().render(t);
+        parse_timestamp(&format!("{}{}", rendered, offset))
+    });
+
+    assert_eq!(
+        Err(LucidError::InvalidTimestampFormat),
+        TimestampFormat::parse("%H:%M:%Q")
+    );
+    assert_eq!(
+        Err(LucidError::InvalidTimestampFormat),
+        TimestampFormat::parse("%H:%")
+    );
+}
+
+#[test]
+fn test_next_boundary() {
+    let t = time::UNIX_EPOCH + time::Duration::from_millis(1_704_099_630_500);
+    let next_minute = next_boundary(t, Boundary::Minute).unwrap();
+    assert_eq!(
+        Ok(time::Duration::from_millis(29_500)),
+        next_minute.duration_since(t).map_err(|_| ())
+    );
+    assert_eq!(
+        "00",
+        TimestampFormat::parse("%S").unwrap().render(next_minute)
+    );
+
+    let next_hour = next_boundary(t, Boundary::Hour).unwrap();
+    assert_eq!(
+        "00:00",
+        TimestampFormat::parse("%M:%S").unwrap().render(next_hour)
+    );
+
+    let next_day = next_boundary(t, Boundary::Day).unwrap();
+    assert_eq!(
+        "00:00:00",
+        TimestampFormat::parse("%T").unwrap().render(next_day)
+    );
+    assert!(next_day > t && next_day.duration_since(t).unwrap().as_secs() <= 25 * 3600);
+
+    assert_eq!(
+        next_minute,
+        next_boundary(
+            next_minute - time::Duration::from_nanos(1),
+            Boundary::Minute
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_parse_deadline() {
+    let now = time::UNIX_EPOCH + time::Duration::from_millis(1_704_099_630_500);
+    let time_of_day = TimestampFormat::parse("%H:%M:%S").unwrap();
+
+    let deadline = parse_deadline("14:30", now).unwrap();
+    assert!(deadline > now && deadline.duration_since(now).unwrap().as_secs() <= 25 * 3600);
+    assert_eq!("14:30:00", time_of_day.render(deadline));
+
+    let current = time_of_day.render(now);
+    let tomorrow = parse_deadline(&current[..5], now).unwrap();
+    assert!(tomorrow.duration_since(now).unwrap().as_secs() >= 23 * 3600 - 60);
+
+    let local = parse_deadline("2024-01-01T09:00", now).unwrap();
+    assert_eq!("09:00:00", time_of_day.render(local));
+    assert_eq!(parse_deadline("2024-01-01T09:00:00", now), Ok(local));
+    assert_eq!(
+        parse_timestamp("2024-01-01T09:00:00Z"),
+        parse_deadline("2024-01-01T09:00:00Z", now)
+    );
+
+    assert_eq!(
+        Err(LucidError::TimestampParseError),
+        parse_deadline("25:00", now)
+    );
+    assert_eq!(
+        Err(LucidError::TimestampParseError),
+        parse_deadline("2024-02-30T09:00", now)
+    );
+    assert_eq!(
+        Err(LucidError::TimestampParseError),
+        parse_deadline("tomorrow", now)
+    );
+}
@@ -0,0 +1,85 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time;
+
+use crate::{LucidError, Result};
+
+/// A file whose modification time shows that lucid is alive, for liveness checks based on
+/// file freshness. When dropped, the file is removed or, if a final marker was given, that
+/// marker is written to it.
+pub struct TouchFile {
+    path: Option<PathBuf>,
+    final_marker: Option<String>,
+}
+
+impl TouchFile {
+    /// Creates the file (if necessary) and touches it for the first time.
+    pub fn create(path: &Path, final_marker: Option<String>) -> Result<TouchFile> {
+        let file = TouchFile {
+            path: Some(path.into()),
+            final_marker,
+        };
+        file.touch().map_err(|_| LucidError::TouchFileError)?;
+        Ok(file)
+    }
+
+    /// Sets the modification time to now.
+    pub fn touch(&self) -> io::Result<()> {
+        match self.path {
+            Some(ref path) => fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?
+                .set_modified(time::SystemTime::now()),
+            None => Ok(()),
+        }
+    }
+
+    /// Removes the file or writes the final marker, reporting failures (in contrast to
+    /// dropping the `TouchFile`).
+    pub fn finish(mut self) -> Result<()> {
+        match self.path.take() {
+            Some(path) => {
+                finish(&path, self.final_marker.as_deref()).map_err(|_| LucidError::TouchFileError)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+fn finish(path: &Path, final_marker: Option<&str>) -> io::Result<()> {
+    match final_marker {
+        Some(marker) => fs::write(path, format!("{}\n", marker)),
+        None => fs::remove_file(path),
+    }
+}
+
+impl Drop for TouchFile {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.path {
+            finish(path, self.final_marker.as_deref()).ok();
+        }
+    }
+}
+
+#[test]
+fn test_touch_file() {
+    let path = crate::TempPath::new("touch");
+
+    let file = TouchFile::create(&path, None).unwrap();
+    let first = fs::metadata(&path).unwrap().modified().unwrap();
+    std::thread::sleep(time::Duration::from_millis(20));
+    file.touch().unwrap();
+    assert!(fs::metadata(&path).unwrap().modified().unwrap() > first);
+    file.finish().unwrap();
+    assert!(!path.exists());
+
+    drop(TouchFile::create(&path, Some("done".into())).unwrap());
+    assert_eq!("done\n", fs::read_to_string(&path).unwrap());
+
+    assert_eq!(
+        Err(LucidError::TouchFileError),
+        TouchFile::create(&path.join("missing"), None).map(|_| ())
+    );
+}
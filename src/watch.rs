@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What to wait for, see `--wait-for`, `--wait-for-gone` and `--wait-for-change`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileCondition {
+    Exists,
+    Gone,
+    Changed,
+}
+
+/// The parts of a file's metadata that are compared to detect changes (`None` if the file
+/// does not exist).
+type Fingerprint = Option<(Option<SystemTime>, u64)>;
+
+fn fingerprint(path: &Path) -> Fingerprint {
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| (metadata.modified().ok(), metadata.len()))
+}
+
+/// Watches a path for a `FileCondition` by probing its metadata.
+pub struct FileWatch {
+    path: PathBuf,
+    condition: FileCondition,
+    initial: Fingerprint,
+}
+
+impl FileWatch {
+    /// Starts watching. Changes are detected relative to the state at this point.
+    pub fn new(path: &Path, condition: FileCondition) -> FileWatch {
+        FileWatch {
+            path: path.into(),
+            condition,
+            initial: fingerprint(path),
+        }
+    }
+
+    pub fn is_met(&self) -> bool {
+        match self.condition {
+            FileCondition::Exists => self.path.exists(),
+            FileCondition::Gone => !self.path.exists(),
+            FileCondition::Changed => fingerprint(&self.path) != self.initial,
+        }
+    }
+
+    /// Describes the event that is waited for, e.g. `'/tmp/ready' to appear`.
+    pub fn describe(&self) -> String {
+        let event = match self.condition {
+            FileCondition::Exists => "appear",
+            FileCondition::Gone => "disappear",
+            FileCondition::Changed => "change",
+        };
+        format!("'{}' to {}", self.path.display(), event)
+    }
+}
+
+#[test]
+fn test_file_watch() {
+    let path = crate::TempPath::new("watch");
+
+    let exists = FileWatch::new(&path, FileCondition::Exists);
+    let gone = FileWatch::new(&path, FileCondition::Gone);
+    let changed = FileWatch::new(&path, FileCondition::Changed);
+    assert!(!exists.is_met());
+    assert!(gone.is_met());
+    assert!(!changed.is_met());
+
+    fs::write(&path, "ready").unwrap();
+    assert!(exists.is_met());
+    assert!(!gone.is_met());
+    assert!(changed.is_met());
+
+    let changed = FileWatch::new(&path, FileCondition::Changed);
+    assert!(!changed.is_met());
+    fs::write(&path, "ready again").unwrap();
+    assert!(changed.is_met());
+
+    assert_eq!(format!("'{}' to appear", path.display()), exists.describe());
+}
@@ -0,0 +1,68 @@
+use std::time;
+
+/// Whether lucid was built with support for `--heartbeat-url`.
+pub const SUPPORTED: bool = cfg!(feature = "webhook");
+
+/// How long to wait for the server to respond to a heartbeat.
+#[cfg(feature = "webhook")]
+const TIMEOUT: time::Duration = time::Duration::from_secs(2);
+
+#[cfg_attr(not(feature = "webhook"), allow(dead_code))]
+fn heartbeat_body(
+    pid: u32,
+    elapsed: &time::Duration,
+    remaining: Option<&time::Duration>,
+) -> String {
+    format!(
+        "{{\"pid\":{},\"elapsed\":{},\"remaining\":{}}}",
+        pid,
+        elapsed.as_secs_f64(),
+        remaining
+            .map(|r| r.as_secs_f64().to_string())
+            .unwrap_or_else(|| "null".into())
+    )
+}
+
+/// Sends a heartbeat to the given URL as a JSON-encoded HTTP POST request.
+#[cfg(feature = "webhook")]
+pub fn post_heartbeat(
+    url: &str,
+    pid: u32,
+    elapsed: &time::Duration,
+    remaining: Option<&time::Duration>,
+) -> Result<(), String> {
+    ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .build()
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(&heartbeat_body(pid, elapsed, remaining))
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "webhook"))]
+pub fn post_heartbeat(
+    _url: &str,
+    _pid: u32,
+    _elapsed: &time::Duration,
+    _remaining: Option<&time::Duration>,
+) -> Result<(), String> {
+    Err("lucid was built without the 'webhook' feature".into())
+}
+
+#[test]
+fn test_heartbeat_body() {
+    assert_eq!(
+        "{\"pid\":42,\"elapsed\":1.5,\"remaining\":null}",
+        heartbeat_body(42, &time::Duration::from_millis(1500), None)
+    );
+    assert_eq!(
+        "{\"pid\":1,\"elapsed\":0,\"remaining\":2.25}",
+        heartbeat_body(
+            1,
+            &time::Duration::from_secs(0),
+            Some(&time::Duration::from_millis(2250))
+        )
+    );
+}
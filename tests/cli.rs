@@ -0,0 +1,2162 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+#[cfg(unix)]
+use std::{
+    process::Child,
+    thread,
+    time::{Duration, Instant},
+};
+
+fn lucid(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(args)
+        .output()
+        .expect("failed to run lucid")
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("lucid-test-{}-{}", name, std::process::id()))
+}
+
+#[cfg(unix)]
+fn send_signal(child: &Child, signal: &str) {
+    Command::new("kill")
+        .args([&format!("-{}", signal), &child.id().to_string()])
+        .status()
+        .expect("failed to run kill");
+}
+
+#[test]
+fn quiet_with_exit_code_file() {
+    let path = temp_path("exit-code-file");
+    fs::write(&path, "42\n").unwrap();
+
+    let output = lucid(&["0.1", "--quiet", "--exit-code-file", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(Some(42), output.status.code());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn unreadable_exit_code_file_does_not_skip_the_cleanup() {
+    let lock = temp_path("exit-code-file-lock");
+    let missing = temp_path("exit-code-file-missing");
+
+    let output = lucid(&[
+        "0",
+        "--verbose",
+        "--lock",
+        lock.to_str().unwrap(),
+        "--exit-code-file",
+        missing.to_str().unwrap(),
+    ]);
+    fs::remove_file(&lock).ok();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Released the lock"), "{}", stdout);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Could not read an exit code"));
+}
+
+#[test]
+fn countdown_is_only_drawn_on_a_terminal() {
+    let output = lucid(&["0.3", "--countdown"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains('\r'), "{:?}", stdout);
+    assert_eq!(
+        String::from_utf8_lossy(&lucid(&["0.3"]).stdout)
+            .lines()
+            .count(),
+        stdout.lines().count()
+    );
+}
+
+#[test]
+fn barrier_timeout_uses_timeout_code() {
+    let path = temp_path("barrier-timeout");
+    let barrier = path.to_str().unwrap();
+    fs::remove_file(&path).ok();
+
+    let output = lucid(&["0.2", "--barrier", barrier, "--barrier-count", "2"]);
+    assert_eq!(Some(124), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Timed out"));
+
+    fs::remove_file(&path).ok();
+    let output = lucid(&[
+        "0.2",
+        "--barrier",
+        barrier,
+        "--barrier-count",
+        "2",
+        "--timeout-code",
+        "7",
+    ]);
+    assert_eq!(Some(7), output.status.code());
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn then_runs_command_only_after_normal_completion() {
+    let output = lucid(&["0.1", "--quiet", "--then", "--", "echo", "done"]);
+    assert!(output.status.success());
+    assert_eq!("done\n", String::from_utf8_lossy(&output.stdout));
+
+    let output = lucid(&["0.1", "--quiet", "--then", "--", "sh", "-c", "exit 3"]);
+    assert_eq!(Some(3), output.status.code());
+
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["10", "--quiet", "--then", "--", "echo", "done"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().unwrap();
+    assert!(output.stdout.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn forward_ignored_sends_ignored_signals_to_the_then_command() {
+    use std::os::unix::process::ExitStatusExt;
+
+    let run = |forward: bool| {
+        let mut args = vec!["0.3", "--no-interrupt", "--quiet", "--then"];
+        if forward {
+            args.insert(3, "--forward-ignored");
+        }
+        let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+            .args(args)
+            .args(["--", "sleep", "1"])
+            .spawn()
+            .unwrap();
+        thread::sleep(Duration::from_millis(100));
+        send_signal(&child, "TERM");
+        let start = std::time::Instant::now();
+        let status = child.wait_with_output().unwrap().status;
+        (status, start.elapsed())
+    };
+
+    let (status, elapsed) = run(true);
+    assert_eq!(Some(15), status.signal());
+    assert!(elapsed < Duration::from_millis(800), "{:?}", elapsed);
+
+    let (status, elapsed) = run(false);
+    assert!(status.success());
+    assert!(elapsed >= Duration::from_millis(1000), "{:?}", elapsed);
+}
+
+#[test]
+fn listen_accepts_connections_once_ready() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpStream;
+
+    let start = std::time::Instant::now();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args([
+            "2",
+            "--verbose",
+            "--listen",
+            "127.0.0.1:0",
+            "--ready-after",
+            "0.2",
+        ])
+        .args(["--http-response", "ready"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let address = stdout
+        .lines()
+        .map(Result::unwrap)
+        .find_map(|line| line.split("Listening on ").nth(1).map(String::from))
+        .unwrap();
+    assert!(start.elapsed().as_secs_f64() >= 0.2);
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.ends_with("ready"), "{}", response);
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn control_socket_drives_a_running_instance() {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+
+    let path = temp_path("control-socket");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args([
+            "10",
+            "--control-socket",
+            path.to_str().unwrap(),
+            "--summary",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let stream = loop {
+        match UnixStream::connect(&path) {
+            Ok(stream) => break stream,
+            Err(_) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+            Err(err) => {
+                child.kill().ok();
+                let output = child.wait_with_output().unwrap();
+                panic!(
+                    "could not connect to the control socket: {}\n{}",
+                    err,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+        }
+    };
+    let mut writer = stream.try_clone().unwrap();
+    let mut lines = BufReader::new(stream).lines();
+    let mut command = |line: &str| {
+        writeln!(writer, "{}", line).unwrap();
+        lines.next().unwrap().unwrap()
+    };
+
+    assert!(command("STATUS").starts_with("Status: PID "));
+    assert_eq!("ERROR unknown command 'SNOOZE'", command("snooze"));
+    assert_eq!("OK", command("SET-VERBOSITY verbose"));
+    assert_eq!("OK", command("EXIT 7"));
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(Some(7), output.status.code());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Woken up via the control socket."),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Main loop ran for"), "{}", stdout);
+    assert!(stdout.contains("\"reason\":\"control_exit\""), "{}", stdout);
+    assert!(!path.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn touch_file_is_kept_fresh_while_sleeping() {
+    let path = temp_path("touch");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["10", "--touch", path.to_str().unwrap()])
+        .args(["--touch-interval", "100ms"])
+        .spawn()
+        .unwrap();
+    // Wait until the modification time advanced twice, however slow the machine is
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut mtimes = vec![];
+    while mtimes.len() < 3 {
+        assert!(
+            Instant::now() < deadline,
+            "modification times: {:?}",
+            mtimes
+        );
+        if let Ok(mtime) = fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+            if mtimes.last() != Some(&mtime) {
+                mtimes.push(mtime);
+            }
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    send_signal(&child, "TERM");
+    assert!(child.wait().unwrap().success());
+    assert!(!path.exists());
+
+    let output = lucid(&[
+        "0.1",
+        "--touch",
+        path.to_str().unwrap(),
+        "--touch-final",
+        "done",
+    ]);
+    assert!(output.status.success());
+    assert_eq!("done\n", fs::read_to_string(&path).unwrap());
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn removed_touch_file_does_not_skip_the_cleanup() {
+    let path = temp_path("touch-removed");
+    let lock = temp_path("touch-removed-lock");
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["1", "--verbose", "--touch", path.to_str().unwrap()])
+        .args(["--touch-interval", "1h", "--lock", lock.to_str().unwrap()])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while fs::remove_file(&path).is_err() {
+        assert!(
+            Instant::now() < deadline,
+            "the touch file was never created"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&lock).ok();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Released the lock"), "{}", stdout);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'--touch' file"));
+}
+
+#[test]
+fn disk_io_at_the_given_rate() {
+    let path = temp_path("disk-io");
+    let file = path.to_str().unwrap();
+    let output = lucid(&[
+        "0.3",
+        "--write-rate",
+        "1M/s",
+        "--write-file",
+        file,
+        "--fsync",
+    ]);
+    assert!(output.status.success());
+    let written = fs::metadata(&path).unwrap().len();
+    assert!((200_000..=500_000).contains(&written), "{}", written);
+
+    fs::write(&path, "").unwrap();
+    let output = lucid(&["0.3", "--read-rate", "1M/s", "--read-file", file]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Disk I/O failed, stopping it: empty file"),
+        "{}",
+        stdout
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn spam_writes_lines_at_the_given_rate() {
+    let output = lucid(&[
+        "0.5",
+        "--quiet",
+        "--spam-stdout",
+        "100/s",
+        "--spam-stderr",
+        "20",
+        "--line-length",
+        "20",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert!((40..=55).contains(&lines.len()), "{} lines", lines.len());
+    assert!(lines.iter().all(|line| line.len() == 20), "{:?}", lines);
+    assert_eq!("000000000 abcdefghij", lines[0]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!((8..=12).contains(&stderr.lines().count()), "{}", stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn lock_waits_for_the_current_holder() {
+    let path = temp_path("lock");
+    let path = path.to_str().unwrap();
+    let holder = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.5", "--lock", path, "--lock-mode", "fcntl"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    let output = lucid(&[
+        "0.1",
+        "--lock",
+        path,
+        "--lock-mode",
+        "fcntl",
+        "--lock-shared",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("[lucid]: Waiting for the shared fcntl lock on "),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Acquired the shared fcntl lock"),
+        "{}",
+        stdout
+    );
+    let holder = holder.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&holder.stdout).contains("Acquired the exclusive fcntl lock"));
+
+    let mut holder = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["1", "--quiet", "--lock", path])
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+    let waiting = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.1", "--lock", path, "--interrupt-exit-code", "9"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+    send_signal(&waiting, "TERM");
+    let output = waiting.wait_with_output().unwrap();
+    assert_eq!(Some(9), output.status.code());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Caught SIGTERM"), "{}", stdout);
+    assert!(
+        stdout.contains("interrupting the wait for the lock."),
+        "{}",
+        stdout
+    );
+    send_signal(&holder, "TERM");
+    holder.wait().unwrap();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn open_fds_are_held_while_sleeping() {
+    let count_fds = |args: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+            .args(["0.5", "--quiet"])
+            .args(args)
+            .spawn()
+            .unwrap();
+        thread::sleep(Duration::from_millis(250));
+        let count = fs::read_dir(format!("/proc/{}/fd", child.id()))
+            .unwrap()
+            .count();
+        child.wait().unwrap();
+        count
+    };
+    let baseline = count_fds(&[]);
+    assert_eq!(baseline + 25, count_fds(&["--open-fds", "25"]));
+    assert_eq!(
+        baseline + 7,
+        count_fds(&["--open-fds", "7", "--fd-target", "pipe"])
+    );
+
+    let output = lucid(&["0.1", "--open-fds", "2", "--fd-target", "/nonexistent"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Could not open descriptor 1 of 2"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn threads_are_spawned_and_named() {
+    let thread_names = |args: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+            .args(["0.5", "--quiet"])
+            .args(args)
+            .spawn()
+            .unwrap();
+        thread::sleep(Duration::from_millis(250));
+        let mut names: Vec<_> = fs::read_dir(format!("/proc/{}/task", child.id()))
+            .unwrap()
+            .map(|task| fs::read_to_string(task.unwrap().path().join("comm")).unwrap())
+            .map(|name| name.trim_end().to_string())
+            .collect();
+        names.sort();
+        child.wait().unwrap();
+        names
+    };
+    let baseline = thread_names(&[]).len();
+    assert_eq!(baseline + 12, thread_names(&["--threads", "12"]).len());
+
+    let names = thread_names(&["--threads", "2", "--thread-name", "worker"]);
+    assert!(names.contains(&"worker-1".to_string()), "{:?}", names);
+    assert!(names.contains(&"worker-2".to_string()), "{:?}", names);
+    assert!(!names.contains(&"worker-3".to_string()), "{:?}", names);
+
+    let names = thread_names(&[
+        "--busy",
+        "--load",
+        "1%",
+        "--threads",
+        "3",
+        "--thread-name",
+        "spin",
+    ]);
+    assert!(names.contains(&"spin-3".to_string()), "{:?}", names);
+    assert_eq!(baseline + 3, names.len());
+
+    assert_eq!(Some(1), lucid(&["0.1", "--threads", "0"]).status.code());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn rlimits_are_applied_at_startup() {
+    let output = lucid(&["0.1", "--verbose", "--rlimit", "NOFILE=64:128,CPU=5"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Set RLIMIT_NOFILE to 64 (hard limit: 128)"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Set RLIMIT_CPU to 5"), "{}", stdout);
+
+    let output = lucid(&["0.1", "--rlimit", "NOFILE=64", "--open-fds", "100"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Could not open descriptor"));
+
+    // The soft limit must not exceed the hard limit
+    let output = lucid(&["0.1", "--rlimit", "NOFILE=64:32"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Could not set RLIMIT_NOFILE to 64"));
+
+    assert_eq!(Some(1), lucid(&["0.1", "--rlimit", "NICE=5"]).status.code());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn user_and_group_are_switched_after_startup() {
+    let ids = |pid: u32, field: &str| {
+        let status = fs::read_to_string(format!("/proc/{}/status", pid)).unwrap();
+        let line = status.lines().find(|l| l.starts_with(field)).unwrap();
+        line.split_whitespace().nth(1).unwrap().to_string()
+    };
+    let own_uid = ids(std::process::id(), "Uid:");
+
+    // Switching to the current user works without privileges
+    let output = lucid(&["0.1", "--verbose", "--user", &own_uid]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Switched to user"));
+
+    let output = lucid(&["0.1", "--user", "no-such-user-lucid"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("There is no user"));
+
+    if own_uid != "0" {
+        return;
+    }
+    let pidfile = temp_path("user.pid");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.5", "--quiet", "--user", "65534", "--group", "65533"])
+        .arg("--pidfile")
+        .arg(&pidfile)
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(250));
+    assert_eq!("65534", ids(child.id(), "Uid:"));
+    assert_eq!("65533", ids(child.id(), "Gid:"));
+    assert!(pidfile.exists());
+    assert!(child.wait().unwrap().success());
+    fs::remove_file(pidfile).ok();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn nice_and_cpu_affinity_are_inherited_by_threads() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.5", "--quiet", "--nice", "7", "--cpu-affinity", "0"])
+        .args(["--threads", "2"])
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(250));
+    let tasks: Vec<_> = fs::read_dir(format!("/proc/{}/task", child.id()))
+        .unwrap()
+        .map(|task| task.unwrap().path())
+        .collect();
+    assert!(tasks.len() >= 3);
+    for task in tasks {
+        // The nice value is the 19th field, counted after the parenthesized command name
+        let stat = fs::read_to_string(task.join("stat")).unwrap();
+        let fields: Vec<_> = stat
+            .rsplit(')')
+            .next()
+            .unwrap()
+            .split_whitespace()
+            .collect();
+        assert_eq!("7", fields[16]);
+
+        let status = fs::read_to_string(task.join("status")).unwrap();
+        assert!(status.contains("Cpus_allowed_list:\t0\n"), "{}", status);
+    }
+    assert!(child.wait().unwrap().success());
+
+    let output = lucid(&["0.1", "--verbose", "--nice", "3"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Set the nice value to 3"));
+    assert_eq!(
+        Some(1),
+        lucid(&["0.1", "--cpu-affinity", "2-1"]).status.code()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn die_with_parent_exits_once_reparented() {
+    // The shell exits after a short while, and lucid keeps the pipe open until it exits
+    let run_in_shell = |args: &str| {
+        let start = std::time::Instant::now();
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "{} {} & sleep 0.3",
+                env!("CARGO_BIN_EXE_lucid"),
+                args
+            ))
+            .output()
+            .unwrap();
+        (
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            start.elapsed(),
+        )
+    };
+
+    let (stdout, elapsed) = run_in_shell("10 --die-with-parent");
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    assert!(stdout.contains("exited - exiting."), "{}", stdout);
+
+    let (stdout, elapsed) = run_in_shell("1 --verbose");
+    assert!(elapsed >= Duration::from_secs(1), "{:?}", elapsed);
+    assert!(stdout.contains("getppid() changed from"), "{}", stdout);
+    assert!(stdout.contains("Woke up after"), "{}", stdout);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn core_is_dumped_on_demand() {
+    use std::os::unix::process::ExitStatusExt;
+
+    // Without a hard limit for core files, the kernel writes them (also with a soft limit of 0)
+    let limits = fs::read_to_string("/proc/self/limits").unwrap();
+    let core_limit = limits.lines().find(|l| l.starts_with("Max core file size"));
+    let can_dump = core_limit.is_some_and(|l| l.split_whitespace().nth(5) != Some("0"));
+
+    let directory = temp_path("core");
+    fs::create_dir_all(&directory).unwrap();
+    let lucid_in_directory = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_lucid"))
+            .args(args)
+            .current_dir(&directory)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap()
+    };
+
+    let child = lucid_in_directory(&["10", "--crash-after", "0.1", "--crash-mode", "core"]);
+    let status = child.wait_with_output().unwrap().status;
+    assert_eq!(Some(3), status.signal());
+    assert_eq!(can_dump, status.core_dumped());
+
+    let child = lucid_in_directory(&["10", "--dump-core-on", "SIGUSR2"]);
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "USR2");
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(Some(3), output.status.signal());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Received SIGUSR2 - dumping core."));
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn dump_state_shows_the_inherited_state() {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "trap '' USR1; umask 027; exec {} 0 --dump-state 3</dev/null",
+            env!("CARGO_BIN_EXE_lucid")
+        ))
+        .env("LUCID_TEST_STATE", "probe")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for expected in [
+        "environ: LUCID_TEST_STATE=\"probe\"",
+        "umask() = 0027",
+        "fd 0 -> /dev/null",
+        "fd 3 -> /dev/null\n",
+        "getrlimit(RLIMIT_NOFILE) = ",
+        "Ignored signals: SIGUSR1",
+        "Blocked signals: none",
+    ] {
+        assert!(stdout.contains(expected), "{} not in {}", expected, stdout);
+    }
+
+    let output = lucid(&["0"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("umask"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn verbose_output_shows_the_cgroup() {
+    let output = lucid(&["0", "--verbose"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("cgroup"), "{}", stdout);
+    assert!(!stdout.contains("Running as PID 1"), "{}", stdout);
+    assert!(!String::from_utf8_lossy(&lucid(&["0"]).stdout).contains("cgroup"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn init_reaps_orphans_and_exits_with_the_main_process() {
+    let output = lucid(&["--init", "--then", "--", "sh", "-c", "exit 3"]);
+    assert_eq!(Some(3), output.status.code());
+
+    // The subshell exits right away, so its `sleep` is reparented to lucid (a subreaper)
+    let output = lucid(&[
+        "--init",
+        "--verbose",
+        "--then",
+        "--",
+        "sh",
+        "-c",
+        "(sleep 0.1 &); sleep 0.5",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(Some(0), output.status.code());
+    assert!(stdout.contains("Reaped process"), "{}", stdout);
+    assert!(stdout.contains("exited (exit code 0)."), "{}", stdout);
+
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["--init", "--then", "--", "sh", "-c"])
+        .arg("trap 'exit 7' TERM; sleep 5 & wait")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(Some(7), output.status.code());
+    assert!(
+        stdout.contains("Forwarded SIGTERM to 1 child process"),
+        "{}",
+        stdout
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn stops_are_reported_once_continued() {
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["1.5", "--report-stops"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+    send_signal(&child, "TSTP");
+    thread::sleep(Duration::from_millis(500));
+    send_signal(&child, "CONT");
+    thread::sleep(Duration::from_millis(200));
+    send_signal(&child, "STOP");
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "CONT");
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(
+        stdout.contains("Received SIGTSTP - stopping."),
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        2,
+        stdout
+            .matches("Continued after being stopped for 0.")
+            .count(),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("counted towards the sleep"), "{}", stdout);
+}
+
+#[test]
+fn fork_runs_instances_and_aggregates_exit_codes() {
+    let output = lucid(&["0.1", "--fork", "3", "--exit-code", "4"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(Some(4), output.status.code());
+    for prefix in ["[lucid-1]: ", "[lucid-2]: ", "[lucid-3]: "] {
+        assert!(
+            stdout.contains(&format!("{}Woke up after", prefix)),
+            "{}",
+            stdout
+        );
+    }
+    assert!(
+        stdout.contains("3 of 3 instances failed (#1, #2, #3), the highest exit code is 4."),
+        "{}",
+        stdout
+    );
+
+    let output = lucid(&["0", "--fork", "0"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[cfg(unix)]
+#[test]
+fn fork_forwards_termination_signals() {
+    let start = std::time::Instant::now();
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["10", "--fork", "2"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(output.status.success());
+    assert!(stdout.contains("to 2 instances"), "{}", stdout);
+    assert!(
+        stdout.contains("All 2 instances exited successfully."),
+        "{}",
+        stdout
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn forwarded_signals_reach_grandchildren() {
+    let pid_file = temp_path("forward-grandchild");
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args([
+            "--init",
+            "--forward-signals",
+            "--verbose",
+            "--then",
+            "--",
+            "sh",
+            "-c",
+        ])
+        .arg(format!("sleep 30 & echo $! > {}; wait", pid_file.display()))
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Forwarded SIGTERM to process group"),
+        "{}",
+        stdout
+    );
+
+    // The `sleep` was only reached via its process group
+    let grandchild = fs::read_to_string(&pid_file).unwrap();
+    fs::remove_file(&pid_file).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    let alive = Command::new("kill")
+        .args(["-0", grandchild.trim()])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!alive.success(), "{}", stdout);
+
+    // The children handle SIGUSR1 themselves
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["10", "--children", "2", "--forward-signals"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "USR1");
+    thread::sleep(Duration::from_millis(200));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(3, stdout.matches("Status: PID").count(), "{}", stdout);
+    assert_eq!(3, stdout.matches("Caught SIGTERM").count(), "{}", stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn interrupt_exit_code_after_sigterm() {
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["10", "--exit-code", "5", "--interrupt-exit-code", "130"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(Some(130), output.status.code());
+
+    let output = lucid(&["0.1", "--exit-code", "5", "--interrupt-exit-code", "130"]);
+    assert_eq!(Some(5), output.status.code());
+}
+
+#[cfg(unix)]
+#[test]
+fn signal_exit_codes_after_sighup() {
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["10", "--exit-code", "5", "--signal-exit-codes"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "HUP");
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(Some(129), output.status.code());
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("Exiting with code 129 because of SIGHUP")
+    );
+
+    let output = lucid(&["0.1", "--exit-code-on-interrupt", "7", "--exit-code", "5"]);
+    assert_eq!(Some(5), output.status.code());
+}
+
+#[cfg(unix)]
+#[test]
+fn pidfile_contains_pid_and_is_removed() {
+    let path = temp_path("pidfile");
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["10", "--quiet", "--pidfile", path.to_str().unwrap()])
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(
+        format!("{}\n", child.id()),
+        fs::read_to_string(&path).unwrap()
+    );
+
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(!path.exists());
+}
+
+#[test]
+fn pidfile_is_removed_when_the_accuracy_is_not_met() {
+    let path = temp_path("pidfile-accuracy");
+    let output = lucid(&[
+        "0.01",
+        "--require-accuracy",
+        "0",
+        "--max-retries",
+        "0",
+        "--pidfile",
+        path.to_str().unwrap(),
+    ]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("required accuracy"));
+    assert!(!path.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn pid_file_contains_the_pid_of_the_daemon() {
+    let path = temp_path("pid-file-daemon");
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args([
+            "10",
+            "--quiet",
+            "--daemon",
+            "--pid-file",
+            path.to_str().unwrap(),
+        ])
+        .spawn()
+        .unwrap();
+    let parent = child.id();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    thread::sleep(Duration::from_millis(300));
+    let pid = fs::read_to_string(&path).unwrap().trim().to_string();
+    assert_ne!(parent.to_string(), pid);
+
+    Command::new("kill").args(["-TERM", &pid]).status().unwrap();
+    for _ in 0..20 {
+        if !path.exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert!(!path.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn daemon_options_set_up_the_daemon_process() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_path("daemon-dir");
+    fs::create_dir_all(&dir).unwrap();
+    let stdout = temp_path("daemon-stdout");
+    fs::remove_file(&stdout).ok();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args([
+            "0.3",
+            "--daemon",
+            "--pidfile",
+            "lucid.pid",
+            "--umask",
+            "077",
+        ])
+        .arg("--chdir")
+        .arg(&dir)
+        .arg("--daemon-stdout")
+        .arg(&stdout)
+        .args([
+            "--daemon-stdin",
+            "/dev/null",
+            "--daemon-stderr",
+            "/dev/null",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    thread::sleep(Duration::from_millis(150));
+    let pidfile = dir.join("lucid.pid");
+    let mode = fs::metadata(&pidfile).unwrap().permissions().mode();
+    assert_eq!(0o600, mode & 0o777);
+
+    let mut content = String::new();
+    for _ in 0..20 {
+        thread::sleep(Duration::from_millis(50));
+        content = fs::read_to_string(&stdout).unwrap();
+        if content.contains("Woke up after") {
+            break;
+        }
+    }
+    fs::remove_file(&stdout).ok();
+    fs::remove_dir_all(&dir).ok();
+    assert!(content.contains("[lucid]: Going to sleep for 0.300s\n"));
+
+    let output = lucid(&["0.1", "--daemon", "--umask", "888"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[test]
+fn random_duration_is_reproducible_with_seed() {
+    let run = |seed: &str| {
+        let output = lucid(&["--random", "0.05", "0.2", "--seed", seed, "--verbose"]);
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find(|line| line.contains("Randomly chose"))
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(run("42"), run("42"));
+
+    let output = lucid(&["--random", "2", "1"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[test]
+fn random_exit_codes_are_reproducible_with_seed() {
+    let codes = |args: &[&str]| {
+        (0..20)
+            .map(|seed| {
+                let seed = seed.to_string();
+                let mut args = args.to_vec();
+                args.extend(["--seed", &seed]);
+                lucid(&args).status.code().unwrap()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let random = codes(&["0", "--exit-code", "random:3-5"]);
+    assert!(
+        random.iter().all(|code| (3..=5).contains(code)),
+        "{:?}",
+        random
+    );
+    assert!(random.iter().any(|&code| code != random[0]), "{:?}", random);
+    assert_eq!(random, codes(&["0", "--exit-code", "random:3-5"]));
+
+    let failing = codes(&["0", "--fail-probability", "0.5", "--exit-code", "4"]);
+    assert!(
+        failing.contains(&0) && failing.contains(&4),
+        "{:?}",
+        failing
+    );
+    assert!(failing.iter().all(|&code| code == 0 || code == 4));
+    assert_eq!(vec![1; 20], codes(&["0", "--fail-probability", "1"]));
+
+    assert_eq!(
+        Some(1),
+        lucid(&["0", "--exit-code", "random:5-3"]).status.code()
+    );
+    assert_eq!(
+        Some(1),
+        lucid(&["0", "--fail-probability", "1.5"]).status.code()
+    );
+}
+
+#[test]
+fn jitter_is_bounded_and_reproducible_with_seed() {
+    let run = |seed: &str| {
+        let output = lucid(&["0.2", "--jitter", "50%", "--seed", seed, "--format", "csv"]);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let requested: f64 = stdout.split(',').next().unwrap().parse().unwrap();
+        assert!((0.1..=0.3).contains(&requested), "{}", stdout);
+        requested
+    };
+    assert_eq!(run("7"), run("7"));
+
+    let output = lucid(&["0.1", "--jitter", "101%"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[test]
+fn large_poll_interval_does_not_overshoot() {
+    let output = lucid(&["0.3", "--poll-interval", "250", "--format", "csv"]);
+    assert!(output.status.success());
+    let actual = actual_seconds(&output);
+    assert!((0.3..0.4).contains(&actual), "slept for {}s", actual);
+
+    let output = lucid(&["0.3", "--poll-interval", "400"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[test]
+fn many_short_cycles_do_not_drift() {
+    let output = lucid(&["1", "--poll-interval", "1", "--format", "csv"]);
+    assert!(output.status.success());
+    let actual = actual_seconds(&output);
+    assert!((actual - 1.0).abs() < 0.02, "slept for {}s", actual);
+}
+
+#[test]
+fn sleeping_does_not_poll() {
+    let output = lucid(&["0.5", "--verbose"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Main loop ran for 2 cycles"), "{}", stdout);
+
+    let output = lucid(&["0.5", "--verbose", "--interval", "100ms"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Main loop ran for 6 cycles"), "{}", stdout);
+}
+
+#[test]
+fn heartbeat_is_printed_even_when_quiet() {
+    let output = lucid(&["0.35", "--quiet", "--heartbeat", "100ms"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert!(lines.len() >= 2, "{:?}", lines);
+    assert!(lines
+        .iter()
+        .all(|line| line.starts_with("[lucid]: Still sleeping, ") && line.ends_with(" remaining")));
+
+    let output = lucid(&["0.1", "--quiet", "--heartbeat", "1s"]);
+    assert!(output.stdout.is_empty());
+
+    let output = lucid(&["0.25", "--interval", "100ms"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(2, stdout.matches("Still sleeping, ").count(), "{}", stdout);
+}
+
+#[test]
+fn progress_falls_back_to_plain_lines_without_a_terminal() {
+    let output = lucid(&["1.5", "--progress"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(1, stdout.matches("Still sleeping, ").count(), "{}", stdout);
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn color_is_only_used_when_requested() {
+    let output = lucid(&["0", "--prefix", "worker", "--color", "always"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("[\x1b["), "{:?}", stdout);
+    assert!(stdout.contains("worker\x1b[0m]: "), "{:?}", stdout);
+
+    let output = lucid(&["--color", "always", "--nice", "x"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("\x1b[31mError: "), "{:?}", stderr);
+
+    // Not a terminal
+    let output = lucid(&["0"]);
+    assert!(!output.stdout.contains(&0x1b));
+}
+
+#[cfg(unix)]
+#[test]
+fn log_target_replaces_stdout() {
+    // Messages that syslog can not deliver are dropped silently
+    let output = lucid(&["0", "--log-target", "syslog"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = lucid(&["0", "--log-target", "syslog,stdout"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Woke up after"), "{}", stdout);
+
+    let output = lucid(&["0", "--log-target", "file"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[test]
+fn measure_reports_the_overshoot_in_microseconds() {
+    let output = lucid(&["50ms", "--repeat", "3", "--measure", "--quiet"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(1, lines.len(), "{}", stdout);
+    assert!(
+        lines[0].starts_with("[lucid]: Sleep overshoot: min "),
+        "{}",
+        stdout
+    );
+    assert!(lines[0].ends_with("us (3 samples)"), "{}", stdout);
+
+    let output = lucid(&["0.25", "--measure", "--heartbeat", "100ms"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Heartbeat latency: min "), "{}", stdout);
+}
+
+#[test]
+fn sub_millisecond_durations_are_kept() {
+    let output = lucid(&["0.0005"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Going to sleep for 0.000500s"));
+    assert!(!stdout.contains("Going to sleep for 0.000s"));
+
+    let output = lucid(&["250us"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Going to sleep for 0.000250s"));
+}
+
+#[test]
+fn generate_prints_completions_and_man_page() {
+    let output = lucid(&["--generate", "completions", "bash"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("_lucid()"), "{}", stdout);
+    assert!(stdout.contains("--fork"));
+
+    let output = lucid(&["--generate", "man"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(".TH lucid 1"), "{}", stdout);
+    assert!(stdout.contains("exit\\-code"), "{}", stdout);
+
+    assert_eq!(
+        Some(1),
+        lucid(&["--generate", "completions", "tcsh"]).status.code()
+    );
+    assert_eq!(Some(1), lucid(&["--generate", "html"]).status.code());
+}
+
+#[test]
+fn prefix_placeholders_are_expanded() {
+    let output = lucid(&["0", "--prefix", "w{index}-{pid}"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("[w0-"), "{}", stdout);
+    assert!(!stdout.contains("{pid}"), "{}", stdout);
+
+    let output = lucid(&["0", "--fork", "2", "--prefix", "w{index}/{ppid}"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[w1/"), "{}", stdout);
+    assert!(stdout.contains("[w2/"), "{}", stdout);
+    assert!(!stdout.contains("[w1/0]"), "{}", stdout);
+
+    assert_eq!(Some(1), lucid(&["0", "--prefix", "{host}"]).status.code());
+}
+
+#[test]
+fn still_dreaming_reports_the_time_after_waiting() {
+    let output = lucid(&["0.2", "--verbose"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout
+        .lines()
+        .find(|line| line.contains("Still dreaming after"))
+        .unwrap();
+    assert!(line.ends_with("(100%)"), "{}", line);
+}
+
+#[cfg(unix)]
+#[test]
+fn fork_instances_get_their_own_pidfile() {
+    let path = temp_path("fork-pidfile");
+    let instance_path = |index: usize| PathBuf::from(format!("{}-{}", path.display(), index));
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args([
+            "1",
+            "--quiet",
+            "--fork",
+            "2",
+            "--pidfile",
+            path.to_str().unwrap(),
+        ])
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(500));
+    assert!(instance_path(1).exists());
+    assert!(instance_path(2).exists());
+    assert_ne!(
+        fs::read_to_string(instance_path(1)).unwrap(),
+        fs::read_to_string(instance_path(2)).unwrap()
+    );
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(!path.exists());
+    assert!(!instance_path(1).exists());
+    assert!(!instance_path(2).exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn children_do_not_take_over_the_pidfile() {
+    let path = temp_path("children-pidfile");
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args([
+            "1",
+            "--quiet",
+            "--children",
+            "2",
+            "--pidfile",
+            path.to_str().unwrap(),
+        ])
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(
+        format!("{}\n", child.id()),
+        fs::read_to_string(&path).unwrap()
+    );
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(!path.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn very_long_sleeps_can_be_interrupted() {
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["1e19"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("interrupting sleep"), "{}", stdout);
+}
+
+#[test]
+fn json_format_emits_one_record_per_message() {
+    let output = lucid(&["0.1", "--verbose", "--format", "json", "--prefix", "a\"b"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert!(lines
+        .iter()
+        .all(|line| line.starts_with("{\"prefix\":\"a\\\"b\",\"level\":") && line.ends_with('}')));
+    assert!(lines
+        .iter()
+        .any(|line| line.contains("\"level\":\"info\",\"msg\":\"Going to sleep for 0.100s\"")));
+    assert!(lines
+        .iter()
+        .any(|line| line.contains("\"level\":\"debug\",\"msg\":\"getpid() = ")));
+    assert!(lines.iter().any(|line| line
+        .contains("\"event\":\"sleep_start\",\"duration_ms\":100}")
+        && line.contains(",\"ts\":\"")));
+    assert!(lines
+        .iter()
+        .any(|line| line.contains("\"event\":\"wake\",\"duration_ms\":")));
+}
+
+#[cfg(unix)]
+#[test]
+fn sigusr1_prints_status_without_waking_up() {
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.6", "--quiet"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+    send_signal(&child, "USR1");
+    let pid = child.id();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with(&format!("[lucid]: Status: PID {}, asleep for ", pid)),
+        "{}",
+        stdout
+    );
+    assert!(stdout.trim_end().ends_with(" remaining"));
+}
+
+#[cfg(unix)]
+#[test]
+fn sigusr2_toggles_verbose_output() {
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.8", "--memory", "1M"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+    send_signal(&child, "USR2");
+    thread::sleep(Duration::from_millis(200));
+    send_signal(&child, "USR1");
+    thread::sleep(Duration::from_millis(200));
+    send_signal(&child, "USR2");
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let on = stdout
+        .find("Received SIGUSR2 - verbose output on.")
+        .unwrap();
+    let off = stdout
+        .find("Received SIGUSR2 - verbose output off.")
+        .unwrap();
+    let dreaming = stdout.find("Still dreaming after").unwrap();
+    assert!(on < dreaming && dreaming < off);
+    assert!(!stdout[off..].contains("Still dreaming after"));
+    assert!(stdout.contains(", 0 termination signals, 1.0 MiB allocated, "));
+}
+
+fn actual_seconds(output: &Output) -> f64 {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.split(',').nth(1).unwrap().parse().unwrap()
+}
+
+#[test]
+fn until_sleeps_until_a_wall_clock_deadline() {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap();
+    let deadline = format!("{:.3}", now.as_secs_f64() + 1.3);
+    let output = lucid(&["--until", &deadline, "--format", "csv"]);
+    assert!(output.status.success());
+    let actual = actual_seconds(&output);
+    assert!((1.2..1.4).contains(&actual), "slept for {}s", actual);
+
+    let output = lucid(&["--until", "2000-01-01T00:00:00Z"]);
+    assert!(!output.status.success());
+}
+
+#[cfg(unix)]
+#[test]
+fn sighup_extends_the_sleep() {
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.4", "--extend", "0.3", "--format", "csv"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(150));
+    send_signal(&child, "HUP");
+    thread::sleep(Duration::from_millis(100));
+    send_signal(&child, "HUP");
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert!(actual_seconds(&output) >= 1.0);
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("1.000,"));
+}
+
+fn lucid_with_stdin(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn duration_from_stdin() {
+    let output = lucid_with_stdin(&["--stdin"], "  150ms \nignored\n");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Going to sleep for 0.150s"));
+
+    let output = lucid_with_stdin(&["--stdin"], "soon\n");
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "Error: Could not parse 'duration' argument\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn stdin_modes() {
+    let output = lucid_with_stdin(&["--stdin", "--stdin-mode", "echo"], "0.2\nfirst\nsecond\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("[lucid]: first\n[lucid]: second\n"),
+        "{}",
+        stdout
+    );
+
+    let output = lucid_with_stdin(&["0.2", "--stdin-mode", "consume", "--verbose"], "abc\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Reached the end of stdin after 4 bytes"),
+        "{}",
+        stdout
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn stdin_mode_close_breaks_the_pipe() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["1", "--quiet", "--stdin-mode", "close"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    let err = child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"hello\n")
+        .unwrap_err();
+    assert_eq!(std::io::ErrorKind::BrokenPipe, err.kind());
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn repeat_prints_a_message_between_cycles() {
+    let start = std::time::Instant::now();
+    let output = lucid(&["0.1", "--repeat", "3", "--verbose"]);
+    assert!(start.elapsed().as_secs_f64() >= 0.3);
+    assert_eq!(Some(0), output.status.code());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Cycle 3/3"));
+    assert!(stdout.contains("Finished cycle 1/3"));
+    assert!(stdout.contains("Finished cycle 2/3"));
+    assert!(!stdout.contains("Finished cycle 3/3"));
+}
+
+#[cfg(unix)]
+#[test]
+fn forever_repeats_until_interrupted() {
+    let output = lucid(&["0.05", "--cycles", "2"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Finished cycle 1/2"), "{}", stdout);
+
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.1", "--forever"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(450));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Finished cycle 3\n"), "{}", stdout);
+}
+
+fn lucid_with_env(args: &[&str], vars: &[(&str, &str)]) -> String {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_lucid"));
+    command.args(args);
+    for name in [
+        "LUCID_PREFIX",
+        "LUCID_VERBOSITY",
+        "LUCID_VERBOSE",
+        "LUCID_QUIET",
+    ] {
+        command.env_remove(name);
+    }
+    command.envs(vars.iter().copied());
+    let output = command.output().expect("failed to run lucid");
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn environment_provides_defaults_for_prefix_and_verbosity() {
+    let stdout = lucid_with_env(&["0"], &[("LUCID_PREFIX", "worker")]);
+    assert!(stdout.contains("[worker]"));
+    let stdout = lucid_with_env(&["0", "--prefix", "cli"], &[("LUCID_PREFIX", "worker")]);
+    assert!(stdout.contains("[cli]") && !stdout.contains("[worker]"));
+
+    assert_eq!("", lucid_with_env(&["0"], &[("LUCID_QUIET", "1")]));
+    let stdout = lucid_with_env(&["0"], &[("LUCID_VERBOSE", "1")]);
+    assert!(stdout.contains("getpid()"));
+    let stdout = lucid_with_env(&["0", "--verbose"], &[("LUCID_QUIET", "1")]);
+    assert!(stdout.contains("getpid()"));
+    let stdout = lucid_with_env(&["0", "--quiet"], &[("LUCID_VERBOSE", "1")]);
+    assert_eq!("", stdout);
+
+    let both = [("LUCID_VERBOSE", "1"), ("LUCID_QUIET", "1")];
+    assert_eq!("", lucid_with_env(&["0"], &both));
+}
+
+#[cfg(unix)]
+#[test]
+fn ignored_termination_signals_are_counted() {
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["1", "--no-interrupt"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run lucid");
+    thread::sleep(Duration::from_millis(300));
+    for signal in ["TERM", "INT", "TERM"] {
+        send_signal(&child, signal);
+        thread::sleep(Duration::from_millis(50));
+    }
+    let output = child.wait_with_output().expect("failed to wait for lucid");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(2, stdout.matches("Ignoring SIGTERM from PID ").count());
+    assert_eq!(1, stdout.matches("Ignoring SIGINT from PID ").count());
+    assert!(stdout.contains("Ignored 3 termination signals."));
+}
+
+#[cfg(unix)]
+#[test]
+fn log_file_receives_messages_after_daemonizing() {
+    let path = temp_path("log-file");
+    fs::write(&path, "previous line\n").unwrap();
+
+    let output = lucid(&[
+        "0.2",
+        "--daemon",
+        "--verbose",
+        "--log-file",
+        path.to_str().unwrap(),
+        "--append",
+    ]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let mut content = String::new();
+    for _ in 0..50 {
+        thread::sleep(Duration::from_millis(50));
+        content = fs::read_to_string(&path).unwrap();
+        if content.contains("Woke up after") {
+            break;
+        }
+    }
+    fs::remove_file(&path).ok();
+
+    assert!(content.starts_with("previous line\n"));
+    assert!(content.contains("[lucid]: Daemonizing..\n"));
+    assert!(content.contains("[lucid]: Going to sleep for 0.200s\n"));
+    assert!(content.contains("[lucid]: Woke up after"));
+}
+
+#[test]
+fn log_file_replaces_stdout_and_is_truncated() {
+    let path = temp_path("log-file-truncated");
+    fs::write(&path, "previous line\n").unwrap();
+
+    let output = lucid(&["0.1", "--log-file", path.to_str().unwrap()]);
+    let content = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).ok();
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(content.starts_with("[lucid]: Going to sleep for 0.100s\n"));
+    assert!(content.contains("[lucid]: Woke up after"));
+}
+
+#[test]
+fn message_format_controls_each_line() {
+    let output = lucid(&["0", "--message-format", "{prefix} ({pid}): {msg}"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("lucid ("));
+    assert!(stdout.contains("): Going to sleep for 0.000s\n"));
+
+    let output = lucid(&["0", "--message-format", "{level} {msg}"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[test]
+fn summary_describes_a_completed_sleep() {
+    let output = lucid(&["0.1", "--summary", "-c", "4"]);
+    assert_eq!(Some(4), output.status.code());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let summary = stdout.lines().last().unwrap();
+    assert!(summary.starts_with("{\"requested_s\":0.100,\"actual_s\":0.1"));
+    assert!(summary.ends_with(
+        "\"interrupted\":false,\"reason\":\"elapsed\",\"termination_signals\":0,\"exit_code\":4,\
+         \"signals\":{}}"
+    ));
+
+    let output = lucid(&["0.1", "--summary", "--quiet"]);
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("{\"requested_s\":0.100,"));
+}
+
+#[cfg(unix)]
+#[test]
+fn summary_describes_an_interrupted_sleep() {
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["--summary"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run lucid");
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "USR1");
+    thread::sleep(Duration::from_millis(100));
+    send_signal(&child, "USR1");
+    thread::sleep(Duration::from_millis(100));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().expect("failed to wait for lucid");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let summary = stdout.lines().last().unwrap();
+    assert!(summary.starts_with("{\"requested_s\":null,"));
+    assert!(
+        summary.ends_with(
+            "\"interrupted\":true,\"reason\":\"interrupted\",\"termination_signals\":1,\
+             \"exit_code\":0,\"signals\":{\"SIGUSR1\":2,\"SIGTERM\":1}}"
+        ),
+        "{}",
+        summary
+    );
+}
+
+#[test]
+fn summary_is_not_fatal_when_stdout_is_closed() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.3", "--summary"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn csv_line_is_not_fatal_when_stdout_is_closed() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.3", "--format", "csv", "--csv-header"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn summary_is_printed_before_crashing() {
+    let output = lucid(&[
+        "10",
+        "--crash-after",
+        "100ms",
+        "--crash-mode",
+        "panic",
+        "--summary",
+    ]);
+    assert_eq!(Some(101), output.status.code());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let summary = stdout.lines().last().unwrap();
+    assert!(
+        summary.contains("\"reason\":\"crash\",\"termination_signals\":0,\"exit_code\":101,"),
+        "{}",
+        summary
+    );
+}
+
+#[test]
+fn max_duration_caps_the_sleep() {
+    let output = lucid(&["--max-duration", "200ms", "--format", "csv"]);
+    assert!(output.status.success());
+    let actual = actual_seconds(&output);
+    assert!((0.2..0.3).contains(&actual), "slept for {}s", actual);
+
+    let output = lucid(&["10", "--max-duration", "0.1", "--verbose"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Capping the sleep of 10.000s at 0.100s"));
+
+    let output = lucid(&["0.1", "--max-duration", "10", "--verbose"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("--max-duration"));
+}
+
+#[cfg(unix)]
+#[test]
+fn termination_during_grace_period_is_deferred() {
+    let start = std::time::Instant::now();
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["10", "--grace", "1"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run lucid");
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().expect("failed to wait for lucid");
+    let elapsed = start.elapsed().as_secs_f64();
+
+    assert!((1.0..1.5).contains(&elapsed), "exited after {}s", elapsed);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("during the grace period - deferring it."));
+    assert!(stdout.contains("Caught SIGTERM from PID "));
+    assert!(stdout.contains(" - interrupting sleep."));
+}
+
+#[test]
+fn wait_for_wakes_up_when_the_file_appears() {
+    let path = temp_path("wait-for");
+    fs::remove_file(&path).ok();
+
+    let creator = {
+        let path = path.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            fs::write(path, "").unwrap();
+        })
+    };
+    let output = lucid(&["10", "--wait-for", path.to_str().unwrap()]);
+    creator.join().unwrap();
+    fs::remove_file(&path).ok();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("Done waiting for '{}' to appear.", path.display())));
+
+    let output = lucid(&["0.2", "--wait-for", path.to_str().unwrap()]);
+    assert_eq!(Some(124), output.status.code());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Timed out while waiting for"));
+}
+
+#[cfg(unix)]
+#[test]
+fn per_signal_ignore_and_exit_on() {
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args([
+            "10",
+            "--ignore",
+            "SIGTERM,SIGHUP",
+            "--exit-on",
+            "SIGUSR2=42",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run lucid");
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "TERM");
+    send_signal(&child, "HUP");
+    thread::sleep(Duration::from_millis(200));
+    send_signal(&child, "USR2");
+    let output = child.wait_with_output().expect("failed to wait for lucid");
+
+    assert_eq!(Some(42), output.status.code());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Ignoring SIGTERM."));
+    assert!(stdout.contains("Ignoring SIGHUP."));
+    assert!(stdout.contains("Received SIGUSR2 - waking up."));
+
+    let output = lucid(&["1", "--ignore", "USR2", "--exit-on", "USR2"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[cfg(unix)]
+#[test]
+fn shutdown_delay_postpones_the_exit() {
+    let start = std::time::Instant::now();
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["10", "--shutdown-delay", "700ms"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run lucid");
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "TERM");
+    thread::sleep(Duration::from_millis(200));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().expect("failed to wait for lucid");
+    let elapsed = start.elapsed().as_secs_f64();
+
+    assert!((1.0..1.5).contains(&elapsed), "exited after {}s", elapsed);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Shutting down... (this takes 0.700s)"));
+    assert!(stdout.contains(" while shutting down - ignoring it."));
+}
+
+#[cfg(unix)]
+#[test]
+fn die_after_signals_exits_on_the_nth_signal() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["10", "--die-after-signals", "3"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run lucid");
+    thread::sleep(Duration::from_millis(300));
+    for _ in 0..2 {
+        send_signal(&child, "TERM");
+        thread::sleep(Duration::from_millis(200));
+    }
+    assert!(child.try_wait().unwrap().is_none());
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().expect("failed to wait for lucid");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("(1/3)."));
+    assert!(stdout.contains("(2/3)."));
+    assert!(stdout.contains(" - interrupting sleep."));
+
+    let output = lucid(&["1", "--die-after-signals", "0"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[test]
+fn busy_mode_keeps_threads_busy_for_the_duration() {
+    let output = lucid(&["0.3", "--busy", "--cpus", "2", "--load", "50%"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Keeping 2 threads busy at 50% load"));
+
+    let output = lucid(&["0.1", "--busy", "--cpus", "0"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[test]
+fn memory_is_allocated_and_leaked() {
+    let output = lucid(&["0.3", "--memory", "8M", "--leak-rate", "10M/s", "--verbose"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Allocated 8.0 MiB\n"));
+    assert!(stdout.contains(" MiB in total"));
+
+    let output = lucid(&["0.1", "--memory", "8X"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[test]
+fn script_runs_its_timeline() {
+    let start = std::time::Instant::now();
+    let output = lucid(&[
+        "--quiet",
+        "--script",
+        "print \"ready\"; sleep 200ms; stderr \"warning; careful\"; sleep 100ms; exit 7",
+    ]);
+    assert!(start.elapsed().as_secs_f64() >= 0.3);
+    assert_eq!(Some(7), output.status.code());
+    assert_eq!("ready\n", String::from_utf8_lossy(&output.stdout));
+    assert_eq!(
+        "warning; careful\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = lucid(&["--script", "sleep 1; jump"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid script step 'jump'"));
+}
+
+#[test]
+fn children_form_a_process_tree() {
+    let output = lucid(&["0.3", "--children", "2", "--depth", "2", "--verbose"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(6, stdout.matches("Spawned child process with PID ").count());
+    assert_eq!(6, stdout.matches("Reaped child process ").count());
+
+    let output = lucid(&["0.1", "--children", "many"]);
+    assert_eq!(Some(1), output.status.code());
+}
+
+#[cfg(unix)]
+#[test]
+fn children_are_terminated_with_the_parent() {
+    let start = std::time::Instant::now();
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["10", "--children", "2", "--verbose"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run lucid");
+    thread::sleep(Duration::from_millis(300));
+    send_signal(&child, "TERM");
+    let output = child.wait_with_output().expect("failed to wait for lucid");
+
+    assert!(start.elapsed().as_secs_f64() < 5.0);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(2, stdout.matches("Reaped child process ").count());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn zombies_stay_around_until_exit() {
+    use std::io::{BufRead, BufReader};
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.5", "--zombies", "2", "--verbose"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run lucid");
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let pids: Vec<String> = lines
+        .by_ref()
+        .map(|line| line.unwrap())
+        .filter_map(|line| {
+            line.split("zombie process with PID ")
+                .nth(1)
+                .map(String::from)
+        })
+        .take(2)
+        .collect();
+    thread::sleep(Duration::from_millis(100));
+    for pid in &pids {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap();
+        assert!(stat.contains(") Z "), "{}", stat);
+    }
+
+    let rest: Vec<String> = lines.map(|line| line.unwrap()).collect();
+    assert!(child.wait().unwrap().success());
+    assert!(rest
+        .iter()
+        .any(|line| line.ends_with("Reaped 2 zombie processes")));
+}
+
+#[cfg(unix)]
+#[test]
+fn orphan_is_reparented() {
+    let pidfile = temp_path("orphan-pidfile");
+    let log_file = temp_path("orphan-log");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.5", "--orphan", "--verbose", "--pidfile"])
+        .arg(&pidfile)
+        .arg("--log-file")
+        .arg(&log_file)
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("failed to run lucid");
+    assert!(child.wait().unwrap().success());
+
+    thread::sleep(Duration::from_millis(200));
+    let orphan = fs::read_to_string(&pidfile).unwrap();
+    assert_ne!(child.id().to_string(), orphan.trim());
+
+    thread::sleep(Duration::from_millis(600));
+    assert!(!pidfile.exists());
+    let log = fs::read_to_string(&log_file).unwrap();
+    fs::remove_file(&log_file).unwrap();
+    assert!(log.contains(&format!(
+        "Leaving orphan process with PID {} behind",
+        orphan.trim()
+    )));
+    assert!(log.contains("(after being orphaned)"));
+}
+
+#[test]
+fn crash_after_terminates_abnormally() {
+    let start = std::time::Instant::now();
+    let output = lucid(&["10", "--crash-after", "200ms", "--crash-mode", "panic"]);
+    assert!(start.elapsed().as_secs_f64() < 5.0);
+    assert_eq!(Some(101), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Crashing (panic)"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("simulated crash"));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        let output = lucid(&["10", "--crash-after", "100ms"]);
+        assert_eq!(Some(6), output.status.signal());
+        let output = lucid(&["10", "--crash-after", "100ms", "--crash-mode", "segfault"]);
+        assert_eq!(Some(11), output.status.signal());
+    }
+}
+
+#[test]
+fn timestamp_styles() {
+    let output = lucid(&["0.1", "--timestamp=elapsed"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("[0.0"), "{}", stdout);
+    assert!(stdout.contains("s] [lucid]: Woke up"));
+
+    let output = lucid(&["0.1", "--timestamp=unix"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let seconds = stdout[1..].split(']').next().unwrap();
+    assert!(seconds.parse::<f64>().unwrap() > 1.6e9);
+
+    let output = lucid(&["--timestamp", "0.1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(Some("T"), stdout.get(11..12), "{}", stdout);
+    assert_eq!(Some("] [lucid]"), stdout.get(30..39), "{}", stdout);
+
+    let output = lucid(&["0.1", "--timestamp=ms"]);
+    assert!(!output.status.success());
+}
+
+#[cfg(unix)]
+#[test]
+fn notify_speaks_the_sd_notify_protocol() {
+    use std::os::unix::net::UnixDatagram;
+
+    let path = temp_path("notify-socket");
+    fs::remove_file(&path).ok();
+    let socket = UnixDatagram::bind(&path).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let start = std::time::Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["0.6", "--notify", "--ready-after", "200ms"])
+        .env("NOTIFY_SOCKET", &path)
+        .env("WATCHDOG_USEC", "200000")
+        .env_remove("WATCHDOG_PID")
+        .output()
+        .expect("failed to run lucid");
+    assert!(output.status.success());
+
+    let mut messages = vec![];
+    let mut buffer = [0; 64];
+    while messages.last().map(String::as_str) != Some("STOPPING=1") {
+        let len = socket.recv(&mut buffer).unwrap();
+        messages.push(String::from_utf8_lossy(&buffer[..len]).into_owned());
+    }
+    fs::remove_file(&path).ok();
+
+    assert!(start.elapsed() >= Duration::from_millis(600));
+    let ready = messages.iter().position(|m| m == "READY=1").unwrap();
+    // Pings every 100ms, the first of which precede READY=1
+    assert!(ready >= 1, "{:?}", messages);
+    assert!(messages.iter().filter(|m| *m == "WATCHDOG=1").count() >= 4);
+}
+
+#[cfg(unix)]
+#[test]
+fn reload_on_hup_rereads_the_script() {
+    let path = temp_path("reload-script");
+    fs::write(&path, "sleep 600ms; exit 3").unwrap();
+
+    let start = std::time::Instant::now();
+    let child = Command::new(env!("CARGO_BIN_EXE_lucid"))
+        .args(["--reload-on-hup", "--verbose", "--script-file"])
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run lucid");
+    thread::sleep(Duration::from_millis(300));
+    fs::write(&path, "sleep 400ms; exit 4").unwrap();
+    send_signal(&child, "HUP");
+    let output = child.wait_with_output().expect("failed to wait for lucid");
+    let elapsed = start.elapsed().as_secs_f64();
+    fs::remove_file(&path).ok();
+
+    assert_eq!(Some(4), output.status.code());
+    assert!((0.7..1.2).contains(&elapsed), "exited after {}s", elapsed);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Received SIGHUP - reloading configuration."));
+    assert!(stdout.contains("Reloaded the script from '"));
+}